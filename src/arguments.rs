@@ -1,20 +1,196 @@
-use clap::{arg, Parser, ValueEnum};
+use crate::config::Config;
+use crate::header::Header;
+use clap::{Parser, Subcommand, ValueEnum};
+use serde::{Deserialize, Deserializer};
 
 #[derive(Parser, Debug)]
-#[command(author, about, long_about = None, version)]
+#[command(author, about, long_about = None, version, args_conflicts_with_subcommands = true)]
 pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
     /// The brainfuck source code file to run (if not will be entered in REPL mode)
     #[arg(default_value = None)]
     pub source: Option<String>,
     /// The extra features to enable
     #[arg(short, long, default_value = None)]
     pub features: Option<Vec<Feature>>,
-    /// The brainfuck array size
-    #[arg(short, long, default_value = "30000")]
-    pub array_size: usize,
-    /// Dont print the tiles (e.g. exit code, file name, etc)
-    #[arg(short, long)]
-    pub without_tiles: bool,
+    /// The brainfuck array size (30000 if it is not given here, in the source file header or in the config files)
+    #[arg(short, long, default_value = None)]
+    pub array_size: Option<usize>,
+    /// Dont print the tiles (e.g. exit code, file name, etc), `--without-tiles=false` prints them
+    /// even if the source file header or the config files disable them
+    #[arg(short, long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    pub without_tiles: Option<bool>,
+    /// What `,` does when there is no more input (`error` if it is not given here or in the config files),
+    /// the compiled programs only exit with the error
+    #[arg(short, long, default_value = None)]
+    pub eof: Option<Eof>,
+    /// The language of the source code (by default from the file extension, e.g. `.ook`)
+    #[arg(short, long, default_value = None, requires = "source")]
+    pub lang: Option<Lang>,
+    /// The definition file of the dialect of the source code, the tokens of the commands (see the README)
+    #[arg(short, long, default_value = None, requires = "source", conflicts_with = "lang")]
+    pub dialect: Option<String>,
+    /// Compile the source code to native code before running it (x86-64 linux only, otherwise it will be interpreted)
+    #[cfg(feature = "jit")]
+    #[arg(short, long, requires = "source")]
+    pub jit: bool,
+    /// Compile the source code to the given target instead of running it
+    #[arg(short, long, default_value = None, requires = "source")]
+    pub target: Option<Target>,
+    /// The file to write the compiled code to (if not will be printed to stdout)
+    #[arg(short, long, default_value = None, requires = "target")]
+    pub output: Option<String>,
+    /// The directory of the files of the `file-io` feature, the other files can't be opened
+    #[arg(long, default_value = None)]
+    pub file_root: Option<String>,
+    /// Analyse the source code before running it, and refuse to run it if it has definite errors
+    #[arg(short, long, requires = "source")]
+    pub safe: bool,
+}
+
+/// The array size if it is not given in the command line, in the source file header or in the config files
+pub const DEFAULT_ARRAY_SIZE: usize = 30000;
+
+impl Args {
+    /// Take the options that are not given in the command line from the source file header
+    pub fn apply_header(&mut self, header: Header) {
+        if self.features.is_none() {
+            self.features = header.features;
+        }
+        if self.array_size.is_none() {
+            self.array_size = header.array_size;
+        }
+        if self.without_tiles.is_none() && header.without_tiles {
+            self.without_tiles = Some(true);
+        }
+    }
+
+    /// Take the options that are not given in the command line or in the source file header from the config files
+    pub fn apply_config(&mut self, config: &Config) {
+        if self.features.is_none() {
+            self.features.clone_from(&config.features);
+        }
+        if self.array_size.is_none() {
+            self.array_size = config.array_size;
+        }
+        if self.eof.is_none() {
+            self.eof = config.eof;
+        }
+        if self.without_tiles.is_none() {
+            self.without_tiles = Some(config.without_tiles);
+        }
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Decompile the brainfuck source code to a readable pseudo-code
+    Decompile {
+        /// The brainfuck source code file to decompile (the header features with extra commands can't be decompiled)
+        source: String,
+        /// The file to write the pseudo-code to (if not will be printed to stdout)
+        #[arg(short, long, default_value = None)]
+        output: Option<String>,
+    },
+    /// Format the brainfuck source code, indent the loops and keep the comments after their code
+    Fmt {
+        /// The brainfuck source code file to format
+        source: String,
+        /// The file to write the formatted code to (if not will be printed to stdout)
+        #[arg(short, long, default_value = None, conflicts_with = "check")]
+        output: Option<String>,
+        /// Don't print the formatted code, exit with code 1 if the file is not formatted
+        #[arg(short, long)]
+        check: bool,
+    },
+    /// Minify the brainfuck source code to the shortest equivalent program on one line
+    Minify {
+        /// The brainfuck source code file to minify
+        source: String,
+        /// The extra features to follow, or the ones in the source file header (the `+-` runs are only cancelled with `nrv`, and the `<>` runs with `rp`)
+        #[arg(short, long, default_value = None)]
+        features: Option<Vec<Feature>>,
+        /// The brainfuck array size (30000 if it is not given here, in the source file header or in the config files)
+        #[arg(short, long, default_value = None)]
+        array_size: Option<usize>,
+        /// The file to write the minified code to (if not will be printed to stdout)
+        #[arg(short, long, default_value = None)]
+        output: Option<String>,
+    },
+    /// Lint the brainfuck source code for the common mistakes, exit with code 1 if there are warnings.
+    /// The lints on a line can be suppressed with a `bfy:allow(L001 L002)` comment on it or on the line before it
+    Lint {
+        /// The brainfuck source code file to lint
+        source: String,
+        /// The extra features, or the ones in the source file header (their commands are not typos)
+        #[arg(short, long, default_value = None)]
+        features: Option<Vec<Feature>>,
+        /// The codes of the lints to suppress in all the file (e.g. `L001`)
+        #[arg(short = 'A', long, default_value = None)]
+        allow: Option<Vec<String>>,
+    },
+    /// Analyse the brainfuck source code without running it, for the infinite loops and the out of bounds errors.
+    /// Exit with code 1 if there are definite errors
+    Analyze {
+        /// The brainfuck source code file to analyse
+        source: String,
+        /// The extra features to follow, or the ones in the source file header
+        #[arg(short, long, default_value = None)]
+        features: Option<Vec<Feature>>,
+        /// The brainfuck array size (30000 if it is not given here, in the source file header or in the config files)
+        #[arg(short, long, default_value = None)]
+        array_size: Option<usize>,
+    },
+    /// Generate a brainfuck program that prints the text, with the multiplication loops and the cells reused across the characters
+    Gen {
+        /// The text to print
+        #[arg(short, long)]
+        text: String,
+        /// The extra features to follow (`utf8` allows the characters above 255, and the values wrap with `nrv`)
+        #[arg(short, long, default_value = None)]
+        features: Option<Vec<Feature>>,
+        /// Search harder for a shorter program, it is slower
+        #[arg(short = 'O', long)]
+        optimize: bool,
+        /// The file to write the generated code to (if not will be printed to stdout)
+        #[arg(short, long, default_value = None)]
+        output: Option<String>,
+    },
+    /// Compile a program of the bfy language (a tiny language with variables, if, while, arithmetic and print) to brainfuck
+    Build {
+        /// The bfy language source code file to compile
+        source: String,
+        /// The extra features the brainfuck code will run with (`utf8` for the bigger values and the characters)
+        #[arg(short, long, default_value = None)]
+        features: Option<Vec<Feature>>,
+        /// Format the brainfuck code, otherwise it is on one line
+        #[arg(short, long)]
+        pretty: bool,
+        /// The file to write the brainfuck code to (if not will be printed to stdout)
+        #[arg(short, long, default_value = None)]
+        output: Option<String>,
+    },
+    /// Convert the source code between brainfuck and its dialects (e.g. Ook!, or a dialect definition file)
+    Convert {
+        /// The source code file to convert
+        source: String,
+        /// The language to convert to
+        #[arg(short, long, default_value = None, required_unless_present = "to_dialect")]
+        to: Option<Lang>,
+        /// The definition file of the dialect to convert to
+        #[arg(short = 'D', long, default_value = None, conflicts_with = "to")]
+        to_dialect: Option<String>,
+        /// The language of the source code (by default from the file extension, e.g. `.ook`)
+        #[arg(short, long, default_value = None)]
+        lang: Option<Lang>,
+        /// The definition file of the dialect of the source code
+        #[arg(short, long, default_value = None, conflicts_with = "lang")]
+        dialect: Option<String>,
+        /// The file to write the converted code to (if not will be printed to stdout)
+        #[arg(short, long, default_value = None)]
+        output: Option<String>,
+    },
 }
 
 #[derive(Debug, PartialEq, Copy, Clone, ValueEnum)]
@@ -37,4 +213,122 @@ pub enum Feature {
     /// The alias are: `utf8`
     #[clap(alias = "utf8")]
     AllowUtf8,
+    /// The pbrain procedures, `(` starts the procedure numbered by the current cell value and `)` ends it,
+    /// and `:` calls the procedure numbered by the current cell value. The procedures are run by the interpreter only.
+    Pbrain,
+    /// The brainfork threads, `Y` forks the current thread: the parent sets its current cell to 0,
+    /// and the child moves its pointer one cell to the right and sets it to 1.
+    /// The threads share the cells and run one command each in turn. They are run by the interpreter only.
+    Brainfork,
+    /// The Extended Type I commands: `@` exits, `$` stores the cell in the storage and `!` loads it back,
+    /// `}` and `{` shift the cell bits right and left, `~` inverts them, and `^`, `&` and `|` are the xor, the and
+    /// and the or of the cell with the storage. The commands are run by the interpreter only.
+    /// The alias are: `ext1`
+    #[clap(alias = "ext1")]
+    ExtendedType1,
+    /// `@` ends the program, and the current cell value is the exit code of the process.
+    /// The alias are: `exit`
+    #[clap(alias = "exit")]
+    ExitCode,
+    /// The file commands, `%` opens the file named by the zero-terminated string after the current cell
+    /// (the current cell is the mode: 0 to read, 1 to write and 2 to append), `_` reads a byte of the file,
+    /// `;` writes the current cell to the file, and `=` closes it. The files must be in the `--file-root` directory.
+    /// The alias are: `fio`
+    #[clap(alias = "fio")]
+    FileIo,
+    /// `#` prints the pointer and the cells around it to stderr, without the feature it is a comment.
+    /// The alias are: `dbg`
+    #[clap(alias = "dbg")]
+    Debug,
+}
+
+impl Feature {
+    /// The extra commands of the feature, the other features change the behaviour of the 8 commands
+    pub fn commands(&self) -> &'static str {
+        match self {
+            Feature::Pbrain => "():",
+            Feature::Brainfork => "Y",
+            Feature::ExtendedType1 => "@$!}{~^&|",
+            Feature::ExitCode => "@",
+            Feature::FileIo => "%_;=",
+            Feature::Debug => "#",
+            _ => "",
+        }
+    }
+}
+
+/// The feature names in the config files, the same names and aliases as the command line
+impl<'de> Deserialize<'de> for Feature {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Feature::from_str(&name, true)
+            .map_err(|_| serde::de::Error::custom(format!("unknown feature `{}`", name)))
+    }
+}
+
+#[derive(Debug, PartialEq, Copy, Clone, Default, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Eof {
+    /// Exit with the IO error (code 13)
+    #[default]
+    Error,
+    /// Set the cell to 0
+    Zero,
+    /// Leave the cell unchanged
+    Unchanged,
+}
+
+#[derive(Debug, PartialEq, Copy, Clone, Default, ValueEnum)]
+pub enum Lang {
+    /// The brainfuck commands, the other chars are comments.
+    /// The alias are: `bf`
+    #[default]
+    #[clap(alias = "bf")]
+    Brainfuck,
+    /// Every command is a pair of the `Ook.`, `Ook?` and `Ook!` tokens (e.g. `Ook. Ook?` is `>`),
+    /// the `.ook` files are Ook! by default
+    Ook,
+    /// The tape of bits: `+` flips the current bit, `,` reads a bit and `;` writes a bit (the least significant bits first),
+    /// `<`, `>`, `[` and `]` are the brainfuck commands
+    Boolfuck,
+    /// The tape of bits without the input and output: `*` flips the current bit,
+    /// `<`, `>`, `[` and `]` are the brainfuck commands
+    Smallfuck,
+}
+
+impl Lang {
+    /// The language runs on a tape of bits, the interpreter runs it on the bit cells
+    pub fn bit_tape(&self) -> bool {
+        matches!(self, Lang::Boolfuck | Lang::Smallfuck)
+    }
+}
+
+#[derive(Debug, PartialEq, Copy, Clone, ValueEnum)]
+pub enum Target {
+    /// A self-contained rust program, it exits with the same codes as the interpreter errors.
+    /// The alias are: `rs`
+    #[clap(alias = "rs")]
+    Rust,
+    /// A rust module that exposes the `run` and `run_with` functions, to be included in your crate.
+    /// The alias are: `rs-mod`
+    #[clap(alias = "rs-mod")]
+    RustModule,
+    /// A WebAssembly module in the text format, `.` and `,` are imported as `bfy.output` and `bfy.input`,
+    /// and the exported `run` function returns the same codes as the interpreter errors.
+    Wat,
+    /// A WebAssembly module in the binary format, the same module as the `wat` target.
+    Wasm,
+    /// x86-64 GNU assembler source code for linux, it uses the syscalls for the I/O
+    /// and exits with the same codes as the interpreter errors.
+    /// The alias are: `s`
+    #[clap(alias = "s")]
+    Asm,
+    /// A static x86-64 linux executable, the `asm` target assembled and linked with `as` and `ld`
+    /// (no C toolchain is needed). The output file defaults to the source file name without the extension.
+    Elf,
+    /// A textual LLVM IR module, the `main` function returns the same codes as the interpreter errors
+    /// and the I/O uses the `read` and `write` functions of libc.
+    /// The alias are: `ll`
+    #[clap(alias = "ll")]
+    Llvm,
 }
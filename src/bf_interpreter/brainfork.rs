@@ -0,0 +1,285 @@
+use crate::bf_interpreter::cell::Cell;
+use crate::bf_interpreter::error::{InterpreterError, InterpreterErrorKind};
+use crate::bf_interpreter::file_io::FileCommand;
+use crate::bf_interpreter::interpreter::{BfCommand, ExtendedCommand, Interpreter, MAX_CALL_DEPTH};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+// Brainfork runs threads that share the cells, `Y` forks the current thread: the parent sets
+// its current cell to 0, and the child moves its pointer one cell to the right and sets it to 1.
+// A thread can stop in the middle of a loop, so the commands are flattened to the jumps,
+// and the threads run one instruction each in turn, in the order they were forked
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Instr {
+    IncPtr,
+    DecPtr,
+    IncVal,
+    DecVal,
+    Print,
+    Read,
+    /// The start of a loop, jump after its end if the current cell is zero
+    JumpIfZero(usize),
+    /// The end of a loop, jump after its start if the current cell isn't zero
+    JumpIfNotZero(usize),
+    /// Define the pbrain procedure that starts after it, and jump after its end,
+    /// with the index of its body in the bodies
+    Define(usize, usize),
+    Return,
+    Call,
+    Fork,
+    Extended(ExtendedCommand),
+    File(FileCommand),
+    Debug,
+    /// The end of the program, the threads that reach it stop
+    End,
+}
+
+/// The flattened program, the procedures of the earlier runs are appended after its end when they are called
+#[derive(Default)]
+struct Program {
+    instrs: Vec<Instr>,
+    /// The bodies of the procedures, the interpreter keeps them for the next runs
+    bodies: Vec<Rc<Vec<BfCommand>>>,
+    /// The index of the first instruction of the procedures by their numbers
+    procedures: HashMap<u32, usize>,
+}
+
+#[derive(Debug, Clone)]
+struct Thread {
+    pointer: usize,
+    /// The index of the next instruction
+    next: usize,
+    /// The return indexes of the running procedures
+    calls: Vec<usize>,
+}
+
+/// Run the brainfork program until all the threads end, the procedures of the earlier runs can be called
+/// # Arguments
+/// * `interpreter` - The interpreter that holds the cells and the features, its pointer is the first thread's
+/// * `bf_commands` - The parsed program
+pub fn run(
+    interpreter: &mut Interpreter,
+    bf_commands: &[BfCommand],
+) -> Result<(), InterpreterError> {
+    let mut program = Program::default();
+    program.flatten(bf_commands);
+    program.instrs.push(Instr::End);
+    let mut threads = vec![Thread {
+        pointer: interpreter.pointer,
+        next: 0,
+        calls: Vec::new(),
+    }];
+
+    let result = run_threads(interpreter, &mut program, &mut threads);
+    // The threads keep their pointers after an error too, like the interpreter does
+    interpreter.pointer = threads[0].pointer;
+    interpreter.thread_pointers = threads.iter().map(|thread| thread.pointer).collect();
+    result
+}
+
+fn run_threads(
+    interpreter: &mut Interpreter,
+    program: &mut Program,
+    threads: &mut Vec<Thread>,
+) -> Result<(), InterpreterError> {
+    let mut running = true;
+    while running && interpreter.exit_code.is_none() {
+        running = false;
+        // The forked threads run in the same round, after the threads before them
+        let mut index = 0;
+        while index < threads.len() && interpreter.exit_code.is_none() {
+            if program.instrs[threads[index].next] != Instr::End {
+                running = true;
+                let forked = step(interpreter, program, &mut threads[index])?;
+                threads.extend(forked);
+            }
+            index += 1;
+        }
+    }
+    Ok(())
+}
+
+impl Program {
+    fn flatten(&mut self, bf_commands: &[BfCommand]) {
+        for command in bf_commands {
+            match command {
+                BfCommand::IncPtr => self.instrs.push(Instr::IncPtr),
+                BfCommand::DecPtr => self.instrs.push(Instr::DecPtr),
+                BfCommand::IncVal => self.instrs.push(Instr::IncVal),
+                BfCommand::DecVal => self.instrs.push(Instr::DecVal),
+                BfCommand::Print => self.instrs.push(Instr::Print),
+                BfCommand::Read => self.instrs.push(Instr::Read),
+                BfCommand::Loop(loop_body) => {
+                    let start = self.instrs.len();
+                    self.instrs.push(Instr::JumpIfZero(0));
+                    self.flatten(loop_body);
+                    self.instrs.push(Instr::JumpIfNotZero(start + 1));
+                    self.instrs[start] = Instr::JumpIfZero(self.instrs.len());
+                }
+                BfCommand::Procedure(body) => {
+                    let start = self.instrs.len();
+                    self.instrs.push(Instr::Define(0, 0));
+                    self.flatten(body);
+                    self.instrs.push(Instr::Return);
+                    self.instrs[start] = Instr::Define(self.instrs.len(), self.bodies.len());
+                    self.bodies.push(Rc::new(body.clone()));
+                }
+                BfCommand::Call => self.instrs.push(Instr::Call),
+                BfCommand::Fork => self.instrs.push(Instr::Fork),
+                BfCommand::Extended(command) => self.instrs.push(Instr::Extended(*command)),
+                BfCommand::File(command) => self.instrs.push(Instr::File(*command)),
+                BfCommand::Debug => self.instrs.push(Instr::Debug),
+            }
+        }
+    }
+
+    /// The first instruction of the procedure, the procedures of the earlier runs are appended
+    /// to the program at their first call
+    fn procedure(&mut self, interpreter: &Interpreter, procedure: u32) -> Option<usize> {
+        if let Some(start) = self.procedures.get(&procedure) {
+            return Some(*start);
+        }
+        let body = interpreter.procedures.get(&procedure)?.clone();
+        let start = self.instrs.len();
+        self.flatten(&body);
+        self.instrs.push(Instr::Return);
+        self.procedures.insert(procedure, start);
+        Some(start)
+    }
+}
+
+/// Run the next instruction of the thread
+/// # Returns
+/// The forked thread, if the instruction is `Y`
+fn step(
+    interpreter: &mut Interpreter,
+    program: &mut Program,
+    thread: &mut Thread,
+) -> Result<Option<Thread>, InterpreterError> {
+    interpreter.pointer = thread.pointer;
+    let instr = program.instrs[thread.next];
+    trace!("Thread at {}: {:?}", thread.pointer, instr);
+    thread.next += 1;
+    let value = interpreter.cells[interpreter.pointer].get_value_utf8();
+    let mut forked = None;
+    match instr {
+        Instr::IncPtr => interpreter.increment_pointer()?,
+        Instr::DecPtr => interpreter.decrement_pointer()?,
+        Instr::IncVal => interpreter.increment_value()?,
+        Instr::DecVal => interpreter.decrement_value()?,
+        Instr::Print => interpreter.output_value()?,
+        Instr::Read => interpreter.input_value()?,
+        Instr::JumpIfZero(end) if value == 0 => thread.next = end,
+        Instr::JumpIfNotZero(start) if value != 0 => thread.next = start,
+        Instr::JumpIfZero(_) | Instr::JumpIfNotZero(_) => {}
+        Instr::Define(end, body) => {
+            program.procedures.insert(value, thread.next);
+            interpreter
+                .procedures
+                .insert(value, program.bodies[body].clone());
+            thread.next = end;
+        }
+        Instr::Return => {
+            thread.next = thread
+                .calls
+                .pop()
+                .expect("The procedures are only entered by the calls");
+        }
+        Instr::Call => {
+            let Some(start) = program.procedure(interpreter, value) else {
+                return Err(InterpreterErrorKind::UndefinedProcedure(value).to_error());
+            };
+            if thread.calls.len() == MAX_CALL_DEPTH {
+                return Err(InterpreterErrorKind::CallStackOverflow(MAX_CALL_DEPTH).to_error());
+            }
+            thread.calls.push(thread.next);
+            thread.next = start;
+        }
+        Instr::Fork => {
+            interpreter.cells[thread.pointer] = Cell::default_cell(&interpreter.features);
+            interpreter.increment_pointer()?;
+            interpreter.cells[interpreter.pointer] = Cell::new(1, &interpreter.features);
+            forked = Some(Thread {
+                pointer: interpreter.pointer,
+                next: thread.next,
+                calls: thread.calls.clone(),
+            });
+            interpreter.pointer = thread.pointer;
+        }
+        Instr::Extended(command) => interpreter.extended(command)?,
+        Instr::File(command) => interpreter.file(command)?,
+        Instr::Debug => interpreter.debug(),
+        Instr::End => unreachable!("The threads stop at the end"),
+    }
+    thread.pointer = interpreter.pointer;
+    Ok(forked)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::arguments::Feature;
+    use crate::bf_interpreter::cell::Cell;
+    use crate::bf_interpreter::error::InterpreterErrorKind;
+    use crate::bf_interpreter::interpreter::Interpreter;
+    use console::Term;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn fork_the_threads() {
+        let features = vec![Feature::Brainfork];
+        let mut interpreter = Interpreter::new(8, features.clone(), Term::stdout());
+
+        // The parent skips the loop, the child adds 2 to the next cell, then both add 1 to their cells
+        assert_eq!(interpreter.run(String::from("Y[>++<-]+")), Ok(0));
+        let cells = [1, 1, 2, 0].map(|value| Cell::new(value, &features));
+        assert_eq!(interpreter.cells[..4], cells);
+        assert_eq!(interpreter.pointer, 0);
+        assert_eq!(interpreter.thread_pointers, vec![0, 1]);
+
+        // The child runs its first instruction in the round of the fork, so it adds 1 to the cell 1
+        // before the parent adds 1 to the cell 0, and the parent sees the cell 1 set when it moves there
+        interpreter.reset();
+        assert_eq!(interpreter.run(String::from("Y+>[-<+>]>")), Ok(0));
+        let cells = [3, 0, 0, 0].map(|value| Cell::new(value, &features));
+        assert_eq!(interpreter.cells[..4], cells);
+        assert_eq!(interpreter.thread_pointers, vec![2, 3]);
+
+        let mut interpreter = Interpreter::new(1, features.clone(), Term::stdout());
+        assert_eq!(
+            interpreter.run(String::from("Y")),
+            Err(InterpreterErrorKind::PointerOutOfBounds(1).to_error())
+        );
+
+        // The threads keep their pointers after an error
+        let mut interpreter = Interpreter::new(3, features, Term::stdout());
+        assert_eq!(
+            interpreter.run(String::from("Y>>")),
+            Err(InterpreterErrorKind::PointerOutOfBounds(3).to_error())
+        );
+        assert_eq!(interpreter.pointer, 1);
+        assert_eq!(interpreter.thread_pointers, vec![1, 2]);
+
+        // The forks are comments without the feature
+        let mut interpreter = Interpreter::new(8, vec![], Term::stdout());
+        assert_eq!(interpreter.run(String::from("+Y")), Ok(0));
+        assert_eq!(interpreter.thread_pointers, Vec::<usize>::new());
+    }
+
+    #[test]
+    fn call_the_procedures_of_the_earlier_runs() {
+        let features = vec![Feature::Brainfork, Feature::Pbrain];
+        let mut interpreter = Interpreter::new(8, features.clone(), Term::stdout());
+
+        // Like in the REPL, the procedure 1 adds 2 to the next cell and the next run calls it
+        assert_eq!(interpreter.run(String::from("+(>++<)")), Ok(0));
+        assert_eq!(interpreter.run(String::from(":")), Ok(0));
+        assert_eq!(interpreter.cells[1], Cell::new(2, &features));
+
+        // The procedures defined by the threads are kept for the next runs too
+        assert_eq!(interpreter.run(String::from("(>+++<):")), Ok(0));
+        assert_eq!(interpreter.cells[1], Cell::new(5, &features));
+        assert_eq!(interpreter.run(String::from(":")), Ok(0));
+        assert_eq!(interpreter.cells[1], Cell::new(8, &features));
+    }
+}
@@ -5,6 +5,8 @@ use crate::bf_interpreter::error::{InterpreterError, InterpreterErrorKind};
 pub enum Cell {
     Byte(u8),
     Utf8(u32),
+    /// The cells of the bit tape languages, incrementing or decrementing flips the bit
+    Bit(bool),
 }
 
 impl Cell {
@@ -16,12 +18,15 @@ impl Cell {
             Cell::Utf8(p) => {
                 *p = ch as u32;
             }
+            Cell::Bit(p) => {
+                *p = ch as u32 & 1 == 1;
+            }
         }
     }
 }
 
 impl Cell {
-    pub fn default_cell(future: &Vec<Feature>) -> Self {
+    pub fn default_cell(future: &[Feature]) -> Self {
         if future.contains(&Feature::AllowUtf8) {
             Cell::Utf8(0)
         } else {
@@ -31,7 +36,7 @@ impl Cell {
 
     #[allow(dead_code)]
     /// For testing purposes
-    pub fn new(value: u32, future: &Vec<Feature>) -> Self {
+    pub fn new(value: u32, future: &[Feature]) -> Self {
         if future.contains(&Feature::AllowUtf8) {
             Cell::Utf8(value)
         } else {
@@ -39,10 +44,17 @@ impl Cell {
         }
     }
 
+    /// Whether the value wraps around when it goes out of bounds,
+    /// the interpreter only wraps it when the `NoReverseValue` feature is enabled
+    pub fn wraps(future: &[Feature]) -> bool {
+        future.contains(&Feature::NoReverseValue)
+    }
+
     pub fn get_value(&self) -> u8 {
         match self {
             Self::Byte(value) => *value,
             Self::Utf8(value) => *value as u8,
+            Self::Bit(value) => *value as u8,
         }
     }
 
@@ -50,11 +62,13 @@ impl Cell {
         match self {
             Self::Byte(value) => *value as u32,
             Self::Utf8(value) => *value,
+            Self::Bit(value) => *value as u32,
         }
     }
 
     pub fn increment(&mut self, no_reverse_value: bool) -> Result<(), InterpreterError> {
-        if self.get_value_utf8() == self.max_value() && no_reverse_value {
+        // The bits always flip
+        if self.get_value_utf8() == self.max_value() && no_reverse_value && !self.is_bit() {
             return Err(InterpreterErrorKind::ValueOutOfBounds.to_error());
         }
         match self {
@@ -72,12 +86,13 @@ impl Cell {
                     *value += 1;
                 }
             }
+            Self::Bit(value) => *value = !*value,
         }
         Ok(())
     }
 
     pub fn decrement(&mut self, no_reverse_value: bool) -> Result<(), InterpreterError> {
-        if self.get_value_utf8() == 0 && no_reverse_value {
+        if self.get_value_utf8() == 0 && no_reverse_value && !self.is_bit() {
             return Err(InterpreterErrorKind::ValueOutOfBounds.to_error());
         }
         match self {
@@ -95,21 +110,46 @@ impl Cell {
                     *value -= 1;
                 }
             }
+            Self::Bit(value) => *value = !*value,
+        }
+        Ok(())
+    }
+
+    /// Apply the bitwise operation to the value, the bits above the cell size are dropped
+    /// (8 bits, or 21 bits for the utf8 cells, then the value must not be above the maximum)
+    pub fn bitwise(&mut self, operation: impl FnOnce(u32) -> u32) -> Result<(), InterpreterError> {
+        let value = operation(self.get_value_utf8());
+        match self {
+            Self::Byte(cell) => *cell = value as u8,
+            Self::Utf8(cell) => {
+                let value = value & 0x1FFFFF;
+                if value > 1114111 {
+                    return Err(InterpreterErrorKind::ValueOutOfBounds.to_error());
+                }
+                *cell = value;
+            }
+            Self::Bit(cell) => *cell = value & 1 == 1,
         }
         Ok(())
     }
 
+    pub fn is_bit(&self) -> bool {
+        matches!(self, Self::Bit(_))
+    }
+
     pub fn max_value(&self) -> u32 {
         match self {
             Self::Byte(_) => u8::MAX as u32,
             Self::Utf8(_) => 1114111,
+            Self::Bit(_) => 1,
         }
     }
 
-    pub fn to_char(&self) -> Result<char, InterpreterError> {
+    pub fn to_char(self) -> Result<char, InterpreterError> {
         let c = match self {
-            Self::Byte(value) => Some(*value as char),
-            Self::Utf8(value) => char::from_u32(*value),
+            Self::Byte(value) => Some(value as char),
+            Self::Utf8(value) => char::from_u32(value),
+            Self::Bit(value) => Some(value as u8 as char),
         };
 
         if let Some(c) = c {
@@ -125,6 +165,7 @@ impl std::fmt::Display for Cell {
         match self {
             Self::Byte(value) => write!(f, "{}", value),
             Self::Utf8(value) => write!(f, "{}", value),
+            Self::Bit(value) => write!(f, "{}", *value as u8),
         }
     }
 }
@@ -136,7 +177,7 @@ mod tests {
 
     #[test]
     fn test_increment_u8_no_revers() {
-        let mut cell = Cell::default_cell(&vec![]);
+        let mut cell = Cell::default_cell(&[]);
         cell.increment(true).unwrap();
         assert_eq!(cell, Cell::Byte(1));
 
@@ -154,7 +195,7 @@ mod tests {
 
     #[test]
     fn test_increment_u32_no_revers() {
-        let mut cell = Cell::default_cell(&vec![Feature::AllowUtf8]);
+        let mut cell = Cell::default_cell(&[Feature::AllowUtf8]);
         cell.increment(true).unwrap();
         assert_eq!(cell, Cell::Utf8(1));
 
@@ -172,7 +213,7 @@ mod tests {
 
     #[test]
     fn test_increment_u8_revers() {
-        let mut cell = Cell::default_cell(&vec![]);
+        let mut cell = Cell::default_cell(&[]);
         cell.increment(false).unwrap();
         assert_eq!(cell, Cell::Byte(1));
 
@@ -187,7 +228,7 @@ mod tests {
 
     #[test]
     fn test_increment_u32_revers() {
-        let mut cell = Cell::default_cell(&vec![Feature::AllowUtf8]);
+        let mut cell = Cell::default_cell(&[Feature::AllowUtf8]);
         cell.increment(false).unwrap();
         assert_eq!(cell, Cell::Utf8(1));
 
@@ -202,7 +243,7 @@ mod tests {
 
     #[test]
     fn test_decrement_u8_no_revers() {
-        let mut cell = Cell::new(255, &vec![]);
+        let mut cell = Cell::new(255, &[]);
         cell.decrement(true).unwrap();
         assert_eq!(cell, Cell::Byte(254));
 
@@ -220,7 +261,7 @@ mod tests {
 
     #[test]
     fn test_decrement_u32_no_revers() {
-        let mut cell = Cell::new(1114111, &vec![Feature::AllowUtf8]);
+        let mut cell = Cell::new(1114111, &[Feature::AllowUtf8]);
         cell.decrement(true).unwrap();
         assert_eq!(cell, Cell::Utf8(1114110));
 
@@ -238,7 +279,7 @@ mod tests {
 
     #[test]
     fn test_decrement_u8_revers() {
-        let mut cell = Cell::new(0, &vec![]);
+        let mut cell = Cell::new(0, &[]);
         cell.decrement(false).unwrap();
         assert_eq!(cell, Cell::Byte(255));
 
@@ -253,7 +294,7 @@ mod tests {
 
     #[test]
     fn test_decrement_u32_revers() {
-        let mut cell = Cell::new(0, &vec![Feature::AllowUtf8]);
+        let mut cell = Cell::new(0, &[Feature::AllowUtf8]);
         cell.decrement(false).unwrap();
         assert_eq!(cell, Cell::Utf8(1114111));
 
@@ -268,23 +309,23 @@ mod tests {
 
     #[test]
     fn test_to_char() {
-        let cell = Cell::new(65, &vec![]);
+        let cell = Cell::new(65, &[]);
         assert_eq!(cell.to_char().unwrap(), 'A');
 
-        let cell = Cell::new(129408, &vec![Feature::AllowUtf8]);
+        let cell = Cell::new(129408, &[Feature::AllowUtf8]);
         assert_eq!(cell.to_char().unwrap(), '🦀');
 
-        let cell = Cell::new(129392, &vec![Feature::AllowUtf8]);
+        let cell = Cell::new(129392, &[Feature::AllowUtf8]);
         assert_eq!(cell.to_char().unwrap(), '🥰');
     }
 
     #[test]
     fn test_set_value() {
-        let mut cell = Cell::default_cell(&vec![]);
+        let mut cell = Cell::default_cell(&[]);
         cell.set_value('A');
         assert_eq!(cell, Cell::Byte(65));
 
-        let mut cell = Cell::default_cell(&vec![Feature::AllowUtf8]);
+        let mut cell = Cell::default_cell(&[Feature::AllowUtf8]);
         cell.set_value('🦀');
         assert_eq!(cell, Cell::Utf8(129408));
     }
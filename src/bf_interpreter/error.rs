@@ -40,6 +40,16 @@ pub enum InterpreterErrorKind {
     FlushError(std::io::Error),
     UnmatchedBracket,
     InvalidUtf8,
+    UndefinedProcedure(u32),  // takes procedure number
+    CallStackOverflow(usize), // takes the maximum depth
+    NoFileRoot,
+    FileOutOfRoot(String), // takes the file name
+    InvalidFileName,
+    InvalidFileMode(u32),
+    FileOpenError(String, std::io::Error), // takes the file name
+    FileNotOpen(&'static str),             // takes what the file is open for
+    FileReadError(std::io::Error),
+    FileWriteError(std::io::Error),
 }
 
 impl InterpreterErrorKind {
@@ -47,7 +57,7 @@ impl InterpreterErrorKind {
         InterpreterError::new(self.to_string(), self.code())
     }
 
-    fn code(&self) -> i32 {
+    pub fn code(&self) -> i32 {
         match self {
             InterpreterErrorKind::PointerOutOfBounds(_) => 11,
             InterpreterErrorKind::ValueOutOfBounds => 12,
@@ -55,6 +65,16 @@ impl InterpreterErrorKind {
             InterpreterErrorKind::FlushError(_) => 14,
             InterpreterErrorKind::UnmatchedBracket => 15,
             InterpreterErrorKind::InvalidUtf8 => 16,
+            InterpreterErrorKind::UndefinedProcedure(_) => 17,
+            InterpreterErrorKind::CallStackOverflow(_) => 18,
+            InterpreterErrorKind::NoFileRoot => 19,
+            InterpreterErrorKind::FileOutOfRoot(_) => 20,
+            InterpreterErrorKind::InvalidFileName => 21,
+            InterpreterErrorKind::InvalidFileMode(_) => 22,
+            InterpreterErrorKind::FileOpenError(..) => 23,
+            InterpreterErrorKind::FileNotOpen(_) => 24,
+            InterpreterErrorKind::FileReadError(_) => 25,
+            InterpreterErrorKind::FileWriteError(_) => 26,
         }
     }
 }
@@ -74,6 +94,36 @@ impl Display for InterpreterErrorKind {
             InterpreterErrorKind::FlushError(e) => write!(f, "Failed to flush stdout: {}", e),
             InterpreterErrorKind::UnmatchedBracket => write!(f, "Unmatched bracket"),
             InterpreterErrorKind::InvalidUtf8 => write!(f, "Invalid utf8"),
+            InterpreterErrorKind::UndefinedProcedure(procedure) => {
+                write!(f, "Undefined procedure {}", procedure)
+            }
+            InterpreterErrorKind::CallStackOverflow(depth) => {
+                write!(f, "The procedure calls are deeper than {}", depth)
+            }
+            InterpreterErrorKind::NoFileRoot => {
+                write!(f, "The file commands need the `--file-root` directory")
+            }
+            InterpreterErrorKind::FileOutOfRoot(name) => {
+                write!(f, "The file `{}` is out of the file root directory", name)
+            }
+            InterpreterErrorKind::InvalidFileName => {
+                write!(f, "The file name is empty or not zero-terminated")
+            }
+            InterpreterErrorKind::InvalidFileMode(mode) => write!(
+                f,
+                "Invalid file mode {} (0 to read, 1 to write, 2 to append)",
+                mode
+            ),
+            InterpreterErrorKind::FileOpenError(name, e) => {
+                write!(f, "Failed to open the file `{}`: {}", name, e)
+            }
+            InterpreterErrorKind::FileNotOpen(purpose) => {
+                write!(f, "No file is open for {}", purpose)
+            }
+            InterpreterErrorKind::FileReadError(e) => write!(f, "Failed to read the file: {}", e),
+            InterpreterErrorKind::FileWriteError(e) => {
+                write!(f, "Failed to write the file: {}", e)
+            }
         }
     }
 }
@@ -93,9 +143,7 @@ mod tests {
         assert_eq!(error.to_string(), "Value out of bounds");
         assert_eq!(error.code, 12);
 
-        let error =
-            InterpreterErrorKind::IoError(std::io::Error::new(std::io::ErrorKind::Other, "test"))
-                .to_error();
+        let error = InterpreterErrorKind::IoError(std::io::Error::other("test")).to_error();
         assert_eq!(
             error.to_string(),
             "Failed to read byte from stdin: no bytes available: test"
@@ -109,6 +157,28 @@ mod tests {
         let error = InterpreterErrorKind::InvalidUtf8.to_error();
         assert_eq!(error.to_string(), "Invalid utf8");
         assert_eq!(error.code, 16);
+
+        let error = InterpreterErrorKind::UndefinedProcedure(3).to_error();
+        assert_eq!(error.to_string(), "Undefined procedure 3");
+        assert_eq!(error.code, 17);
+
+        let error = InterpreterErrorKind::CallStackOverflow(1000).to_error();
+        assert_eq!(
+            error.to_string(),
+            "The procedure calls are deeper than 1000"
+        );
+        assert_eq!(error.code, 18);
+
+        let error = InterpreterErrorKind::FileOutOfRoot("../a".to_string()).to_error();
+        assert_eq!(
+            error.to_string(),
+            "The file `../a` is out of the file root directory"
+        );
+        assert_eq!(error.code, 20);
+
+        let error = InterpreterErrorKind::FileNotOpen("writing").to_error();
+        assert_eq!(error.to_string(), "No file is open for writing");
+        assert_eq!(error.code, 24);
     }
 
     #[test]
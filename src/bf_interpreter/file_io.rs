@@ -0,0 +1,247 @@
+use crate::bf_interpreter::error::{InterpreterError, InterpreterErrorKind};
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Component, Path, PathBuf};
+
+// The file commands read and write the files of the file root directory (`--file-root`) only:
+// `%` opens the file named by the zero-terminated string after the current cell, in the mode of the current cell
+// (0 to read, 1 to write and 2 to append), `_` reads a byte of the file opened to read to the current cell,
+// `;` writes the current cell to the file opened to write, and `=` closes the file of the mode of the current cell.
+// A file is open to read and another one to write at the same time, opening a file closes the previous one of its mode
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum FileCommand {
+    Open,
+    Read,
+    Write,
+    Close,
+}
+
+impl FileCommand {
+    /// The commands and their chars
+    const CHARS: [(char, FileCommand); 4] = [
+        ('%', FileCommand::Open),
+        ('_', FileCommand::Read),
+        (';', FileCommand::Write),
+        ('=', FileCommand::Close),
+    ];
+
+    pub fn from(c: char) -> Option<Self> {
+        Self::CHARS
+            .iter()
+            .find(|(command_char, _)| *command_char == c)
+            .map(|(_, command)| *command)
+    }
+
+    pub fn to_char(self) -> char {
+        Self::CHARS
+            .iter()
+            .find(|(_, command)| *command == self)
+            .map_or('?', |(c, _)| *c)
+    }
+}
+
+/// The open files of the file commands
+#[derive(Default)]
+pub struct Files {
+    /// The directory of the files, the file commands fail without it
+    pub root: Option<PathBuf>,
+    reader: Option<BufReader<File>>,
+    writer: Option<BufWriter<File>>,
+}
+
+impl Files {
+    /// Open the file of the root directory in the mode, 0 to read, 1 to write and 2 to append
+    pub fn open(&mut self, mode: u32, name: &str) -> Result<(), InterpreterError> {
+        let mut options = OpenOptions::new();
+        match mode {
+            0 => options.read(true),
+            1 => options.write(true).create(true).truncate(true),
+            2 => options.append(true).create(true),
+            _ => return Err(InterpreterErrorKind::InvalidFileMode(mode).to_error()),
+        };
+        let path = self.path(name)?;
+        self.close(mode)?;
+        let file = options
+            .open(path)
+            .map_err(|e| InterpreterErrorKind::FileOpenError(name.to_string(), e).to_error())?;
+        match mode {
+            0 => self.reader = Some(BufReader::new(file)),
+            _ => self.writer = Some(BufWriter::new(file)),
+        }
+        Ok(())
+    }
+
+    /// Read the next byte of the file opened to read
+    /// # Returns
+    /// The byte, or `None` at the end of the file
+    pub fn read(&mut self) -> Result<Option<u8>, InterpreterError> {
+        let reader = self
+            .reader
+            .as_mut()
+            .ok_or_else(|| InterpreterErrorKind::FileNotOpen("reading").to_error())?;
+        let mut byte = [0];
+        match reader.read(&mut byte) {
+            Ok(0) => Ok(None),
+            Ok(_) => Ok(Some(byte[0])),
+            Err(e) => Err(InterpreterErrorKind::FileReadError(e).to_error()),
+        }
+    }
+
+    /// Write the byte to the file opened to write
+    pub fn write(&mut self, byte: u8) -> Result<(), InterpreterError> {
+        let writer = self
+            .writer
+            .as_mut()
+            .ok_or_else(|| InterpreterErrorKind::FileNotOpen("writing").to_error())?;
+        writer
+            .write_all(&[byte])
+            .map_err(|e| InterpreterErrorKind::FileWriteError(e).to_error())
+    }
+
+    /// Close the file of the mode, the file opened to read for 0 and the file opened to write otherwise
+    pub fn close(&mut self, mode: u32) -> Result<(), InterpreterError> {
+        match mode {
+            0 => self.reader = None,
+            _ => {
+                self.flush()?;
+                self.writer = None;
+            }
+        }
+        Ok(())
+    }
+
+    /// Close both files, the buffered bytes are written without their errors
+    pub fn close_all(&mut self) {
+        self.reader = None;
+        self.writer = None;
+    }
+
+    /// Write the buffered bytes to the file opened to write
+    pub fn flush(&mut self) -> Result<(), InterpreterError> {
+        match self.writer.as_mut().map(|writer| writer.flush()) {
+            Some(Err(e)) => Err(InterpreterErrorKind::FileWriteError(e).to_error()),
+            _ => Ok(()),
+        }
+    }
+
+    /// The path of the file in the root directory, the names that lead out of it are refused
+    /// (the absolute paths, the `..` and the symbolic links to the outside)
+    fn path(&self, name: &str) -> Result<PathBuf, InterpreterError> {
+        let root = self
+            .root
+            .as_ref()
+            .ok_or_else(|| InterpreterErrorKind::NoFileRoot.to_error())?;
+        let relative = Path::new(name);
+        let out_of_root = || InterpreterErrorKind::FileOutOfRoot(name.to_string()).to_error();
+        if relative.file_name().is_none()
+            || !relative
+                .components()
+                .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+        {
+            return Err(out_of_root());
+        }
+        let open_error = |e| InterpreterErrorKind::FileOpenError(name.to_string(), e).to_error();
+        let root = root.canonicalize().map_err(open_error)?;
+        let path = root.join(relative);
+        let resolved = match path.canonicalize() {
+            Ok(resolved) => resolved,
+            // A dangling symbolic link can't be resolved, and opening it would create its target
+            Err(_) if path.symlink_metadata().is_ok() => return Err(out_of_root()),
+            // The new files are resolved by their directory
+            Err(_) => path
+                .parent()
+                .unwrap_or(&root)
+                .canonicalize()
+                .map_err(open_error)?
+                .join(relative.file_name().unwrap_or_default()),
+        };
+        match resolved.starts_with(&root) {
+            true => Ok(resolved),
+            false => Err(out_of_root()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::arguments::{Eof, Feature};
+    use crate::bf_interpreter::error::InterpreterErrorKind;
+    use crate::bf_interpreter::interpreter::Interpreter;
+    use console::Term;
+    use pretty_assertions::assert_eq;
+
+    /// The code that sets the mode in the current cell and the zero-terminated name after it
+    fn name_on_tape(mode: u8, name: &str) -> String {
+        let mut code = "+".repeat(mode as usize);
+        for byte in name.bytes() {
+            code.push('>');
+            code.push_str(&"+".repeat(byte as usize));
+        }
+        code + &"<".repeat(name.len())
+    }
+
+    #[test]
+    fn read_and_write_the_files() {
+        // The root is in a directory of the test, with the files out of the root next to it
+        let directory =
+            std::env::temp_dir().join(format!("bfy_file_io_test_{}", std::process::id()));
+        let root = directory.join("root");
+        std::fs::create_dir_all(&root).unwrap();
+        let mut interpreter = Interpreter::new(30, vec![Feature::FileIo], Term::stdout());
+        interpreter.files.root = Some(root.clone());
+
+        // Write `AB` to the file, and read it back to the cells 20 and 21
+        let write =
+            name_on_tape(1, "out.txt") + "%>>>>>>>>>>[-]++++++++[<++++++++>-]<+;+;<<<<<<<<<=";
+        assert_eq!(interpreter.run(write), Ok(0));
+        assert_eq!(std::fs::read_to_string(root.join("out.txt")).unwrap(), "AB");
+        interpreter.reset();
+        // The end of the file is 0 like the end of the input
+        interpreter.eof = Eof::Zero;
+        let read = name_on_tape(0, "out.txt") + "%>>>>>>>>>>>>>>>>>>>>_>_>_";
+        assert_eq!(interpreter.run(read), Ok(0));
+        let values = interpreter.cells[20..23]
+            .iter()
+            .map(|cell| cell.get_value())
+            .collect::<Vec<_>>();
+        assert_eq!(values, vec![b'A', b'B', 0]);
+
+        interpreter.reset();
+        assert_eq!(
+            interpreter.run(name_on_tape(0, "../out.txt") + "%"),
+            Err(InterpreterErrorKind::FileOutOfRoot("../out.txt".to_string()).to_error())
+        );
+        assert_eq!(
+            interpreter.run(String::from("+++%")),
+            Err(InterpreterErrorKind::InvalidFileMode(3).to_error())
+        );
+        interpreter.reset();
+        assert_eq!(
+            interpreter.run(String::from("%")),
+            Err(InterpreterErrorKind::InvalidFileName.to_error())
+        );
+        assert_eq!(
+            interpreter.run(String::from("_")),
+            Err(InterpreterErrorKind::FileNotOpen("reading").to_error())
+        );
+
+        // The dangling link would create its target out of the root
+        let outside = directory.join("outside.txt");
+        std::os::unix::fs::symlink(&outside, root.join("link")).unwrap();
+        interpreter.reset();
+        assert_eq!(
+            interpreter.run(name_on_tape(1, "link") + "%;"),
+            Err(InterpreterErrorKind::FileOutOfRoot("link".to_string()).to_error())
+        );
+        assert!(!outside.exists());
+
+        let mut interpreter = Interpreter::new(30, vec![Feature::FileIo], Term::stdout());
+        assert_eq!(
+            interpreter.run(name_on_tape(0, "out.txt") + "%"),
+            Err(InterpreterErrorKind::NoFileRoot.to_error())
+        );
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
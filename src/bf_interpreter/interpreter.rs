@@ -1,8 +1,19 @@
 use crate::arguments;
+use crate::bf_interpreter::brainfork;
 use crate::bf_interpreter::cell::Cell;
 use crate::bf_interpreter::error::{InterpreterError, InterpreterErrorKind};
-use std::io::Write;
-use std::{char, usize, vec};
+use crate::bf_interpreter::file_io::{FileCommand, Files};
+use crate::dialects::bits;
+use std::collections::{HashMap, VecDeque};
+use std::io::{IsTerminal, Read, Write};
+use std::rc::Rc;
+use std::{char, vec};
+
+/// The maximum depth of the pbrain procedure calls, the loops that are running count as calls
+/// because both of them recurse in the interpreter
+pub const MAX_CALL_DEPTH: usize = 1000;
+/// The number of the cells on each side of the pointer that `#` prints
+pub const DEBUG_WINDOW: usize = 8;
 
 pub struct Interpreter {
     pub cells: Vec<Cell>,
@@ -11,6 +22,32 @@ pub struct Interpreter {
     brackets: Vec<BfCommand>,
     pub features: Vec<arguments::Feature>,
     pub term: console::Term,
+    /// What `,` does when the input can't be read
+    pub eof: arguments::Eof,
+    /// The pbrain procedures by their numbers
+    pub procedures: HashMap<u32, Rc<Vec<BfCommand>>>,
+    /// The numbers of the pbrain procedures that are running, the innermost last
+    call_stack: Vec<u32>,
+    /// The nesting of the running loops and procedure calls
+    depth: usize,
+    /// The pointers of the brainfork threads at the end of the last run, the first thread first
+    pub thread_pointers: Vec<usize>,
+    /// The Extended Type I storage
+    pub storage: Cell,
+    /// The exit code of the program if it ended with `@`, the current cell value with the `exit-code` feature
+    pub exit_code: Option<i32>,
+    /// The language of the code, the bit tape languages run on the bit cells
+    pub lang: arguments::Lang,
+    /// The bits of the input bytes that are not read yet
+    input_bits: VecDeque<bool>,
+    /// The bits of the output byte that is not written yet
+    output_bits: Vec<bool>,
+    /// The files of the `file-io` feature
+    pub files: Files,
+    /// The input chars, read instead of stdin if set, e.g. by the tests
+    pub input: Option<VecDeque<char>>,
+    /// The output bytes, written instead of stdout if set
+    pub output: Option<Vec<u8>>,
 }
 
 impl Interpreter {
@@ -20,27 +57,63 @@ impl Interpreter {
             pointer: 0,
             bf_commands: vec![],
             brackets: Vec::new(),
-            features,
             term,
+            eof: arguments::Eof::default(),
+            procedures: HashMap::new(),
+            call_stack: Vec::new(),
+            depth: 0,
+            thread_pointers: Vec::new(),
+            storage: Cell::default_cell(&features),
+            exit_code: None,
+            lang: arguments::Lang::default(),
+            input_bits: VecDeque::new(),
+            output_bits: Vec::new(),
+            files: Files::default(),
+            input: None,
+            output: None,
+            features,
         }
     }
 
-    pub fn run(&mut self, bf_code: String) -> Result<i32, InterpreterError> {
-        self.bf_commands = to_bf_commands(bf_code.chars().collect())?;
+    /// Run the code of the language, the cells are reset to the cells of the language
+    pub fn set_lang(&mut self, lang: arguments::Lang) {
+        self.lang = lang;
+        self.reset();
+    }
 
-        match self.run_brainfuck_code(&self.bf_commands.clone()) {
-            Ok(_) => Ok(0),
-            Err(e) => Err(e),
-        }
+    pub fn run(&mut self, bf_code: String) -> Result<i32, InterpreterError> {
+        self.bf_commands = match self.lang.bit_tape() {
+            // The extra commands of the features are comments in the bit tape languages
+            true => to_bf_commands(bits::to_commands(self.lang, &bf_code).chars().collect())?,
+            false => to_bf_commands_with(bf_code.chars().collect(), &self.features)?,
+        };
+        self.exit_code = None;
+
+        let result =
+            if self.features.contains(&arguments::Feature::Brainfork) && !self.lang.bit_tape() {
+                brainfork::run(self, &self.bf_commands.clone())
+            } else {
+                self.run_brainfuck_code(&self.bf_commands.clone())
+            };
+        result?;
+        self.flush_output_bits()?;
+        self.files.flush()?;
+        Ok(self.exit_code.unwrap_or(0))
     }
 
     // +[>++<-]
     fn iterate(&mut self, code: &Vec<BfCommand>) -> Result<(), InterpreterError> {
         trace!("Iterate: {:?}", code);
-        while self.cells[self.pointer].get_value_utf8() != 0 {
-            self.run_brainfuck_code(code)?;
+        self.depth += 1;
+        let mut result = Ok(());
+        while result.is_ok()
+            && self.exit_code.is_none()
+            && self.cells[self.pointer].get_value_utf8() != 0
+        {
+            result = self.run_brainfuck_code(code);
         }
-        Ok(())
+        self.depth -= 1;
+        result
     }
 
     fn run_brainfuck_code(&mut self, bf_code: &Vec<BfCommand>) -> Result<(), InterpreterError> {
@@ -53,13 +126,128 @@ impl Interpreter {
                 BfCommand::Print => self.output_value()?,
                 BfCommand::Read => self.input_value()?,
                 BfCommand::Loop(loop_body) => self.iterate(loop_body)?,
+                BfCommand::Procedure(body) => self.define_procedure(body),
+                BfCommand::Call => self.call_procedure()?,
+                BfCommand::Fork => unreachable!("The forks are run by the brainfork threads"),
+                BfCommand::Extended(command) => self.extended(*command)?,
+                BfCommand::File(command) => self.file(*command)?,
+                BfCommand::Debug => self.debug(),
+            }
+            if self.exit_code.is_some() {
+                break;
             }
         }
 
         Ok(())
     }
 
-    fn increment_pointer(&mut self) -> Result<(), InterpreterError> {
+    /// Run the Extended Type I command
+    pub fn extended(&mut self, command: ExtendedCommand) -> Result<(), InterpreterError> {
+        trace!("Extended command: {:?}", command);
+        let storage = self.storage.get_value_utf8();
+        let cell = &mut self.cells[self.pointer];
+        match command {
+            ExtendedCommand::Exit => {
+                self.exit_code = Some(
+                    match self.features.contains(&arguments::Feature::ExitCode) {
+                        true => cell.get_value_utf8() as i32,
+                        false => 0,
+                    },
+                )
+            }
+            ExtendedCommand::Store => self.storage = *cell,
+            ExtendedCommand::Load => *cell = self.storage,
+            ExtendedCommand::ShiftRight => cell.bitwise(|value| value >> 1)?,
+            ExtendedCommand::ShiftLeft => cell.bitwise(|value| value << 1)?,
+            ExtendedCommand::Not => cell.bitwise(|value| !value)?,
+            ExtendedCommand::Xor => cell.bitwise(|value| value ^ storage)?,
+            ExtendedCommand::And => cell.bitwise(|value| value & storage)?,
+            ExtendedCommand::Or => cell.bitwise(|value| value | storage)?,
+        }
+        Ok(())
+    }
+
+    /// Print the pointer and the cells around it to stderr, like the REPL `!array`
+    pub fn debug(&self) {
+        let window = self.debug_window();
+        eprintln!(
+            "Pointer: {}, cells {}..{}: {:?}",
+            self.pointer,
+            window.start,
+            window.end,
+            &self.cells[window.clone()]
+        );
+    }
+
+    /// The cells that `#` prints, `DEBUG_WINDOW` cells on each side of the pointer
+    fn debug_window(&self) -> std::ops::Range<usize> {
+        self.pointer.saturating_sub(DEBUG_WINDOW)
+            ..(self.pointer + DEBUG_WINDOW + 1).min(self.cells.len())
+    }
+
+    /// Run the file command of the `file-io` feature
+    pub fn file(&mut self, command: FileCommand) -> Result<(), InterpreterError> {
+        trace!("File command: {:?}", command);
+        let mode = self.cells[self.pointer].get_value_utf8();
+        match command {
+            FileCommand::Open => {
+                let name = self.file_name()?;
+                self.files.open(mode, &name)
+            }
+            FileCommand::Read => {
+                match self.files.read()? {
+                    Some(byte) => self.cells[self.pointer].set_value(byte as char),
+                    None => match self.eof {
+                        arguments::Eof::Error => {
+                            return Err(InterpreterErrorKind::FileReadError(
+                                std::io::ErrorKind::UnexpectedEof.into(),
+                            )
+                            .to_error())
+                        }
+                        arguments::Eof::Zero => self.cells[self.pointer] = self.default_cell(),
+                        arguments::Eof::Unchanged => {}
+                    },
+                }
+                Ok(())
+            }
+            FileCommand::Write => self.files.write(self.cells[self.pointer].get_value()),
+            FileCommand::Close => self.files.close(mode),
+        }
+    }
+
+    /// The zero-terminated file name after the current cell
+    fn file_name(&self) -> Result<String, InterpreterError> {
+        let cells = &self.cells[self.pointer + 1..];
+        match cells.iter().position(|cell| cell.get_value_utf8() == 0) {
+            Some(end) if end > 0 => cells[..end].iter().map(|cell| cell.to_char()).collect(),
+            _ => Err(InterpreterErrorKind::InvalidFileName.to_error()),
+        }
+    }
+
+    fn define_procedure(&mut self, body: &[BfCommand]) {
+        let procedure = self.cells[self.pointer].get_value_utf8();
+        trace!("Define procedure {}", procedure);
+        self.procedures.insert(procedure, Rc::new(body.to_vec()));
+    }
+
+    fn call_procedure(&mut self) -> Result<(), InterpreterError> {
+        let procedure = self.cells[self.pointer].get_value_utf8();
+        trace!("Call procedure {}", procedure);
+        let Some(body) = self.procedures.get(&procedure).cloned() else {
+            return Err(InterpreterErrorKind::UndefinedProcedure(procedure).to_error());
+        };
+        if self.depth >= MAX_CALL_DEPTH {
+            return Err(InterpreterErrorKind::CallStackOverflow(MAX_CALL_DEPTH).to_error());
+        }
+        self.call_stack.push(procedure);
+        self.depth += 1;
+        let result = self.run_brainfuck_code(&body);
+        self.depth -= 1;
+        self.call_stack.pop();
+        result
+    }
+
+    pub fn increment_pointer(&mut self) -> Result<(), InterpreterError> {
         trace!("Increment pointer");
         self.pointer += 1;
         if self.pointer >= self.cells.len() {
@@ -72,7 +260,7 @@ impl Interpreter {
         Ok(())
     }
 
-    fn decrement_pointer(&mut self) -> Result<(), InterpreterError> {
+    pub fn decrement_pointer(&mut self) -> Result<(), InterpreterError> {
         trace!("Decrement pointer");
         if self.pointer == 0 {
             if self.features.contains(&arguments::Feature::ReversePointer) {
@@ -86,58 +274,164 @@ impl Interpreter {
         Ok(())
     }
 
-    fn increment_value(&mut self) -> Result<(), InterpreterError> {
+    pub fn increment_value(&mut self) -> Result<(), InterpreterError> {
         trace!("Increment value");
-        self.cells[self.pointer]
-            .increment(!self.features.contains(&arguments::Feature::NoReverseValue))?;
+        self.cells[self.pointer].increment(!Cell::wraps(&self.features))?;
         Ok(())
     }
 
-    fn decrement_value(&mut self) -> Result<(), InterpreterError> {
+    pub fn decrement_value(&mut self) -> Result<(), InterpreterError> {
         trace!("Decrement value");
-        self.cells[self.pointer]
-            .decrement(!self.features.contains(&arguments::Feature::NoReverseValue))?;
+        self.cells[self.pointer].decrement(!Cell::wraps(&self.features))?;
         Ok(())
     }
 
-    fn output_value(&mut self) -> Result<(), InterpreterError> {
+    pub fn output_value(&mut self) -> Result<(), InterpreterError> {
         trace!("Output value");
 
-        if self.features.contains(&arguments::Feature::AllowUtf8) {
-            let c = char::from_u32(self.cells[self.pointer].get_value_utf8());
-            match c {
-                Some(c) => print!("{}", c),
-                None => return Err(InterpreterErrorKind::InvalidUtf8.to_error()),
+        if let Cell::Bit(bit) = self.cells[self.pointer] {
+            self.output_bits.push(bit);
+            if self.output_bits.len() == 8 {
+                return self.flush_output_bits();
             }
+            return Ok(());
+        }
+
+        let c = if self.features.contains(&arguments::Feature::AllowUtf8) {
+            char::from_u32(self.cells[self.pointer].get_value_utf8())
+                .ok_or_else(|| InterpreterErrorKind::InvalidUtf8.to_error())?
         } else {
-            print!("{}", self.cells[self.pointer].get_value() as char);
+            self.cells[self.pointer].get_value() as char
+        };
+        let mut bytes = [0; 4];
+        self.write_output(c.encode_utf8(&mut bytes).as_bytes())
+    }
+
+    /// Write the bytes to stdout, or to the output bytes if they are set
+    fn write_output(&mut self, bytes: &[u8]) -> Result<(), InterpreterError> {
+        if let Some(output) = self.output.as_mut() {
+            output.extend_from_slice(bytes);
+            return Ok(());
+        }
+        let mut stdout = std::io::stdout();
+        if let Err(e) = stdout.write_all(bytes) {
+            return Err(InterpreterErrorKind::IoError(e).to_error());
         }
-        match std::io::stdout().flush() {
+        match stdout.flush() {
             Ok(_) => Ok(()),
             Err(e) => Err(InterpreterErrorKind::FlushError(e).to_error()),
         }
     }
 
-    fn input_value(&mut self) -> Result<(), InterpreterError> {
+    /// Write the output bits as a byte, the least significant bit first and the missing bits are 0
+    fn flush_output_bits(&mut self) -> Result<(), InterpreterError> {
+        if self.output_bits.is_empty() {
+            return Ok(());
+        }
+        let byte = self
+            .output_bits
+            .drain(..)
+            .enumerate()
+            .fold(0u8, |byte, (i, bit)| byte | (bit as u8) << i);
+        self.write_output(&[byte])
+    }
+
+    /// Read a char of the input, from the input chars if they are set, from the terminal
+    /// or from stdin when it is piped (a byte, or a UTF-8 char with the utf8 feature)
+    /// # Returns
+    /// The char, and whether it must be echoed (the terminal doesn't print it)
+    fn read_char(&mut self) -> std::io::Result<(char, bool)> {
+        if let Some(input) = self.input.as_mut() {
+            return input
+                .pop_front()
+                .map(|ch| (ch, false))
+                .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::UnexpectedEof));
+        }
+        if std::io::stdin().is_terminal() {
+            return self.term.read_char().map(|ch| (ch, true));
+        }
+        let mut stdin = std::io::stdin().lock();
+        if !self.features.contains(&arguments::Feature::AllowUtf8) {
+            let mut byte = [0];
+            stdin.read_exact(&mut byte)?;
+            return Ok((byte[0] as char, false));
+        }
+        read_utf8_char(&mut stdin).map(|ch| (ch, false))
+    }
+
+    pub fn input_value(&mut self) -> Result<(), InterpreterError> {
         trace!("Input value");
-        match self.term.read_char() {
-            Ok(ch) => {
+        let bit_tape = self.cells[self.pointer].is_bit();
+        if let Some(bit) = self.input_bits.pop_front().filter(|_| bit_tape) {
+            self.cells[self.pointer] = Cell::Bit(bit);
+            return Ok(());
+        }
+        match self.read_char() {
+            Ok((ch, echo)) if bit_tape => {
+                // The bits of the UTF-8 bytes of the char (or of the piped byte), the least significant bit first
+                let mut buffer = [0; 4];
+                let bytes = match echo || self.features.contains(&arguments::Feature::AllowUtf8) {
+                    true => ch.encode_utf8(&mut buffer).as_bytes(),
+                    false => {
+                        buffer[0] = ch as u8;
+                        &buffer[..1]
+                    }
+                };
+                for byte in bytes {
+                    self.input_bits.extend((0..8).map(|i| byte >> i & 1 == 1));
+                }
+                if echo {
+                    print!("{}", ch);
+                }
+                self.input_value()
+            }
+            Ok((ch, echo)) => {
                 self.cells[self.pointer].set_value(ch);
+                if !echo {
+                    return Ok(());
+                }
                 print!("{}", ch);
                 match std::io::stdout().flush() {
                     Ok(_) => Ok(()),
                     Err(e) => Err(InterpreterErrorKind::FlushError(e).to_error()),
                 }
             }
-            Err(e) => Err(InterpreterErrorKind::IoError(e).to_error()),
+            // The invalid input is not the end of the input
+            Err(e) if e.kind() == std::io::ErrorKind::InvalidData => {
+                Err(InterpreterErrorKind::InvalidUtf8.to_error())
+            }
+            Err(e) => match self.eof {
+                arguments::Eof::Error => Err(InterpreterErrorKind::IoError(e).to_error()),
+                arguments::Eof::Zero => {
+                    self.cells[self.pointer] = self.default_cell();
+                    Ok(())
+                }
+                arguments::Eof::Unchanged => Ok(()),
+            },
+        }
+    }
+
+    /// The zero cell, a bit for the bit tape languages
+    fn default_cell(&self) -> Cell {
+        match self.lang.bit_tape() {
+            true => Cell::Bit(false),
+            false => Cell::default_cell(&self.features),
         }
     }
 
     pub fn reset(&mut self) {
-        self.cells = vec![Cell::default_cell(&self.features); self.cells.len()];
+        self.cells = vec![self.default_cell(); self.cells.len()];
         self.pointer = 0;
         self.brackets = Vec::new();
         self.bf_commands = Vec::new();
+        self.procedures = HashMap::new();
+        self.call_stack = Vec::new();
+        self.depth = 0;
+        self.thread_pointers = Vec::new();
+        self.storage = self.default_cell();
+        self.input_bits = VecDeque::new();
+        self.output_bits = Vec::new();
+        self.files.close_all();
     }
 }
 
@@ -150,20 +444,108 @@ pub enum BfCommand {
     Print,
     Read,
     Loop(Vec<BfCommand>),
+    /// The pbrain procedure definition, it is numbered by the current cell value when it is run
+    Procedure(Vec<BfCommand>),
+    /// The pbrain procedure call, of the procedure numbered by the current cell value
+    Call,
+    /// The brainfork `Y`, fork the current thread
+    Fork,
+    Extended(ExtendedCommand),
+    File(FileCommand),
+    /// The `#` of the debug feature, print the pointer and the cells around it
+    Debug,
+}
+
+/// The Extended Type I commands
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ExtendedCommand {
+    Exit,
+    Store,
+    Load,
+    ShiftRight,
+    ShiftLeft,
+    Not,
+    Xor,
+    And,
+    Or,
 }
 
-fn to_bf_commands(bf_code: Vec<char>) -> Result<Vec<BfCommand>, InterpreterError> {
+impl ExtendedCommand {
+    /// The commands and their chars
+    const CHARS: [(char, ExtendedCommand); 9] = [
+        ('@', ExtendedCommand::Exit),
+        ('$', ExtendedCommand::Store),
+        ('!', ExtendedCommand::Load),
+        ('}', ExtendedCommand::ShiftRight),
+        ('{', ExtendedCommand::ShiftLeft),
+        ('~', ExtendedCommand::Not),
+        ('^', ExtendedCommand::Xor),
+        ('&', ExtendedCommand::And),
+        ('|', ExtendedCommand::Or),
+    ];
+
+    fn from(c: char) -> Option<Self> {
+        Self::CHARS
+            .iter()
+            .find(|(command_char, _)| *command_char == c)
+            .map(|(_, command)| *command)
+    }
+
+    fn to_char(self) -> char {
+        Self::CHARS
+            .iter()
+            .find(|(_, command)| *command == self)
+            .map_or('?', |(c, _)| *c)
+    }
+}
+
+/// Read a UTF-8 char, the invalid bytes fail without reading the bytes after them
+fn read_utf8_char(reader: &mut impl Read) -> std::io::Result<char> {
+    let invalid = || std::io::Error::from(std::io::ErrorKind::InvalidData);
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes[..1])?;
+    let length = match bytes[0] {
+        0..=0x7F => 1,
+        0xC0..=0xDF => 2,
+        0xE0..=0xEF => 3,
+        0xF0..=0xF7 => 4,
+        _ => return Err(invalid()),
+    };
+    reader.read_exact(&mut bytes[1..length])?;
+    std::str::from_utf8(&bytes[..length])
+        .ok()
+        .and_then(|s| s.chars().next())
+        .ok_or_else(invalid)
+}
+
+pub fn to_bf_commands(bf_code: Vec<char>) -> Result<Vec<BfCommand>, InterpreterError> {
+    to_bf_commands_with(bf_code, &[])
+}
+
+/// Parse the brainfuck code with the extra commands of the features (e.g. the pbrain procedures),
+/// they are comments without their features
+pub fn to_bf_commands_with(
+    bf_code: Vec<char>,
+    features: &[arguments::Feature],
+) -> Result<Vec<BfCommand>, InterpreterError> {
+    let pbrain = features.contains(&arguments::Feature::Pbrain);
+    let brainfork = features.contains(&arguments::Feature::Brainfork);
+    let extended = features.contains(&arguments::Feature::ExtendedType1);
+    let exit_code = features.contains(&arguments::Feature::ExitCode);
+    let file_io = features.contains(&arguments::Feature::FileIo);
+    let debug = features.contains(&arguments::Feature::Debug);
     let mut bf_commands = Vec::new();
     let mut i = 0;
     while i < bf_code.len() {
         match bf_code[i] {
-            '[' => {
+            open @ ('[' | '(') if open == '[' || pbrain => {
+                let close = if open == '[' { ']' } else { ')' };
                 let mut bracket_count = 1;
                 let mut j = i + 1;
                 while j < bf_code.len() {
                     match bf_code[j] {
-                        '[' => bracket_count += 1,
-                        ']' => bracket_count -= 1,
+                        c if c == open => bracket_count += 1,
+                        c if c == close => bracket_count -= 1,
                         _ => (),
                     }
                     if bracket_count == 0 {
@@ -174,19 +556,65 @@ fn to_bf_commands(bf_code: Vec<char>) -> Result<Vec<BfCommand>, InterpreterError
                 if bracket_count != 0 {
                     return Err(InterpreterErrorKind::UnmatchedBracket.to_error());
                 }
-                bf_commands.push(BfCommand::Loop(to_bf_commands(bf_code[i + 1..j].to_vec())?));
+                let body = to_bf_commands_with(bf_code[i + 1..j].to_vec(), features)?;
+                bf_commands.push(match open {
+                    '[' => BfCommand::Loop(body),
+                    _ => BfCommand::Procedure(body),
+                });
                 i = j;
             }
-            _ => match BfCommand::from(bf_code[i]) {
-                Some(command) => bf_commands.push(command),
-                None => (),
-            },
+            ':' if pbrain => bf_commands.push(BfCommand::Call),
+            'Y' if brainfork => bf_commands.push(BfCommand::Fork),
+            '#' if debug => bf_commands.push(BfCommand::Debug),
+            '@' if exit_code => bf_commands.push(BfCommand::Extended(ExtendedCommand::Exit)),
+            c if file_io && FileCommand::from(c).is_some() => {
+                bf_commands.extend(FileCommand::from(c).map(BfCommand::File))
+            }
+            c if extended && ExtendedCommand::from(c).is_some() => {
+                bf_commands.extend(ExtendedCommand::from(c).map(BfCommand::Extended))
+            }
+            _ => {
+                if let Some(command) = BfCommand::from(bf_code[i]) {
+                    bf_commands.push(command);
+                }
+            }
         }
         i += 1;
     }
     Ok(bf_commands)
 }
 
+/// Write the brainfuck commands back to the brainfuck code
+pub fn to_bf_code(bf_commands: &[BfCommand]) -> String {
+    let mut code = String::new();
+    for command in bf_commands {
+        match command {
+            BfCommand::IncPtr => code.push('>'),
+            BfCommand::DecPtr => code.push('<'),
+            BfCommand::IncVal => code.push('+'),
+            BfCommand::DecVal => code.push('-'),
+            BfCommand::Print => code.push('.'),
+            BfCommand::Read => code.push(','),
+            BfCommand::Loop(loop_body) => {
+                code.push('[');
+                code.push_str(&to_bf_code(loop_body));
+                code.push(']');
+            }
+            BfCommand::Procedure(body) => {
+                code.push('(');
+                code.push_str(&to_bf_code(body));
+                code.push(')');
+            }
+            BfCommand::Call => code.push(':'),
+            BfCommand::Fork => code.push('Y'),
+            BfCommand::Extended(command) => code.push(command.to_char()),
+            BfCommand::File(command) => code.push(command.to_char()),
+            BfCommand::Debug => code.push('#'),
+        }
+    }
+    code
+}
+
 impl BfCommand {
     fn from(c: char) -> Option<Self> {
         match c {
@@ -208,6 +636,34 @@ mod tests {
     use console::Term;
     use pretty_assertions::assert_eq; // for testing only
 
+    #[test]
+    fn read_the_utf8_chars() {
+        let mut input: &[u8] = "aé🦀\u{80}\u{ff}".as_bytes();
+        let chars: Vec<char> = std::iter::from_fn(|| read_utf8_char(&mut input).ok()).collect();
+        assert_eq!(chars, vec!['a', 'é', '🦀', '\u{80}', '\u{ff}']);
+
+        // The invalid lead bytes fail right away, the next byte is the next char
+        for lead in [0x80, 0xBF, 0xF8, 0xFF] {
+            let bytes = [lead, b'A', b'B', b'C'];
+            let mut input: &[u8] = &bytes;
+            assert_eq!(
+                read_utf8_char(&mut input).map_err(|e| e.kind()),
+                Err(std::io::ErrorKind::InvalidData)
+            );
+            assert_eq!(read_utf8_char(&mut input).ok(), Some('A'));
+        }
+        let mut input: &[u8] = b"\xC3A";
+        assert_eq!(
+            read_utf8_char(&mut input).map_err(|e| e.kind()),
+            Err(std::io::ErrorKind::InvalidData)
+        );
+        let mut input: &[u8] = b"\xE2\x82";
+        assert_eq!(
+            read_utf8_char(&mut input).map_err(|e| e.kind()),
+            Err(std::io::ErrorKind::UnexpectedEof)
+        );
+    }
+
     #[test]
     fn print_h_combine_repl() {
         let mut interpreter = Interpreter::new(30000, vec![], Term::stdout());
@@ -235,7 +691,7 @@ mod tests {
         let mut interpreter = Interpreter::new(5, vec![], Term::stdout());
 
         assert_eq!(interpreter.run(String::from("++[>++[>+<-]<-]")), Ok(0));
-        assert_eq!(interpreter.cells[2], Cell::new(4, &vec![]));
+        assert_eq!(interpreter.cells[2], Cell::new(4, &[]));
 
         println!();
     }
@@ -309,12 +765,12 @@ mod tests {
             Ok(0)
         );
 
-        assert_eq!(interpreter.cells[0], Cell::default_cell(&vec![]));
-        assert_eq!(interpreter.cells[1], Cell::default_cell(&vec![]));
-        assert_eq!(interpreter.cells[2], Cell::new(115, &vec![]));
-        assert_eq!(interpreter.cells[3], Cell::new(96, &vec![]));
-        assert_eq!(interpreter.cells[4], Cell::new(112, &vec![]));
-        assert_eq!(interpreter.cells[5], Cell::new(32, &vec![]));
+        assert_eq!(interpreter.cells[0], Cell::default_cell(&[]));
+        assert_eq!(interpreter.cells[1], Cell::default_cell(&[]));
+        assert_eq!(interpreter.cells[2], Cell::new(115, &[]));
+        assert_eq!(interpreter.cells[3], Cell::new(96, &[]));
+        assert_eq!(interpreter.cells[4], Cell::new(112, &[]));
+        assert_eq!(interpreter.cells[5], Cell::new(32, &[]));
     }
 
     #[test]
@@ -352,16 +808,116 @@ mod tests {
         assert_eq!(interpreter.run(String::from(">++++")), Ok(0));
 
         assert_eq!(interpreter.pointer, 1);
-        assert_eq!(interpreter.cells[0], Cell::new(0, &vec![]));
-        assert_eq!(interpreter.cells[1], Cell::new(4, &vec![]));
+        assert_eq!(interpreter.cells[0], Cell::new(0, &[]));
+        assert_eq!(interpreter.cells[1], Cell::new(4, &[]));
         // assert_eq!(interpreter.commands, vec!['>', '+', '+', '+', '+']);
 
         // reset
         interpreter.reset();
 
         assert_eq!(interpreter.pointer, 0);
-        assert_eq!(interpreter.cells[0], Cell::new(0, &vec![]));
-        assert_eq!(interpreter.cells[1], Cell::new(0, &vec![]));
+        assert_eq!(interpreter.cells[0], Cell::new(0, &[]));
+        assert_eq!(interpreter.cells[1], Cell::new(0, &[]));
         assert_eq!(interpreter.bf_commands, Vec::<BfCommand>::new());
     }
+
+    #[test]
+    fn pbrain_procedures() {
+        let features = vec![arguments::Feature::Pbrain];
+        let mut interpreter = Interpreter::new(10, features.clone(), Term::stdout());
+
+        // Procedure 1 adds 3 to the next cell, procedure 2 calls it twice
+        assert_eq!(interpreter.run(String::from("+(>+++<)+(-::+)>+<:")), Ok(0));
+        assert_eq!(interpreter.cells[0], Cell::new(2, &features));
+        assert_eq!(interpreter.cells[1], Cell::new(7, &features));
+        assert_eq!(to_bf_code(&interpreter.bf_commands), "+(>+++<)+(-::+)>+<:");
+
+        assert_eq!(
+            interpreter.run(String::from(">>:")),
+            Err(InterpreterErrorKind::UndefinedProcedure(0).to_error())
+        );
+        assert_eq!(
+            interpreter.run(String::from("+(:):")),
+            Err(InterpreterErrorKind::CallStackOverflow(MAX_CALL_DEPTH).to_error())
+        );
+        // The loops in a recursive procedure don't overflow the stack
+        assert_eq!(
+            interpreter.run(String::from("+([[[[[:]]]]]):")),
+            Err(InterpreterErrorKind::CallStackOverflow(MAX_CALL_DEPTH).to_error())
+        );
+
+        // They are comments without the feature
+        let mut interpreter = Interpreter::new(10, vec![], Term::stdout());
+        assert_eq!(interpreter.run(String::from("+(+):")), Ok(0));
+        assert_eq!(interpreter.cells[0], Cell::new(2, &[]));
+    }
+
+    #[test]
+    fn extended_type_1() {
+        let features = vec![arguments::Feature::ExtendedType1];
+        let mut interpreter = Interpreter::new(10, features.clone(), Term::stdout());
+
+        // 10 = 0b1010, 12 = 0b1100
+        let twelve = "++++++++++++";
+        let code = format!("++++++++++$>{twelve}^>{twelve}&>{twelve}|>!{{{{{{{{>!}}>~");
+        assert_eq!(interpreter.run(code), Ok(0));
+        let cells = [10, 6, 8, 14, 160, 5, 255].map(|value| Cell::new(value, &features));
+        assert_eq!(interpreter.cells[..7], cells);
+        assert_eq!(interpreter.storage, Cell::new(10, &features));
+
+        // `@` exits from the loops
+        interpreter.reset();
+        assert_eq!(interpreter.run(String::from("+[>+[@]>+<<]>>+")), Ok(0));
+        assert_eq!(interpreter.cells[1], Cell::new(1, &features));
+        assert_eq!(interpreter.cells[2], Cell::new(0, &features));
+
+        let features = vec![
+            arguments::Feature::ExtendedType1,
+            arguments::Feature::AllowUtf8,
+        ];
+        let mut interpreter = Interpreter::new(10, features, Term::stdout());
+        assert_eq!(
+            interpreter.run(String::from("~")),
+            Err(InterpreterErrorKind::ValueOutOfBounds.to_error())
+        );
+    }
+
+    #[test]
+    fn exit_code() {
+        let features = vec![arguments::Feature::ExitCode];
+        let mut interpreter = Interpreter::new(10, features.clone(), Term::stdout());
+        assert_eq!(interpreter.run(String::from("+++[>++@<-]>+")), Ok(2));
+        assert_eq!(interpreter.exit_code, Some(2));
+        assert_eq!(interpreter.run(String::from("+")), Ok(0));
+
+        // Only `@` is a command, the other Extended Type I commands are comments
+        let mut interpreter = Interpreter::new(10, features, Term::stdout());
+        assert_eq!(interpreter.run(String::from("+$>!@")), Ok(0));
+    }
+
+    #[test]
+    fn debug_dump() {
+        let features = vec![arguments::Feature::Debug];
+        let mut interpreter = Interpreter::new(20, features.clone(), Term::stdout());
+        assert_eq!(interpreter.run(String::from("+#>>#")), Ok(0));
+        assert_eq!(
+            interpreter.bf_commands,
+            vec![
+                BfCommand::IncVal,
+                BfCommand::Debug,
+                BfCommand::IncPtr,
+                BfCommand::IncPtr,
+                BfCommand::Debug
+            ]
+        );
+        assert_eq!(interpreter.debug_window(), 0..11);
+        interpreter.pointer = 15;
+        assert_eq!(interpreter.debug_window(), 7..20);
+
+        // `#` is a comment without the feature
+        assert_eq!(
+            to_bf_commands_with("+#".chars().collect(), &[]),
+            Ok(vec![BfCommand::IncVal])
+        );
+    }
 }
@@ -1,3 +1,5 @@
+pub mod brainfork;
 pub mod cell;
 pub mod error;
+pub mod file_io;
 pub mod interpreter;
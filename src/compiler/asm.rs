@@ -0,0 +1,572 @@
+use crate::arguments::Feature;
+use crate::bf_interpreter::cell::Cell;
+use crate::bf_interpreter::error::InterpreterErrorKind;
+use crate::bf_interpreter::interpreter::BfCommand;
+use crate::compiler::ir::{self, Instr};
+use std::fmt::Write;
+use std::io::Write as _;
+
+/// Generate x86-64 GNU assembler source code for linux from the brainfuck commands,
+/// it uses the linux syscalls for the I/O and exits with the same codes as the interpreter errors
+/// # Arguments
+/// * `bf_commands` - The parsed brainfuck program
+/// * `array_size` - The brainfuck array size
+/// * `features` - The extra features to follow, the same ones the interpreter uses
+pub fn compile(bf_commands: &[BfCommand], array_size: usize, features: &[Feature]) -> String {
+    let mut assembly = Assembly {
+        code: String::new(),
+        labels: 0,
+        utf8: features.contains(&Feature::AllowUtf8),
+        array_size,
+        wrap_value: Cell::wraps(features),
+        wrap_pointer: features.contains(&Feature::ReversePointer),
+        max_value: Cell::default_cell(features).max_value() as i64,
+    };
+
+    writeln!(
+        assembly.code,
+        "# Generated by bfy v{} from brainfuck source code, do not edit by hand.\n\
+        # Build it with: as -o program.o program.s && ld -o program program.o\n\
+        # The registers are: %rbx the array, %r12 the pointer\n\n    \
+            .bss\n\
+        array:\n    \
+            .zero {}\n\
+        buffer:\n    \
+            .zero 4\n\n    \
+            .text\n    \
+            .globl _start\n\
+        _start:\n    \
+            lea array(%rip), %rbx\n    \
+            xor %r12d, %r12d",
+        clap::crate_version!(),
+        array_size * if assembly.utf8 { 4 } else { 1 },
+    )
+    .unwrap();
+    assembly.instrs(&ir::optimize(bf_commands, features));
+    assembly.code.push_str(
+        "    mov $60, %eax\n    \
+            xor %edi, %edi\n    \
+            syscall\n",
+    );
+    assembly.routines();
+    assembly.code
+}
+
+/// Assemble and link the assembly to a static executable with `as` and `ld`
+/// # Arguments
+/// * `assembly` - The code generated by `compile`
+/// * `output` - The executable file to write
+pub fn link(assembly: &str, output: &str) -> Result<(), String> {
+    let object = format!("{}.o", output);
+    let mut as_command = std::process::Command::new("as")
+        .args(["-o", &object, "-"])
+        .stdin(std::process::Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run as: {}", e))?;
+    as_command
+        .stdin
+        .take()
+        .expect("The stdin of as is piped")
+        .write_all(assembly.as_bytes())
+        .map_err(|e| format!("Failed to write the assembly to as: {}", e))?;
+    if !as_command.wait().map_err(|e| e.to_string())?.success() {
+        return Err("Failed to assemble the program".to_string());
+    }
+
+    let status = std::process::Command::new("ld")
+        .args(["-static", "-o", output, &object])
+        .status()
+        .map_err(|e| format!("Failed to run ld: {}", e));
+    let _ = std::fs::remove_file(&object);
+    if !status?.success() {
+        return Err("Failed to link the program".to_string());
+    }
+    Ok(())
+}
+
+struct Assembly {
+    code: String,
+    labels: usize,
+    utf8: bool,
+    array_size: usize,
+    wrap_value: bool,
+    wrap_pointer: bool,
+    max_value: i64,
+}
+
+impl Assembly {
+    fn instrs(&mut self, instrs: &[Instr]) {
+        let cell = self.cell("%r12");
+        let (suffix, load) = if self.utf8 {
+            ("l", "movl")
+        } else {
+            ("b", "movzbl")
+        };
+        for instr in instrs {
+            match instr {
+                Instr::Add(n) => self.add(*n),
+                Instr::Move(n) => self.move_pointer("%r12", *n),
+                Instr::Clear => self.line(&format!("mov{suffix} $0, {cell}")),
+                Instr::MulLoop(targets) => {
+                    let end = self.new_label();
+                    self.line(&format!("{load} {cell}, %r8d"));
+                    self.line("test %r8d, %r8d");
+                    self.line(&format!("jz {end}"));
+                    for (offset, factor) in targets {
+                        self.line("mov %r12, %rcx");
+                        self.move_pointer("%rcx", *offset);
+                        self.mul_add(*factor);
+                    }
+                    self.line(&format!("mov{suffix} $0, {cell}"));
+                    self.label(&end);
+                }
+                Instr::Print => {
+                    self.line(&format!("{load} {cell}, %edi"));
+                    self.line("call output");
+                }
+                Instr::Read => {
+                    self.line(if self.utf8 {
+                        "call input"
+                    } else {
+                        "call read_byte"
+                    });
+                    if self.utf8 {
+                        self.line(&format!("movl %eax, {cell}"));
+                    } else {
+                        self.line(&format!("movb %al, {cell}"));
+                    }
+                }
+                Instr::Loop(loop_body) => {
+                    let start = self.new_label();
+                    let end = self.new_label();
+                    self.line(&format!("cmp{suffix} $0, {cell}"));
+                    self.line(&format!("je {end}"));
+                    self.label(&start);
+                    self.instrs(loop_body);
+                    self.line(&format!("cmp{suffix} $0, {cell}"));
+                    self.line(&format!("jne {start}"));
+                    self.label(&end);
+                }
+            }
+        }
+    }
+
+    /// The memory operand of the cell at the pointer in the register
+    fn cell(&self, register: &str) -> String {
+        if self.utf8 {
+            format!("(%rbx,{register},4)")
+        } else {
+            format!("(%rbx,{register})")
+        }
+    }
+
+    fn add(&mut self, n: i64) {
+        let cell = self.cell("%r12");
+        let suffix = if self.utf8 { "l" } else { "b" };
+        if self.wrap_value {
+            let n = n.rem_euclid(self.max_value + 1);
+            if !self.utf8 {
+                self.line(&format!("addb ${n}, {cell}"));
+                return;
+            }
+            let wrapped = self.new_label();
+            self.line(&format!("movl {cell}, %eax"));
+            self.line(&format!("add ${n}, %eax"));
+            self.line(&format!("cmp ${}, %eax", self.max_value + 1));
+            self.line(&format!("jb {wrapped}"));
+            self.line(&format!("sub ${}, %eax", self.max_value + 1));
+            self.label(&wrapped);
+            self.line(&format!("movl %eax, {cell}"));
+            return;
+        }
+
+        let value = n.unsigned_abs() as i64;
+        if value > self.max_value {
+            self.line("jmp value_out_of_bounds");
+        } else if n > 0 {
+            self.line(&format!("cmp{suffix} ${}, {cell}", self.max_value - value));
+            self.line("ja value_out_of_bounds");
+            self.line(&format!("add{suffix} ${value}, {cell}"));
+        } else {
+            self.line(&format!("cmp{suffix} ${value}, {cell}"));
+            self.line("jb value_out_of_bounds");
+            self.line(&format!("sub{suffix} ${value}, {cell}"));
+        }
+    }
+
+    fn move_pointer(&mut self, register: &str, n: isize) {
+        if self.wrap_pointer {
+            let wrapped = self.new_label();
+            let n = n.rem_euclid(self.array_size as isize);
+            self.line(&format!("add ${n}, {register}"));
+            self.line(&format!("cmp ${}, {register}", self.array_size));
+            self.line(&format!("jb {wrapped}"));
+            self.line(&format!("sub ${}, {register}", self.array_size));
+            self.label(&wrapped);
+        } else if n > 0 {
+            self.line(&format!("add ${n}, {register}"));
+            self.line(&format!("cmp ${}, {register}", self.array_size));
+            self.line("jae pointer_out_of_bounds_right");
+        } else {
+            self.line(&format!("sub ${}, {register}", n.unsigned_abs()));
+            self.line("jb pointer_out_of_bounds_left");
+        }
+    }
+
+    /// Add `%r8` multiplied by the factor to the cell at `%rcx`,
+    /// the multiplication loops are only used when the values wrap
+    fn mul_add(&mut self, factor: i64) {
+        let target = self.cell("%rcx");
+        let factor = factor.rem_euclid(self.max_value + 1);
+        if self.utf8 {
+            self.line(&format!("imul ${factor}, %r8, %rax"));
+            self.line(&format!("movl {target}, %edx"));
+            self.line("add %rdx, %rax");
+            self.line("xor %edx, %edx");
+            self.line(&format!("mov ${}, %esi", self.max_value + 1));
+            self.line("div %rsi");
+            self.line(&format!("movl %edx, {target}"));
+        } else {
+            self.line(&format!("imul ${factor}, %r8d, %edx"));
+            self.line(&format!("addb %dl, {target}"));
+        }
+    }
+
+    /// The I/O routines and the error exits
+    fn routines(&mut self) {
+        let pointer_code = InterpreterErrorKind::PointerOutOfBounds(0).code();
+        let errors = [
+            (
+                "pointer_out_of_bounds_right",
+                InterpreterErrorKind::PointerOutOfBounds(self.array_size).to_string(),
+                pointer_code,
+            ),
+            (
+                "pointer_out_of_bounds_left",
+                InterpreterErrorKind::PointerOutOfBounds(0).to_string(),
+                pointer_code,
+            ),
+            (
+                "value_out_of_bounds",
+                InterpreterErrorKind::ValueOutOfBounds.to_string(),
+                InterpreterErrorKind::ValueOutOfBounds.code(),
+            ),
+            (
+                "io_error",
+                InterpreterErrorKind::IoError(std::io::Error::from(
+                    std::io::ErrorKind::UnexpectedEof,
+                ))
+                .to_string(),
+                InterpreterErrorKind::IoError(std::io::Error::other("")).code(),
+            ),
+            (
+                "flush_error",
+                InterpreterErrorKind::FlushError(std::io::Error::from(
+                    std::io::ErrorKind::WriteZero,
+                ))
+                .to_string(),
+                InterpreterErrorKind::FlushError(std::io::Error::other("")).code(),
+            ),
+            (
+                "invalid_utf8",
+                InterpreterErrorKind::InvalidUtf8.to_string(),
+                InterpreterErrorKind::InvalidUtf8.code(),
+            ),
+        ];
+
+        let check_code_point = "    \
+            mov %edi, %eax\n    \
+            and $0xfffff800, %eax\n    \
+            cmp $0xd800, %eax\n    \
+            je invalid_utf8\n    \
+            cmp $0x10ffff, %edi\n    \
+            ja invalid_utf8\n";
+
+        // The chars are only decoded with the utf8 feature, the byte cells read the bytes as they are
+        let input = match self.utf8 {
+            true => format!(
+                "# Read a utf8 char from stdin to %eax\n\
+                input:\n    \
+                    call read_byte\n    \
+                    cmp $0x80, %eax\n    \
+                    jb 2f\n    \
+                    cmp $0xc0, %eax\n    \
+                    jb invalid_utf8\n    \
+                    mov %eax, %r8d\n    \
+                    mov $1, %r9d\n    \
+                    and $0x1f, %r8d\n    \
+                    cmp $0xe0, %eax\n    \
+                    jb 1f\n    \
+                    mov $2, %r9d\n    \
+                    and $0x0f, %r8d\n    \
+                    cmp $0xf0, %eax\n    \
+                    jb 1f\n    \
+                    mov $3, %r9d\n    \
+                    and $0x07, %r8d\n    \
+                    cmp $0xf8, %eax\n    \
+                    jae invalid_utf8\n\
+                1:\n    \
+                    call read_byte\n    \
+                    mov %eax, %edx\n    \
+                    and $0xc0, %edx\n    \
+                    cmp $0x80, %edx\n    \
+                    jne invalid_utf8\n    \
+                    shl $6, %r8d\n    \
+                    and $0x3f, %eax\n    \
+                    or %eax, %r8d\n    \
+                    dec %r9d\n    \
+                    jnz 1b\n    \
+                    mov %r8d, %edi\n\
+                {check_code_point}    \
+                    mov %edi, %eax\n\
+                2:\n    \
+                    ret\n\
+                \n",
+            ),
+            false => String::new(),
+        };
+
+        // The interpreter prints the cells as chars, so the 8 bits cells over 127 are utf8 encoded too
+        writeln!(
+            self.code,
+            "\n\
+            # Write the code point in %edi to stdout as utf8\n\
+            output:\n\
+            {}    \
+                lea buffer(%rip), %rsi\n    \
+                mov %edi, %eax\n    \
+                cmp $0x80, %edi\n    \
+                jae 1f\n    \
+                mov %al, (%rsi)\n    \
+                mov $1, %edx\n    \
+                jmp 4f\n\
+            1:\n    \
+                cmp $0x800, %edi\n    \
+                jae 2f\n    \
+                shr $6, %eax\n    \
+                or $0xc0, %al\n    \
+                mov %al, (%rsi)\n    \
+                mov $2, %edx\n    \
+                jmp 3f\n\
+            2:\n    \
+                cmp $0x10000, %edi\n    \
+                jae 5f\n    \
+                shr $12, %eax\n    \
+                or $0xe0, %al\n    \
+                mov %al, (%rsi)\n    \
+                mov $3, %edx\n    \
+                jmp 6f\n\
+            5:\n    \
+                shr $18, %eax\n    \
+                or $0xf0, %al\n    \
+                mov %al, (%rsi)\n    \
+                mov %edi, %eax\n    \
+                shr $12, %eax\n    \
+                and $0x3f, %al\n    \
+                or $0x80, %al\n    \
+                mov %al, 1(%rsi)\n    \
+                mov $4, %edx\n\
+            6:\n    \
+                mov %edi, %eax\n    \
+                shr $6, %eax\n    \
+                and $0x3f, %al\n    \
+                or $0x80, %al\n    \
+                mov %al, -2(%rsi,%rdx)\n\
+            3:\n    \
+                mov %edi, %eax\n    \
+                and $0x3f, %al\n    \
+                or $0x80, %al\n    \
+                mov %al, -1(%rsi,%rdx)\n\
+            4:\n    \
+                mov $1, %eax\n    \
+                mov $1, %edi\n    \
+                syscall\n    \
+                cmp $0, %rax\n    \
+                jle flush_error\n    \
+                ret\n\
+            \n\
+            {}# Read a byte from stdin to %eax\n\
+            read_byte:\n    \
+                lea buffer(%rip), %rsi\n    \
+                mov $1, %edx\n    \
+                xor %edi, %edi\n    \
+                xor %eax, %eax\n    \
+                syscall\n    \
+                cmp $1, %rax\n    \
+                jne io_error\n    \
+                movzbl buffer(%rip), %eax\n    \
+                ret\n\
+            \n\
+            # Write the message in %rsi with the length in %rdx to stderr, and exit with the code in %r13d\n\
+            fail:\n    \
+                mov $1, %eax\n    \
+                mov $2, %edi\n    \
+                syscall\n    \
+                mov $60, %eax\n    \
+                mov %r13d, %edi\n    \
+                syscall",
+            if self.utf8 { check_code_point } else { "" },
+            input,
+        )
+        .unwrap();
+
+        for (label, message, code) in &errors {
+            writeln!(
+                self.code,
+                "{label}:\n    \
+                    lea {label}_message(%rip), %rsi\n    \
+                    mov ${}, %edx\n    \
+                    mov ${code}, %r13d\n    \
+                    jmp fail",
+                message.len() + 1
+            )
+            .unwrap();
+        }
+
+        self.code.push_str("\n    .section .rodata\n");
+        for (label, message, _) in &errors {
+            writeln!(
+                self.code,
+                "{label}_message:\n    .ascii {:?}",
+                message.clone() + "\n"
+            )
+            .unwrap();
+        }
+    }
+
+    fn line(&mut self, line: &str) {
+        writeln!(self.code, "    {}", line).unwrap();
+    }
+
+    fn label(&mut self, label: &str) {
+        writeln!(self.code, "{}:", label).unwrap();
+    }
+
+    fn new_label(&mut self) -> String {
+        self.labels += 1;
+        format!(".L{}", self.labels)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bf_interpreter::interpreter::to_bf_commands;
+    use crate::utils;
+    use pretty_assertions::assert_eq;
+    use std::process::{Command, Output, Stdio};
+
+    fn compile_code(code: &str, features: &[Feature]) -> String {
+        compile(
+            &to_bf_commands(code.chars().collect()).unwrap(),
+            30000,
+            features,
+        )
+    }
+
+    #[test]
+    fn checked_commands() {
+        let code = compile_code("+++>>-<.,", &[]);
+
+        assert!(code.contains(
+            "    cmpb $252, (%rbx,%r12)\n    \
+                ja value_out_of_bounds\n    \
+                addb $3, (%rbx,%r12)\n    \
+                add $2, %r12\n    \
+                cmp $30000, %r12\n    \
+                jae pointer_out_of_bounds_right\n    \
+                cmpb $1, (%rbx,%r12)\n    \
+                jb value_out_of_bounds\n    \
+                subb $1, (%rbx,%r12)\n    \
+                sub $1, %r12\n    \
+                jb pointer_out_of_bounds_left\n    \
+                movzbl (%rbx,%r12), %edi\n    \
+                call output\n    \
+                call read_byte\n    \
+                movb %al, (%rbx,%r12)\n"
+        ));
+        assert!(code.contains("    .ascii \"Pointer out of bounds 30000\\n\"\n"));
+        assert!(!code.contains("input:"));
+    }
+
+    #[test]
+    fn wrapping_commands_and_loops() {
+        let code = compile_code("[--<]", &[Feature::NoReverseValue, Feature::ReversePointer]);
+
+        assert!(code.contains(
+            "    cmpb $0, (%rbx,%r12)\n    \
+                je .L2\n\
+            .L1:\n    \
+                addb $254, (%rbx,%r12)\n    \
+                add $29999, %r12\n    \
+                cmp $30000, %r12\n    \
+                jb .L3\n    \
+                sub $30000, %r12\n\
+            .L3:\n    \
+                cmpb $0, (%rbx,%r12)\n    \
+                jne .L1\n\
+            .L2:\n"
+        ));
+    }
+
+    #[test]
+    fn features_select_the_cell_width() {
+        let code = compile_code("[->++<]", &[Feature::AllowUtf8, Feature::NoReverseValue]);
+
+        assert!(code.contains("    .zero 120000\n"));
+        assert!(code.contains("    movl (%rbx,%r12,4), %r8d\n"));
+        assert!(code.contains("    mov $1114112, %esi\n"));
+        assert!(code.contains("    movl $0, (%rbx,%r12,4)\n"));
+    }
+
+    /// Link the program with as and ld and run it with the input piped to stdin
+    fn link_and_run(name: &str, code: &str, features: &[Feature], input: &[u8]) -> Output {
+        let dir = std::env::temp_dir().join(format!("bfy-asm-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let binary = dir.join(name);
+        link(&compile_code(code, features), binary.to_str().unwrap()).unwrap();
+
+        let mut child = Command::new(&binary)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child.stdin.take().unwrap().write_all(input).unwrap();
+        let output = child.wait_with_output().unwrap();
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        output
+    }
+
+    #[test]
+    fn link_and_run_hello_world() {
+        if !crate::compiler::tool_exists("as") || !crate::compiler::tool_exists("ld") {
+            return;
+        }
+        let code = utils::read_brainfuck_code(&String::from("test_code/hello_world.bf"));
+
+        let output = link_and_run("hello_world", &code, &[], b"");
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "Hello world!\n");
+        assert_eq!(output.status.code(), Some(0));
+    }
+
+    #[test]
+    fn link_and_run_non_ascii_input() {
+        if !crate::compiler::tool_exists("as") || !crate::compiler::tool_exists("ld") {
+            return;
+        }
+        // A byte cell reads the piped bytes one by one, like the interpreter
+        let output = link_and_run("bytes", ",.,.", &[], b"\xe9A");
+        assert_eq!(output.stdout, "\u{e9}A".as_bytes());
+        assert_eq!(output.status.code(), Some(0));
+
+        // The utf8 cells read the chars, and fail on an invalid lead byte
+        let output = link_and_run("chars", ",.,.", &[Feature::AllowUtf8], "éA".as_bytes());
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "éA");
+        assert_eq!(output.status.code(), Some(0));
+        let output = link_and_run("invalid", ",.", &[Feature::AllowUtf8], b"\x80AAA");
+        assert_eq!(output.stdout, b"");
+        assert_eq!(output.status.code(), Some(16));
+    }
+}
@@ -0,0 +1,148 @@
+use crate::bf_interpreter::interpreter::BfCommand;
+use crate::compiler::ir::{self, Instr};
+use std::fmt::Write;
+
+/// Decompile the brainfuck commands to a readable pseudo-code,
+/// the cell offsets are relative to the pointer at the entry of the loop (or the program)
+/// # Arguments
+/// * `bf_commands` - The parsed brainfuck program
+pub fn decompile(bf_commands: &[BfCommand]) -> String {
+    let mut pseudo_code = PseudoCode {
+        code: String::new(),
+        depth: 0,
+    };
+    // The pseudo-code doesn't show where the program fails, so the loops are read
+    // as if the values and the pointer wrap, e.g. the multiplication loops without `nrv`
+    pseudo_code.block(&ir::optimize_with(bf_commands, true, true));
+    pseudo_code.code
+}
+
+struct PseudoCode {
+    code: String,
+    depth: usize,
+}
+
+impl PseudoCode {
+    /// Write the block, the pointer moves are delayed to the loops and the end of the block
+    fn block(&mut self, instrs: &[Instr]) {
+        let mut offset = 0;
+        for instr in instrs {
+            match instr {
+                Instr::Add(n) => self.line(&format!("{} {}", cell(offset), assign_op(*n, ""))),
+                Instr::Move(n) => offset += n,
+                Instr::Clear => self.line(&format!("{} = 0", cell(offset))),
+                Instr::MulLoop(targets) => {
+                    for (target, factor) in targets {
+                        let value = match factor.abs() {
+                            1 => cell(offset),
+                            _ => format!("{} * {}", cell(offset), factor.abs()),
+                        };
+                        self.line(&format!(
+                            "{} {}",
+                            cell(offset + target),
+                            assign_op(factor.signum(), &value)
+                        ));
+                    }
+                    self.line(&format!("{} = 0", cell(offset)));
+                }
+                Instr::Print => self.line(&format!("print({})", cell(offset))),
+                Instr::Read => self.line(&format!("{} = read()", cell(offset))),
+                Instr::Loop(loop_body) => {
+                    self.move_pointer(offset);
+                    offset = 0;
+                    self.line("while cell[p] {");
+                    self.depth += 1;
+                    self.block(loop_body);
+                    self.depth -= 1;
+                    self.line("}");
+                }
+            }
+        }
+        self.move_pointer(offset);
+    }
+
+    fn move_pointer(&mut self, offset: isize) {
+        match offset {
+            0 => {}
+            offset if offset > 0 => self.line(&format!("p += {}", offset)),
+            offset => self.line(&format!("p -= {}", -offset)),
+        }
+    }
+
+    fn line(&mut self, line: &str) {
+        writeln!(self.code, "{}{}", "    ".repeat(self.depth), line).unwrap();
+    }
+}
+
+/// The cell at the offset from the pointer, e.g. `cell[p+2]`
+fn cell(offset: isize) -> String {
+    match offset {
+        0 => "cell[p]".to_string(),
+        offset if offset > 0 => format!("cell[p+{}]", offset),
+        offset => format!("cell[p-{}]", -offset),
+    }
+}
+
+/// The compound assignment of the value, e.g. `+= 3`, or of the operand by the sign of the value
+fn assign_op(value: i64, operand: &str) -> String {
+    let operator = if value < 0 { "-=" } else { "+=" };
+    if operand.is_empty() {
+        format!("{} {}", operator, value.abs())
+    } else {
+        format!("{} {}", operator, operand)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bf_interpreter::interpreter::to_bf_commands;
+    use pretty_assertions::assert_eq;
+
+    fn decompile_code(code: &str) -> String {
+        decompile(&to_bf_commands(code.chars().collect()).unwrap())
+    }
+
+    #[test]
+    fn offsets_are_relative_to_the_loop_entry() {
+        assert_eq!(
+            decompile_code("++++++++[>++++.<-]>+.>,<<"),
+            "cell[p] += 8\n\
+            while cell[p] {\n    \
+                cell[p+1] += 4\n    \
+                print(cell[p+1])\n    \
+                cell[p] -= 1\n\
+            }\n\
+            cell[p+1] += 1\n\
+            print(cell[p+1])\n\
+            cell[p+2] = read()\n"
+        );
+        assert_eq!(
+            decompile_code(">>[-<[-]>]<"),
+            "p += 2\n\
+            while cell[p] {\n    \
+                cell[p] -= 1\n    \
+                cell[p-1] = 0\n\
+            }\n\
+            p -= 1\n"
+        );
+    }
+
+    #[test]
+    fn multiplication_loops() {
+        assert_eq!(
+            decompile_code(">[->+++>-<<]"),
+            "cell[p+2] += cell[p+1] * 3\n\
+            cell[p+3] -= cell[p+1]\n\
+            cell[p+1] = 0\n\
+            p += 1\n"
+        );
+        // The cells can overflow in the middle of the loop without `nrv`, it is still read as a multiplication
+        assert_eq!(
+            decompile_code("+++[>>+++<<-]"),
+            "cell[p] += 3\n\
+            cell[p+2] += cell[p] * 3\n\
+            cell[p] = 0\n"
+        );
+    }
+}
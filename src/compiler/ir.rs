@@ -0,0 +1,256 @@
+use crate::arguments::Feature;
+use crate::bf_interpreter::cell::Cell;
+use crate::bf_interpreter::interpreter::BfCommand;
+
+/// The optimised representation of a brainfuck program, shared by the backends
+#[derive(Debug, PartialEq, Clone)]
+pub enum Instr {
+    /// Add the value to the current cell, negative values subtract (e.g. `+++`)
+    Add(i64),
+    /// Move the pointer by the offset, negative offsets move to the left (e.g. `>>`)
+    Move(isize),
+    /// Set the current cell to zero (e.g. `[-]`)
+    Clear,
+    /// For each `(offset, factor)` add the current cell multiplied by the factor
+    /// to the cell at the offset, then clear the current cell (e.g. `[->+++>+<<]`)
+    MulLoop(Vec<(isize, i64)>),
+    Print,
+    Read,
+    Loop(Vec<Instr>),
+}
+
+/// Optimise the brainfuck commands, without changing what the program does under the given features
+/// # Arguments
+/// * `bf_commands` - The parsed brainfuck program
+/// * `features` - The extra features to follow, the same ones the interpreter uses
+pub fn optimize(bf_commands: &[BfCommand], features: &[Feature]) -> Vec<Instr> {
+    optimize_with(
+        bf_commands,
+        Cell::wraps(features),
+        features.contains(&Feature::ReversePointer),
+    )
+}
+
+/// Optimise the brainfuck commands as if the values and the pointer wrap or not,
+/// the instructions don't fail where the program fails if they don't wrap
+/// # Arguments
+/// * `bf_commands` - The parsed brainfuck program
+/// * `wrap_value` - The values wrap, so the loops of the overflowing cells can be replaced
+/// * `wrap_pointer` - The pointer wraps, so the moves out of the array can be folded
+pub fn optimize_with(
+    bf_commands: &[BfCommand],
+    wrap_value: bool,
+    wrap_pointer: bool,
+) -> Vec<Instr> {
+    // Without the wrapping, `+-` and `<>` can fail on their first command, so only the runs
+    // of the same command can be folded
+    let mut instrs = Vec::new();
+    for command in bf_commands {
+        match command {
+            BfCommand::IncVal | BfCommand::DecVal => {
+                let n = if *command == BfCommand::IncVal { 1 } else { -1 };
+                match instrs.last_mut() {
+                    Some(Instr::Add(value)) if wrap_value || value.signum() == n => {
+                        *value += n;
+                        if *value == 0 {
+                            instrs.pop();
+                        }
+                    }
+                    _ => instrs.push(Instr::Add(n)),
+                }
+            }
+            BfCommand::IncPtr | BfCommand::DecPtr => {
+                let n = if *command == BfCommand::IncPtr { 1 } else { -1 };
+                match instrs.last_mut() {
+                    Some(Instr::Move(offset)) if wrap_pointer || offset.signum() == n => {
+                        *offset += n;
+                        if *offset == 0 {
+                            instrs.pop();
+                        }
+                    }
+                    _ => instrs.push(Instr::Move(n)),
+                }
+            }
+            BfCommand::Print => instrs.push(Instr::Print),
+            BfCommand::Read => instrs.push(Instr::Read),
+            BfCommand::Loop(loop_body) => {
+                let loop_body = optimize_with(loop_body, wrap_value, wrap_pointer);
+                instrs.push(
+                    loop_idiom(&loop_body, wrap_value, wrap_pointer)
+                        .unwrap_or(Instr::Loop(loop_body)),
+                );
+            }
+            BfCommand::Procedure(_)
+            | BfCommand::Call
+            | BfCommand::Fork
+            | BfCommand::Extended(_)
+            | BfCommand::File(_)
+            | BfCommand::Debug => {
+                unreachable!(
+                    "The extra commands of the features are only parsed for the interpreter"
+                )
+            }
+        }
+    }
+    instrs
+}
+
+/// Recognise the loops that can be replaced with a single instruction
+fn loop_idiom(loop_body: &[Instr], wrap_value: bool, wrap_pointer: bool) -> Option<Instr> {
+    match loop_body {
+        [Instr::Add(-1)] => return Some(Instr::Clear),
+        [Instr::Add(1)] if wrap_value => return Some(Instr::Clear),
+        _ => {}
+    }
+    // Without the wrapping, the target cells can overflow in the middle of the loop
+    if !wrap_value {
+        return None;
+    }
+
+    let mut offset = 0;
+    let mut min_offset = 0;
+    let mut max_offset = 0;
+    let mut targets: Vec<(isize, i64)> = Vec::new();
+    for instr in loop_body {
+        match instr {
+            Instr::Add(value) => match targets.iter_mut().find(|(o, _)| *o == offset) {
+                Some((_, factor)) => *factor += value,
+                None => targets.push((offset, *value)),
+            },
+            Instr::Move(n) => {
+                offset += n;
+                min_offset = min_offset.min(offset);
+                max_offset = max_offset.max(offset);
+            }
+            _ => return None,
+        }
+    }
+    if offset != 0 {
+        return None;
+    }
+
+    let loop_cell = targets.iter().position(|(o, _)| *o == 0)?;
+    if targets.remove(loop_cell).1 != -1 {
+        return None;
+    }
+    targets.retain(|(_, factor)| *factor != 0);
+
+    // The pointer must not pass any cell that is not a target, otherwise the bounds check
+    // of the targets won't fail where the loop itself fails
+    let is_target = |o: isize| o == 0 || targets.iter().any(|(t, _)| *t == o);
+    if !wrap_pointer && (!is_target(min_offset) || !is_target(max_offset)) {
+        return None;
+    }
+
+    Some(Instr::MulLoop(targets))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bf_interpreter::interpreter::to_bf_commands;
+    use pretty_assertions::assert_eq;
+
+    const WRAP: &[Feature] = &[Feature::NoReverseValue];
+
+    fn optimize_code(code: &str, features: &[Feature]) -> Vec<Instr> {
+        optimize(&to_bf_commands(code.chars().collect()).unwrap(), features)
+    }
+
+    #[test]
+    fn fold_runs() {
+        assert_eq!(
+            optimize_code("+++-->><<<.,", &[Feature::NoReverseValue]),
+            vec![
+                Instr::Add(1),
+                Instr::Move(2),
+                Instr::Move(-3),
+                Instr::Print,
+                Instr::Read
+            ]
+        );
+        assert_eq!(
+            optimize_code("+-<>", &[Feature::NoReverseValue, Feature::ReversePointer]),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn keep_the_failing_commands_without_wrapping() {
+        assert_eq!(
+            optimize_code("++-<<>", &[]),
+            vec![
+                Instr::Add(2),
+                Instr::Add(-1),
+                Instr::Move(-2),
+                Instr::Move(1)
+            ]
+        );
+    }
+
+    #[test]
+    fn clear_loops() {
+        assert_eq!(optimize_code("[-]", &[]), vec![Instr::Clear]);
+        assert_eq!(
+            optimize_code("[+]", &[Feature::NoReverseValue]),
+            vec![Instr::Clear]
+        );
+        assert_eq!(
+            optimize_code("[-][+]", &[]),
+            vec![Instr::Clear, Instr::Loop(vec![Instr::Add(1)])]
+        );
+    }
+
+    #[test]
+    fn multiplication_loops() {
+        assert_eq!(
+            optimize_code("[->+++>+<<]", &[Feature::NoReverseValue]),
+            vec![Instr::MulLoop(vec![(1, 3), (2, 1)])]
+        );
+        assert_eq!(
+            optimize_code("[<--->-]", &[Feature::NoReverseValue]),
+            vec![Instr::MulLoop(vec![(-1, -3)])]
+        );
+        assert_eq!(
+            optimize_code("[->>+<<<+>]", &[Feature::NoReverseValue]),
+            vec![Instr::MulLoop(vec![(2, 1), (-1, 1)])]
+        );
+        assert_eq!(
+            optimize_code("[->+<]", &[]),
+            vec![Instr::Loop(vec![
+                Instr::Add(-1),
+                Instr::Move(1),
+                Instr::Add(1),
+                Instr::Move(-1)
+            ])]
+        );
+    }
+
+    #[test]
+    fn not_multiplication_loops() {
+        // The loop cell is not decremented by one
+        assert!(matches!(
+            optimize_code("[-->+<]", WRAP)[..],
+            [Instr::Loop(_)]
+        ));
+        // The pointer is not balanced
+        assert!(matches!(optimize_code("[->+]", WRAP)[..], [Instr::Loop(_)]));
+        // The body has I/O
+        assert!(matches!(
+            optimize_code("[->.<]", WRAP)[..],
+            [Instr::Loop(_)]
+        ));
+        // The pointer passes a cell beyond the targets
+        assert!(matches!(
+            optimize_code("[->>><+<<]", WRAP)[..],
+            [Instr::Loop(_)]
+        ));
+        assert_eq!(
+            optimize_code(
+                "[->>><+<<]",
+                &[Feature::NoReverseValue, Feature::ReversePointer]
+            ),
+            vec![Instr::MulLoop(vec![(2, 1)])]
+        );
+    }
+}
@@ -0,0 +1,497 @@
+use crate::bf_interpreter::error::InterpreterError;
+use crate::bf_interpreter::interpreter::Interpreter;
+
+/// Run the brainfuck code as native code, with the same results as `Interpreter::run`.
+/// Falls back to the interpreter on the platforms and the features that the jit doesn't support
+/// # Arguments
+/// * `interpreter` - The interpreter that holds the array, the pointer and the features
+/// * `bf_code` - The brainfuck code to run
+pub fn run(interpreter: &mut Interpreter, bf_code: String) -> Result<i32, InterpreterError> {
+    #[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+    if x86_64::is_supported(interpreter) {
+        return x86_64::run(interpreter, bf_code);
+    }
+
+    info!("The jit is not supported here, falling back to the interpreter");
+    interpreter.run(bf_code)
+}
+
+#[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+mod x86_64 {
+    use crate::arguments::Feature;
+    use crate::bf_interpreter::cell::Cell;
+    use crate::bf_interpreter::error::{InterpreterError, InterpreterErrorKind};
+    use crate::bf_interpreter::interpreter::{to_bf_commands, Interpreter};
+    use crate::compiler::ir::{self, Instr};
+
+    /// The status codes returned by the compiled code
+    const STATUS_OK: u32 = 0;
+    const STATUS_POINTER_RIGHT: u32 = 1;
+    const STATUS_POINTER_LEFT: u32 = 2;
+    const STATUS_VALUE: u32 = 3;
+    const STATUS_CALLBACK: u32 = 4;
+
+    /// The cells are 8 bits wide, so the utf8 feature and the bit tape languages are left to the interpreter,
+    /// like the features with the extra commands, and the pointer offsets are 32 bits wide
+    pub fn is_supported(interpreter: &Interpreter) -> bool {
+        !interpreter.features.contains(&Feature::AllowUtf8)
+            && !interpreter.lang.bit_tape()
+            && interpreter.features.iter().all(|f| f.commands().is_empty())
+            && !interpreter.cells.is_empty()
+            && interpreter.cells.len() <= i32::MAX as usize
+    }
+
+    pub fn run(interpreter: &mut Interpreter, bf_code: String) -> Result<i32, InterpreterError> {
+        interpreter.bf_commands = to_bf_commands(bf_code.chars().collect())?;
+        let instrs = ir::optimize(&interpreter.bf_commands, &interpreter.features);
+
+        let mut assembler = Assembler::new(
+            interpreter.cells.len(),
+            !Cell::wraps(&interpreter.features),
+            interpreter.features.contains(&Feature::ReversePointer),
+        );
+        assembler.compile(&instrs);
+        let code = ExecutableCode::new(&assembler.finish())?;
+
+        let mut tape: Vec<u8> = interpreter.cells.iter().map(|c| c.get_value()).collect();
+        let mut context = Context {
+            pointer: interpreter.pointer,
+            tape: tape.as_mut_ptr(),
+            interpreter,
+            error: None,
+        };
+        let status = code.call(&mut context);
+        let error = context.error.take();
+        let pointer = context.pointer;
+
+        for (cell, value) in interpreter.cells.iter_mut().zip(tape) {
+            *cell = Cell::Byte(value);
+        }
+        interpreter.pointer = pointer;
+
+        match status {
+            STATUS_OK => Ok(0),
+            STATUS_POINTER_RIGHT => {
+                Err(InterpreterErrorKind::PointerOutOfBounds(interpreter.cells.len()).to_error())
+            }
+            STATUS_POINTER_LEFT => Err(InterpreterErrorKind::PointerOutOfBounds(0).to_error()),
+            STATUS_VALUE => Err(InterpreterErrorKind::ValueOutOfBounds.to_error()),
+            _ => Err(error.expect("The callback failed without an error")),
+        }
+    }
+
+    /// The state shared between the compiled code and the I/O callbacks,
+    /// the compiled code writes the final pointer to the first field
+    #[repr(C)]
+    struct Context<'a> {
+        pointer: usize,
+        tape: *mut u8,
+        interpreter: &'a mut Interpreter,
+        error: Option<InterpreterError>,
+    }
+
+    impl Context<'_> {
+        /// Give the interpreter the cell the compiled code points to, run the I/O on it and copy it back
+        fn with_cell(
+            &mut self,
+            pointer: usize,
+            io: fn(&mut Interpreter) -> Result<(), InterpreterError>,
+        ) -> u32 {
+            // SAFETY: the compiled code only passes pointers that are inside the tape
+            let value = unsafe { &mut *self.tape.add(pointer) };
+            self.interpreter.pointer = pointer;
+            self.interpreter.cells[pointer] = Cell::Byte(*value);
+            match io(self.interpreter) {
+                Ok(_) => {
+                    *value = self.interpreter.cells[pointer].get_value();
+                    STATUS_OK
+                }
+                Err(e) => {
+                    self.error = Some(e);
+                    STATUS_CALLBACK
+                }
+            }
+        }
+    }
+
+    extern "C" fn output_value(context: *mut Context, pointer: usize) -> u32 {
+        // SAFETY: the compiled code passes back the context it was called with
+        unsafe { &mut *context }.with_cell(pointer, Interpreter::output_value)
+    }
+
+    extern "C" fn input_value(context: *mut Context, pointer: usize) -> u32 {
+        // SAFETY: the compiled code passes back the context it was called with
+        unsafe { &mut *context }.with_cell(pointer, Interpreter::input_value)
+    }
+
+    /// A page of memory that holds the compiled code
+    struct ExecutableCode {
+        memory: *mut libc::c_void,
+        len: usize,
+    }
+
+    impl ExecutableCode {
+        fn new(code: &[u8]) -> Result<Self, InterpreterError> {
+            let len = code.len();
+            // SAFETY: a fresh anonymous mapping, made executable only after the code is copied
+            unsafe {
+                let memory = libc::mmap(
+                    std::ptr::null_mut(),
+                    len,
+                    libc::PROT_READ | libc::PROT_WRITE,
+                    libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                    -1,
+                    0,
+                );
+                if memory == libc::MAP_FAILED {
+                    return Err(
+                        InterpreterErrorKind::IoError(std::io::Error::last_os_error()).to_error(),
+                    );
+                }
+                std::ptr::copy_nonoverlapping(code.as_ptr(), memory as *mut u8, len);
+                if libc::mprotect(memory, len, libc::PROT_READ | libc::PROT_EXEC) != 0 {
+                    let error = std::io::Error::last_os_error();
+                    libc::munmap(memory, len);
+                    return Err(InterpreterErrorKind::IoError(error).to_error());
+                }
+                Ok(Self { memory, len })
+            }
+        }
+
+        fn call(&self, context: &mut Context) -> u32 {
+            // SAFETY: the code is generated by the `Assembler` for this signature,
+            // and only touches the tape the context points to
+            unsafe {
+                let function: extern "C" fn(*mut u8, usize, *mut Context) -> u32 =
+                    std::mem::transmute(self.memory);
+                function(context.tape, context.pointer, context)
+            }
+        }
+    }
+
+    impl Drop for ExecutableCode {
+        fn drop(&mut self) {
+            // SAFETY: the mapping is owned by this struct
+            unsafe {
+                libc::munmap(self.memory, self.len);
+            }
+        }
+    }
+
+    /// Writes the x86-64 machine code, the registers are:
+    /// * `rbx` - The tape
+    /// * `r12` - The pointer
+    /// * `r13` - The context
+    /// * `r14` - The array size
+    struct Assembler {
+        code: Vec<u8>,
+        labels: Vec<Option<usize>>,
+        jumps: Vec<(usize, usize)>,
+        array_size: usize,
+        no_reverse_value: bool,
+        reverse_pointer: bool,
+        exit: usize,
+        pointer_right: usize,
+        pointer_left: usize,
+        value_out_of_bounds: usize,
+    }
+
+    impl Assembler {
+        fn new(array_size: usize, no_reverse_value: bool, reverse_pointer: bool) -> Self {
+            let mut assembler = Self {
+                code: Vec::new(),
+                labels: Vec::new(),
+                jumps: Vec::new(),
+                array_size,
+                no_reverse_value,
+                reverse_pointer,
+                exit: 0,
+                pointer_right: 0,
+                pointer_left: 0,
+                value_out_of_bounds: 0,
+            };
+            assembler.exit = assembler.new_label();
+            assembler.pointer_right = assembler.new_label();
+            assembler.pointer_left = assembler.new_label();
+            assembler.value_out_of_bounds = assembler.new_label();
+
+            // push rbx; push r12; push r13; push r14; push r15
+            assembler.emit(&[0x53, 0x41, 0x54, 0x41, 0x55, 0x41, 0x56, 0x41, 0x57]);
+            // mov rbx, rdi; mov r12, rsi; mov r13, rdx
+            assembler.emit(&[0x48, 0x89, 0xfb, 0x49, 0x89, 0xf4, 0x49, 0x89, 0xd5]);
+            // mov r14, array_size
+            assembler.emit(&[0x49, 0xbe]);
+            assembler.emit(&(array_size as u64).to_le_bytes());
+            assembler
+        }
+
+        fn compile(&mut self, instrs: &[Instr]) {
+            for instr in instrs {
+                match instr {
+                    Instr::Add(n) => self.add(*n),
+                    Instr::Move(n) => self.move_pointer(*n),
+                    Instr::Clear => {
+                        // mov byte [rbx + r12], 0
+                        self.emit(&[0x42, 0xc6, 0x04, 0x23, 0x00]);
+                    }
+                    Instr::MulLoop(targets) => self.mul_loop(targets),
+                    Instr::Print => self.call(output_value as *const ()),
+                    Instr::Read => self.call(input_value as *const ()),
+                    Instr::Loop(loop_body) => {
+                        let start = self.new_label();
+                        let end = self.new_label();
+                        // cmp byte [rbx + r12], 0; je end
+                        self.emit(&[0x42, 0x80, 0x3c, 0x23, 0x00]);
+                        self.jump(&[0x0f, 0x84], end);
+                        self.bind(start);
+                        self.compile(loop_body);
+                        // cmp byte [rbx + r12], 0; jne start
+                        self.emit(&[0x42, 0x80, 0x3c, 0x23, 0x00]);
+                        self.jump(&[0x0f, 0x85], start);
+                        self.bind(end);
+                    }
+                }
+            }
+        }
+
+        fn finish(mut self) -> Vec<u8> {
+            // xor eax, eax; jmp exit
+            self.emit(&[0x31, 0xc0]);
+            self.jump(&[0xe9], self.exit);
+            for (label, status) in [
+                (self.pointer_right, STATUS_POINTER_RIGHT),
+                (self.pointer_left, STATUS_POINTER_LEFT),
+                (self.value_out_of_bounds, STATUS_VALUE),
+            ] {
+                self.bind(label);
+                // mov eax, status; jmp exit
+                self.emit(&[0xb8]);
+                self.emit(&status.to_le_bytes());
+                self.jump(&[0xe9], self.exit);
+            }
+
+            self.bind(self.exit);
+            // mov [r13], r12
+            self.emit(&[0x4d, 0x89, 0x65, 0x00]);
+            // pop r15; pop r14; pop r13; pop r12; pop rbx; ret
+            self.emit(&[0x41, 0x5f, 0x41, 0x5e, 0x41, 0x5d, 0x41, 0x5c, 0x5b, 0xc3]);
+
+            for (at, label) in std::mem::take(&mut self.jumps) {
+                let target = self.labels[label].expect("Jump to an unbound label");
+                let offset = target as i32 - (at as i32 + 4);
+                self.code[at..at + 4].copy_from_slice(&offset.to_le_bytes());
+            }
+            self.code
+        }
+
+        fn add(&mut self, n: i64) {
+            if !self.no_reverse_value {
+                // add byte [rbx + r12], n
+                self.emit(&[0x42, 0x80, 0x04, 0x23, n.rem_euclid(256) as u8]);
+                return;
+            }
+            let value = n.unsigned_abs();
+            if value > u8::MAX as u64 {
+                self.jump(&[0xe9], self.value_out_of_bounds);
+            } else if n > 0 {
+                // cmp byte [rbx + r12], 255 - n; ja value_out_of_bounds; add byte [rbx + r12], n
+                self.emit(&[0x42, 0x80, 0x3c, 0x23, u8::MAX - value as u8]);
+                self.jump(&[0x0f, 0x87], self.value_out_of_bounds);
+                self.emit(&[0x42, 0x80, 0x04, 0x23, value as u8]);
+            } else {
+                // cmp byte [rbx + r12], n; jb value_out_of_bounds; sub byte [rbx + r12], n
+                self.emit(&[0x42, 0x80, 0x3c, 0x23, value as u8]);
+                self.jump(&[0x0f, 0x82], self.value_out_of_bounds);
+                self.emit(&[0x42, 0x80, 0x2c, 0x23, value as u8]);
+            }
+        }
+
+        fn move_pointer(&mut self, n: isize) {
+            if self.reverse_pointer {
+                let n = n.rem_euclid(self.array_size as isize) as u32;
+                // add r12, n; cmp r12, r14; jb +3; sub r12, r14
+                self.emit(&[0x49, 0x81, 0xc4]);
+                self.emit(&n.to_le_bytes());
+                self.emit(&[0x4d, 0x39, 0xf4, 0x72, 0x03, 0x4d, 0x29, 0xf4]);
+            } else if n > 0 {
+                // The bounds are checked before moving, so the pointer stays on the tape after an error
+                // mov r15, r14; sub r15, r12; cmp r15, n; jbe pointer_right; add r12, n
+                self.emit(&[0x4d, 0x89, 0xf7, 0x4d, 0x29, 0xe7, 0x49, 0x81, 0xff]);
+                self.emit(&(n as u32).to_le_bytes());
+                self.jump(&[0x0f, 0x86], self.pointer_right);
+                self.emit(&[0x49, 0x81, 0xc4]);
+                self.emit(&(n as u32).to_le_bytes());
+            } else {
+                // cmp r12, n; jb pointer_left; sub r12, n
+                self.emit(&[0x49, 0x81, 0xfc]);
+                self.emit(&(n.unsigned_abs() as u32).to_le_bytes());
+                self.jump(&[0x0f, 0x82], self.pointer_left);
+                self.emit(&[0x49, 0x81, 0xec]);
+                self.emit(&(n.unsigned_abs() as u32).to_le_bytes());
+            }
+        }
+
+        fn mul_loop(&mut self, targets: &[(isize, i64)]) {
+            let end = self.new_label();
+            // movzx eax, byte [rbx + r12]; test eax, eax; je end
+            self.emit(&[0x42, 0x0f, 0xb6, 0x04, 0x23, 0x85, 0xc0]);
+            self.jump(&[0x0f, 0x84], end);
+            for (offset, factor) in targets {
+                let offset = if self.reverse_pointer {
+                    offset.rem_euclid(self.array_size as isize)
+                } else {
+                    *offset
+                };
+                // mov r15, r12; add r15, offset
+                self.emit(&[0x4d, 0x89, 0xe7, 0x49, 0x81, 0xc7]);
+                self.emit(&(offset as i32).to_le_bytes());
+                if self.reverse_pointer {
+                    // cmp r15, r14; jb +3; sub r15, r14
+                    self.emit(&[0x4d, 0x39, 0xf7, 0x72, 0x03, 0x4d, 0x29, 0xf7]);
+                } else if offset > 0 {
+                    // cmp r15, r14; jae pointer_right
+                    self.emit(&[0x4d, 0x39, 0xf7]);
+                    self.jump(&[0x0f, 0x83], self.pointer_right);
+                } else {
+                    // test r15, r15; js pointer_left
+                    self.emit(&[0x4d, 0x85, 0xff]);
+                    self.jump(&[0x0f, 0x88], self.pointer_left);
+                }
+                // imul ecx, eax, factor; add byte [rbx + r15], cl
+                self.emit(&[0x69, 0xc8]);
+                self.emit(&(factor.rem_euclid(256) as u32).to_le_bytes());
+                self.emit(&[0x42, 0x00, 0x0c, 0x3b]);
+            }
+            // mov byte [rbx + r12], 0
+            self.emit(&[0x42, 0xc6, 0x04, 0x23, 0x00]);
+            self.bind(end);
+        }
+
+        /// Call one of the I/O callbacks, and exit with its status if it fails
+        fn call(&mut self, function: *const ()) {
+            // mov rdi, r13; mov rsi, r12; mov rax, function; call rax
+            self.emit(&[0x4c, 0x89, 0xef, 0x4c, 0x89, 0xe6, 0x48, 0xb8]);
+            self.emit(&(function as u64).to_le_bytes());
+            self.emit(&[0xff, 0xd0]);
+            // test eax, eax; jne exit
+            self.emit(&[0x85, 0xc0]);
+            self.jump(&[0x0f, 0x85], self.exit);
+        }
+
+        fn emit(&mut self, bytes: &[u8]) {
+            self.code.extend_from_slice(bytes);
+        }
+
+        fn new_label(&mut self) -> usize {
+            self.labels.push(None);
+            self.labels.len() - 1
+        }
+
+        fn bind(&mut self, label: usize) {
+            self.labels[label] = Some(self.code.len());
+        }
+
+        /// Emit a jump instruction with a 32 bit offset to the label, fixed in `finish`
+        fn jump(&mut self, opcode: &[u8], label: usize) {
+            self.emit(opcode);
+            self.jumps.push((self.code.len(), label));
+            self.emit(&[0; 4]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arguments::Feature;
+    use crate::bf_interpreter::cell::Cell;
+    use crate::bf_interpreter::error::InterpreterErrorKind;
+    use crate::utils;
+    use console::Term;
+    use pretty_assertions::assert_eq;
+
+    /// Run the code with the jit and the interpreter, and compare the results
+    fn run_both(code: &str, array_size: usize, features: Vec<Feature>) {
+        let mut jit = Interpreter::new(array_size, features.clone(), Term::stdout());
+        let mut interpreter = Interpreter::new(array_size, features, Term::stdout());
+
+        let result = run(&mut jit, code.to_string());
+        assert_eq!(result, interpreter.run(code.to_string()));
+        // After an error the interpreter can be in the middle of a loop the jit runs at once
+        if result.is_ok() {
+            assert_eq!(jit.cells, interpreter.cells);
+            assert_eq!(jit.pointer, interpreter.pointer);
+        }
+    }
+
+    #[test]
+    fn same_cells_as_the_interpreter() {
+        run_both("++[>++[>+<-]<-]", 5, vec![]);
+        run_both("+++[->+++>--<<]>>[-<+>]", 5, vec![Feature::NoReverseValue]);
+        run_both(">-[-<++>]+[+]", 5, vec![Feature::NoReverseValue]);
+        run_both("<+>>>+", 3, vec![Feature::ReversePointer]);
+        run_both(
+            ">+[<+++>-]<<[->>>+<<<]",
+            4,
+            vec![Feature::NoReverseValue, Feature::ReversePointer],
+        );
+        run_both(
+            "-[->+<]",
+            3,
+            vec![Feature::AllowUtf8, Feature::NoReverseValue],
+        );
+    }
+
+    #[test]
+    fn same_errors_as_the_interpreter() {
+        run_both("<", 5, vec![]);
+        run_both(">>>>>", 5, vec![]);
+        run_both("+[->>>>>+<<<<<]", 5, vec![]);
+        run_both("+[-<+>]", 5, vec![]);
+        run_both("-", 5, vec![]);
+        run_both(
+            "+++++[->+++++++++++++++++++++++++++++++++++++++++++++++++++<]>+",
+            5,
+            vec![],
+        );
+        run_both("+[->-<]", 5, vec![]);
+    }
+
+    #[test]
+    fn pointer_error() {
+        let mut interpreter = Interpreter::new(5, vec![], Term::stdout());
+        assert_eq!(
+            run(&mut interpreter, ">+<<".to_string()),
+            Err(InterpreterErrorKind::PointerOutOfBounds(0).to_error())
+        );
+        assert_eq!(interpreter.cells[1], Cell::new(1, &[]));
+        assert_eq!(interpreter.pointer, 1);
+
+        let mut interpreter = Interpreter::new(5, vec![], Term::stdout());
+        assert_eq!(
+            run(&mut interpreter, ">>+>>>".to_string()),
+            Err(InterpreterErrorKind::PointerOutOfBounds(5).to_error())
+        );
+        assert_eq!(interpreter.pointer, 2);
+    }
+
+    #[test]
+    fn execute_print_my_first_name_from_file() {
+        let mut interpreter = Interpreter::new(30000, vec![], Term::stdout());
+
+        println!();
+
+        assert_eq!(
+            run(
+                &mut interpreter,
+                utils::read_brainfuck_code(&String::from("test_code/print_my_first_name.bf"))
+            ),
+            Ok(0)
+        );
+
+        assert_eq!(interpreter.cells[2], Cell::new(115, &[]));
+        assert_eq!(interpreter.cells[3], Cell::new(96, &[]));
+        assert_eq!(interpreter.cells[4], Cell::new(112, &[]));
+        assert_eq!(interpreter.cells[5], Cell::new(32, &[]));
+    }
+}
@@ -0,0 +1,623 @@
+use crate::arguments::Feature;
+use crate::bf_interpreter::cell::Cell;
+use crate::bf_interpreter::error::InterpreterErrorKind;
+use crate::bf_interpreter::interpreter::BfCommand;
+use crate::compiler::ir::{self, Instr};
+use std::fmt::Write;
+
+/// Generate a textual LLVM IR module from the brainfuck commands, the `main` function returns
+/// the same codes as the interpreter errors and the I/O uses the `read` and `write` functions of libc
+/// # Arguments
+/// * `bf_commands` - The parsed brainfuck program
+/// * `array_size` - The brainfuck array size
+/// * `features` - The extra features to follow, the same ones the interpreter uses
+pub fn compile(bf_commands: &[BfCommand], array_size: usize, features: &[Feature]) -> String {
+    let utf8 = features.contains(&Feature::AllowUtf8);
+    let mut module = Module {
+        code: String::new(),
+        values: 0,
+        labels: 0,
+        cell: if utf8 { "i32" } else { "i8" },
+        array: format!("[{} x {}]", array_size, if utf8 { "i32" } else { "i8" }),
+        utf8,
+        array_size,
+        wrap_value: Cell::wraps(features),
+        wrap_pointer: features.contains(&Feature::ReversePointer),
+        max_value: Cell::default_cell(features).max_value() as i64,
+    };
+
+    let errors = [
+        (
+            "pointer_out_of_bounds_right",
+            InterpreterErrorKind::PointerOutOfBounds(array_size).to_string(),
+            InterpreterErrorKind::PointerOutOfBounds(0).code(),
+        ),
+        (
+            "pointer_out_of_bounds_left",
+            InterpreterErrorKind::PointerOutOfBounds(0).to_string(),
+            InterpreterErrorKind::PointerOutOfBounds(0).code(),
+        ),
+        (
+            "value_out_of_bounds",
+            InterpreterErrorKind::ValueOutOfBounds.to_string(),
+            InterpreterErrorKind::ValueOutOfBounds.code(),
+        ),
+        (
+            "io_error",
+            InterpreterErrorKind::IoError(std::io::Error::from(std::io::ErrorKind::UnexpectedEof))
+                .to_string(),
+            InterpreterErrorKind::IoError(std::io::Error::other("")).code(),
+        ),
+        (
+            "flush_error",
+            InterpreterErrorKind::FlushError(std::io::Error::from(std::io::ErrorKind::WriteZero))
+                .to_string(),
+            InterpreterErrorKind::FlushError(std::io::Error::other("")).code(),
+        ),
+        (
+            "invalid_utf8",
+            InterpreterErrorKind::InvalidUtf8.to_string(),
+            InterpreterErrorKind::InvalidUtf8.code(),
+        ),
+    ];
+
+    writeln!(
+        module.code,
+        "; Generated by bfy v{} from brainfuck source code, do not edit by hand.\n\
+        ; Run it with: lli program.ll (with -opaque-pointers before LLVM 15), or build it with: llc -relocation-model=pic program.ll && cc -o program program.s\n\n\
+        @array = internal global {} zeroinitializer\n\
+        @buffer = internal global [4 x i8] zeroinitializer",
+        clap::crate_version!(),
+        module.array,
+    )
+    .unwrap();
+    for (label, message, _) in &errors {
+        let message = message.clone() + "\n";
+        writeln!(
+            module.code,
+            "@{label}_message = private constant [{} x i8] c\"{}\"",
+            message.len(),
+            escape(&message)
+        )
+        .unwrap();
+    }
+    module.code.push_str(
+        "\ndeclare i64 @read(i32, ptr, i64)\n\
+        declare i64 @write(i32, ptr, i64)\n\n\
+        define i32 @main() {\n\
+        entry:\n  \
+            %pointer = alloca i64\n  \
+            store i64 0, ptr %pointer\n",
+    );
+    module.instrs(&ir::optimize(bf_commands, features));
+    module.code.push_str("  ret i32 0\n");
+    for (label, message, code) in &errors {
+        writeln!(
+            module.code,
+            "{label}:\n  \
+                call i64 @write(i32 2, ptr @{label}_message, i64 {})\n  \
+                ret i32 {code}",
+            message.len() + 1
+        )
+        .unwrap();
+    }
+    module.code.push_str("}\n");
+    module.io_functions();
+    module.code
+}
+
+/// Escape the text for a LLVM string constant
+fn escape(text: &str) -> String {
+    text.bytes()
+        .map(|byte| match byte {
+            b' '..=b'~' if byte != b'"' && byte != b'\\' => (byte as char).to_string(),
+            _ => format!("\\{:02X}", byte),
+        })
+        .collect()
+}
+
+struct Module {
+    code: String,
+    values: usize,
+    labels: usize,
+    /// The type of the cells, `i8` or `i32`
+    cell: &'static str,
+    /// The type of the array
+    array: String,
+    utf8: bool,
+    array_size: usize,
+    wrap_value: bool,
+    wrap_pointer: bool,
+    max_value: i64,
+}
+
+impl Module {
+    fn instrs(&mut self, instrs: &[Instr]) {
+        let cell = self.cell;
+        for instr in instrs {
+            match instr {
+                Instr::Add(n) => self.add(*n),
+                Instr::Move(n) => {
+                    let pointer = self.load_pointer();
+                    let pointer = self.move_pointer(&pointer, *n);
+                    self.line(&format!("store i64 {pointer}, ptr %pointer"));
+                }
+                Instr::Clear => {
+                    let (address, _) = self.current_cell();
+                    self.line(&format!("store {cell} 0, ptr {address}"));
+                }
+                Instr::MulLoop(targets) => {
+                    let (address, value) = self.current_cell();
+                    let body = self.new_label();
+                    let end = self.new_label();
+                    let is_zero = self.value(&format!("icmp eq {cell} {value}, 0"));
+                    self.line(&format!("br i1 {is_zero}, label %{end}, label %{body}"));
+                    self.label(&body);
+                    let pointer = self.load_pointer();
+                    for (offset, factor) in targets {
+                        let target = self.move_pointer(&pointer, *offset);
+                        let target = self.address(&target);
+                        self.mul_add(&target, &value, *factor);
+                    }
+                    self.line(&format!("store {cell} 0, ptr {address}"));
+                    self.line(&format!("br label %{end}"));
+                    self.label(&end);
+                }
+                Instr::Print => {
+                    let (_, value) = self.current_cell();
+                    let value = if self.utf8 {
+                        value
+                    } else {
+                        self.value(&format!("zext i8 {value} to i32"))
+                    };
+                    let code = self.value(&format!("call i32 @output(i32 {value})"));
+                    let next = self.new_label();
+                    self.line(&format!(
+                        "switch i32 {code}, label %{next} [ i32 14, label %flush_error \
+                        i32 16, label %invalid_utf8 ]"
+                    ));
+                    self.label(&next);
+                }
+                Instr::Read => {
+                    let next = self.new_label();
+                    let value = if self.utf8 {
+                        let value = self.value("call i32 @input()");
+                        self.line(&format!(
+                            "switch i32 {value}, label %{next} [ i32 -13, label %io_error \
+                            i32 -16, label %invalid_utf8 ]"
+                        ));
+                        self.label(&next);
+                        value
+                    } else {
+                        let value = self.value("call i32 @read_byte()");
+                        self.line(&format!(
+                            "switch i32 {value}, label %{next} [ i32 -13, label %io_error ]"
+                        ));
+                        self.label(&next);
+                        self.value(&format!("trunc i32 {value} to i8"))
+                    };
+                    let (address, _) = self.current_cell();
+                    self.line(&format!("store {cell} {value}, ptr {address}"));
+                }
+                Instr::Loop(loop_body) => {
+                    let head = self.new_label();
+                    let body = self.new_label();
+                    let end = self.new_label();
+                    self.line(&format!("br label %{head}"));
+                    self.label(&head);
+                    let (_, value) = self.current_cell();
+                    let is_zero = self.value(&format!("icmp eq {cell} {value}, 0"));
+                    self.line(&format!("br i1 {is_zero}, label %{end}, label %{body}"));
+                    self.label(&body);
+                    self.instrs(loop_body);
+                    self.line(&format!("br label %{head}"));
+                    self.label(&end);
+                }
+            }
+        }
+    }
+
+    fn load_pointer(&mut self) -> String {
+        self.value("load i64, ptr %pointer")
+    }
+
+    /// The address of the cell at the pointer value
+    fn address(&mut self, pointer: &str) -> String {
+        let array = self.array.clone();
+        self.value(&format!(
+            "getelementptr inbounds {array}, ptr @array, i64 0, i64 {pointer}"
+        ))
+    }
+
+    /// The address and the value of the current cell
+    fn current_cell(&mut self) -> (String, String) {
+        let pointer = self.load_pointer();
+        let address = self.address(&pointer);
+        let value = self.value(&format!("load {}, ptr {address}", self.cell));
+        (address, value)
+    }
+
+    fn add(&mut self, n: i64) {
+        let cell = self.cell;
+        let (address, value) = self.current_cell();
+        let result = if self.wrap_value {
+            let n = n.rem_euclid(self.max_value + 1);
+            let sum = self.value(&format!("add {cell} {value}, {n}"));
+            if self.utf8 {
+                self.value(&format!("urem i32 {sum}, {}", self.max_value + 1))
+            } else {
+                sum
+            }
+        } else {
+            let abs = n.unsigned_abs() as i64;
+            if abs > self.max_value {
+                self.line("br label %value_out_of_bounds");
+                let next = self.new_label();
+                self.label(&next);
+                return;
+            }
+            let out_of_bounds = if n > 0 {
+                self.value(&format!(
+                    "icmp ugt {cell} {value}, {}",
+                    self.max_value - abs
+                ))
+            } else {
+                self.value(&format!("icmp ult {cell} {value}, {abs}"))
+            };
+            self.branch_to_error(&out_of_bounds, "value_out_of_bounds");
+            if n > 0 {
+                self.value(&format!("add {cell} {value}, {abs}"))
+            } else {
+                self.value(&format!("sub {cell} {value}, {abs}"))
+            }
+        };
+        self.line(&format!("store {cell} {result}, ptr {address}"));
+    }
+
+    /// Move the pointer value by the offset, with the bounds check or the wrapping
+    fn move_pointer(&mut self, pointer: &str, n: isize) -> String {
+        if self.wrap_pointer {
+            let n = n.rem_euclid(self.array_size as isize);
+            let sum = self.value(&format!("add i64 {pointer}, {n}"));
+            self.value(&format!("urem i64 {sum}, {}", self.array_size))
+        } else if n > 0 {
+            let sum = self.value(&format!("add i64 {pointer}, {n}"));
+            let out_of_bounds = self.value(&format!("icmp uge i64 {sum}, {}", self.array_size));
+            self.branch_to_error(&out_of_bounds, "pointer_out_of_bounds_right");
+            sum
+        } else {
+            let abs = n.unsigned_abs();
+            let out_of_bounds = self.value(&format!("icmp ult i64 {pointer}, {abs}"));
+            self.branch_to_error(&out_of_bounds, "pointer_out_of_bounds_left");
+            self.value(&format!("sub i64 {pointer}, {abs}"))
+        }
+    }
+
+    /// Add the value multiplied by the factor to the cell at the address,
+    /// the multiplication loops are only used when the values wrap
+    fn mul_add(&mut self, address: &str, value: &str, factor: i64) {
+        let cell = self.cell;
+        let factor = factor.rem_euclid(self.max_value + 1);
+        let old = self.value(&format!("load {cell}, ptr {address}"));
+        let result = if self.utf8 {
+            let value = self.value(&format!("zext i32 {value} to i64"));
+            let old = self.value(&format!("zext i32 {old} to i64"));
+            let product = self.value(&format!("mul i64 {value}, {factor}"));
+            let sum = self.value(&format!("add i64 {old}, {product}"));
+            let result = self.value(&format!("urem i64 {sum}, {}", self.max_value + 1));
+            self.value(&format!("trunc i64 {result} to i32"))
+        } else {
+            let product = self.value(&format!("mul i8 {value}, {factor}"));
+            self.value(&format!("add i8 {old}, {product}"))
+        };
+        self.line(&format!("store {cell} {result}, ptr {address}"));
+    }
+
+    fn branch_to_error(&mut self, condition: &str, error: &str) {
+        let next = self.new_label();
+        self.line(&format!("br i1 {condition}, label %{error}, label %{next}"));
+        self.label(&next);
+    }
+
+    /// The `output` and `input` functions, they encode and decode the chars as utf8
+    /// like the interpreter and return the negative error code on failure (the output returns the positive code),
+    /// without the utf8 feature the bytes are read as they are by `read_byte`
+    fn io_functions(&mut self) {
+        self.code.push_str(
+            "\n\
+            define internal i32 @output(i32 %value) {\n\
+            entry:\n  \
+                %surrogate = and i32 %value, -2048\n  \
+                %is_surrogate = icmp eq i32 %surrogate, 55296\n  \
+                %is_too_big = icmp ugt i32 %value, 1114111\n  \
+                %is_invalid = or i1 %is_surrogate, %is_too_big\n  \
+                br i1 %is_invalid, label %invalid, label %check_1\n\
+            invalid:\n  \
+                ret i32 16\n",
+        );
+        // The utf8 encoding with 1 to 4 bytes, the first byte has the prefix and the highest bits
+        let limits = [128, 2048, 65536];
+        let prefixes = [0, 0xc0, 0xe0, 0xf0];
+        for length in 1..=4 {
+            if length < 4 {
+                writeln!(
+                    self.code,
+                    "check_{length}:\n  \
+                        %is_{length} = icmp ult i32 %value, {}\n  \
+                        br i1 %is_{length}, label %encode_{length}, label %check_{}",
+                    limits[length - 1],
+                    length + 1
+                )
+                .unwrap();
+            } else {
+                self.code.push_str("check_4:\n  br label %encode_4\n");
+            }
+            writeln!(self.code, "encode_{length}:").unwrap();
+            for index in 0..length {
+                let shift = 6 * (length - index - 1);
+                let (mask, prefix) = if index == 0 {
+                    (
+                        0xff >> (length + (length > 1) as usize),
+                        prefixes[length - 1],
+                    )
+                } else {
+                    (0x3f, 0x80)
+                };
+                writeln!(
+                    self.code,
+                    "  %shifted_{length}_{index} = lshr i32 %value, {shift}\n  \
+                        %masked_{length}_{index} = and i32 %shifted_{length}_{index}, {mask}\n  \
+                        %prefixed_{length}_{index} = or i32 %masked_{length}_{index}, {prefix}\n  \
+                        %byte_{length}_{index} = trunc i32 %prefixed_{length}_{index} to i8\n  \
+                        %address_{length}_{index} = getelementptr inbounds [4 x i8], ptr @buffer, i64 0, i64 {index}\n  \
+                        store i8 %byte_{length}_{index}, ptr %address_{length}_{index}"
+                )
+                .unwrap();
+            }
+            self.code.push_str("  br label %write\n");
+        }
+        self.code.push_str(
+            "write:\n  \
+                %length = phi i64 [ 1, %encode_1 ], [ 2, %encode_2 ], [ 3, %encode_3 ], [ 4, %encode_4 ]\n  \
+                %written = call i64 @write(i32 1, ptr @buffer, i64 %length)\n  \
+                %is_written = icmp eq i64 %written, %length\n  \
+                %code = select i1 %is_written, i32 0, i32 14\n  \
+                ret i32 %code\n\
+            }\n\n\
+            define internal i32 @read_byte() {\n\
+            entry:\n  \
+                %read = call i64 @read(i32 0, ptr @buffer, i64 1)\n  \
+                %is_read = icmp eq i64 %read, 1\n  \
+                br i1 %is_read, label %byte, label %eof\n\
+            byte:\n  \
+                %value = load i8, ptr @buffer\n  \
+                %extended = zext i8 %value to i32\n  \
+                ret i32 %extended\n\
+            eof:\n  \
+                ret i32 -13\n\
+            }\n",
+        );
+        if !self.utf8 {
+            return;
+        }
+        self.code.push_str(
+            "\n\
+            define internal i32 @input() {\n\
+            entry:\n  \
+                %first = call i32 @read_byte()\n  \
+                %is_eof = icmp slt i32 %first, 0\n  \
+                br i1 %is_eof, label %eof, label %check_ascii\n\
+            check_ascii:\n  \
+                %is_ascii = icmp ult i32 %first, 128\n  \
+                br i1 %is_ascii, label %ascii, label %check_first\n\
+            ascii:\n  \
+                ret i32 %first\n\
+            check_first:\n  \
+                %is_continuation = icmp ult i32 %first, 192\n  \
+                %is_too_long = icmp uge i32 %first, 248\n  \
+                %is_invalid_first = or i1 %is_continuation, %is_too_long\n  \
+                br i1 %is_invalid_first, label %invalid, label %decode\n\
+            decode:\n  \
+                %is_2 = icmp ult i32 %first, 224\n  \
+                %is_3 = icmp ult i32 %first, 240\n  \
+                %count_3 = select i1 %is_3, i32 2, i32 3\n  \
+                %count = select i1 %is_2, i32 1, i32 %count_3\n  \
+                %mask_3 = select i1 %is_3, i32 15, i32 7\n  \
+                %mask = select i1 %is_2, i32 31, i32 %mask_3\n  \
+                %initial = and i32 %first, %mask\n  \
+                br label %next\n\
+            next:\n  \
+                %value = phi i32 [ %initial, %decode ], [ %next_value, %continuation ]\n  \
+                %remaining = phi i32 [ %count, %decode ], [ %next_remaining, %continuation ]\n  \
+                %byte = call i32 @read_byte()\n  \
+                %is_byte_eof = icmp slt i32 %byte, 0\n  \
+                br i1 %is_byte_eof, label %eof, label %check_continuation\n\
+            check_continuation:\n  \
+                %top = and i32 %byte, 192\n  \
+                %is_valid = icmp eq i32 %top, 128\n  \
+                br i1 %is_valid, label %continuation, label %invalid\n\
+            continuation:\n  \
+                %shifted = shl i32 %value, 6\n  \
+                %low = and i32 %byte, 63\n  \
+                %next_value = or i32 %shifted, %low\n  \
+                %next_remaining = sub i32 %remaining, 1\n  \
+                %is_done = icmp eq i32 %next_remaining, 0\n  \
+                br i1 %is_done, label %check_value, label %next\n\
+            check_value:\n  \
+                %surrogate = and i32 %next_value, -2048\n  \
+                %is_surrogate = icmp eq i32 %surrogate, 55296\n  \
+                %is_too_big = icmp ugt i32 %next_value, 1114111\n  \
+                %is_invalid = or i1 %is_surrogate, %is_too_big\n  \
+                br i1 %is_invalid, label %invalid, label %valid\n\
+            valid:\n  \
+                ret i32 %next_value\n\
+            invalid:\n  \
+                ret i32 -16\n\
+            eof:\n  \
+                ret i32 -13\n\
+            }\n",
+        );
+    }
+
+    /// Emit the instruction to a new value and return its name
+    fn value(&mut self, instruction: &str) -> String {
+        self.values += 1;
+        let value = format!("%v{}", self.values);
+        self.line(&format!("{value} = {instruction}"));
+        value
+    }
+
+    fn line(&mut self, line: &str) {
+        writeln!(self.code, "  {}", line).unwrap();
+    }
+
+    fn label(&mut self, label: &str) {
+        writeln!(self.code, "{}:", label).unwrap();
+    }
+
+    fn new_label(&mut self) -> String {
+        self.labels += 1;
+        format!("L{}", self.labels)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bf_interpreter::interpreter::to_bf_commands;
+    use crate::utils;
+    use pretty_assertions::assert_eq;
+    use std::io::Write;
+    use std::process::{Command, Output, Stdio};
+
+    fn compile_code(code: &str, features: &[Feature]) -> String {
+        compile(
+            &to_bf_commands(code.chars().collect()).unwrap(),
+            30000,
+            features,
+        )
+    }
+
+    #[test]
+    fn checked_commands() {
+        let code = compile_code("+++<", &[]);
+
+        assert!(code.contains(
+            "  %v1 = load i64, ptr %pointer\n  \
+                %v2 = getelementptr inbounds [30000 x i8], ptr @array, i64 0, i64 %v1\n  \
+                %v3 = load i8, ptr %v2\n  \
+                %v4 = icmp ugt i8 %v3, 252\n  \
+                br i1 %v4, label %value_out_of_bounds, label %L1\n\
+            L1:\n  \
+                %v5 = add i8 %v3, 3\n  \
+                store i8 %v5, ptr %v2\n  \
+                %v6 = load i64, ptr %pointer\n  \
+                %v7 = icmp ult i64 %v6, 1\n  \
+                br i1 %v7, label %pointer_out_of_bounds_left, label %L2\n\
+            L2:\n  \
+                %v8 = sub i64 %v6, 1\n  \
+                store i64 %v8, ptr %pointer\n"
+        ));
+        assert!(code.contains(
+            "@pointer_out_of_bounds_right_message = private constant [28 x i8] \
+            c\"Pointer out of bounds 30000\\0A\"\n"
+        ));
+    }
+
+    #[test]
+    fn reverse_pointer_wraps_the_moves() {
+        let code = compile_code("[--<]", &[Feature::NoReverseValue, Feature::ReversePointer]);
+
+        assert!(code.contains("  %v8 = add i8 %v7, 254\n"));
+        assert!(code.contains(
+            "  %v10 = add i64 %v9, 29999\n  \
+                %v11 = urem i64 %v10, 30000\n  \
+                store i64 %v11, ptr %pointer\n  \
+                br label %L1\n"
+        ));
+        assert!(!code.contains("pointer_out_of_bounds_right, label"));
+    }
+
+    #[test]
+    fn features_select_the_cell_type() {
+        let code = compile_code("[->++<].", &[Feature::AllowUtf8, Feature::NoReverseValue]);
+
+        assert!(code.contains("@array = internal global [30000 x i32] zeroinitializer\n"));
+        assert!(code.contains("  %v14 = urem i64 %v13, 1114112\n"));
+        assert_eq!(code.matches("call i32 @output(i32 %v").count(), 1);
+    }
+
+    #[test]
+    fn utf8_selects_the_input_function() {
+        let code = compile_code(",", &[]);
+        assert!(code.contains("call i32 @read_byte()\n"));
+        assert!(!code.contains("@input"));
+
+        let code = compile_code(",", &[Feature::AllowUtf8]);
+        assert!(code.contains("  %v1 = call i32 @input()\n"));
+    }
+
+    /// Run the module with lli and the input piped to stdin
+    fn run_with_lli(name: &str, code: &str, features: &[Feature], input: &[u8]) -> Output {
+        let dir = std::env::temp_dir().join(format!("bfy-llvm-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let module = dir.join(format!("{}.ll", name));
+        std::fs::write(&module, compile_code(code, features)).unwrap();
+
+        // The pointers are opaque by default since LLVM 15
+        let version = Command::new("lli").arg("--version").output().unwrap();
+        let major = String::from_utf8_lossy(&version.stdout)
+            .split("LLVM version ")
+            .nth(1)
+            .and_then(|version| version.split('.').next()?.trim().parse::<u32>().ok())
+            .unwrap_or(u32::MAX);
+        let mut lli = Command::new("lli");
+        if major < 15 {
+            lli.arg("-opaque-pointers");
+        }
+        let mut child = lli
+            .arg(&module)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child.stdin.take().unwrap().write_all(input).unwrap();
+        let output = child.wait_with_output().unwrap();
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        output
+    }
+
+    #[test]
+    fn run_read_name_and_print_hi_with_lli() {
+        if !crate::compiler::tool_exists("lli") {
+            return;
+        }
+        let code = utils::read_brainfuck_code(&String::from("test_code/read_name_and_print_hi.bf"));
+        let output = run_with_lli("read_name_and_print_hi", &code, &[], b"Anas\n");
+
+        // The program reads until the end of the input, that fails like the interpreter does
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "Hi Anas\n");
+        assert_eq!(output.status.code(), Some(13));
+    }
+
+    #[test]
+    fn run_non_ascii_input_with_lli() {
+        if !crate::compiler::tool_exists("lli") {
+            return;
+        }
+        // A byte cell reads the piped bytes one by one, like the interpreter
+        let output = run_with_lli("bytes", ",.,.", &[], b"\xe9A");
+        assert_eq!(output.stdout, "\u{e9}A".as_bytes());
+        assert_eq!(output.status.code(), Some(0));
+
+        // The utf8 cells read the chars, and fail on an invalid lead byte
+        let output = run_with_lli("chars", ",.,.", &[Feature::AllowUtf8], "éA".as_bytes());
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "éA");
+        assert_eq!(output.status.code(), Some(0));
+        let output = run_with_lli("invalid", ",.", &[Feature::AllowUtf8], b"\x80AAA");
+        assert_eq!(output.stdout, b"");
+        assert_eq!(output.status.code(), Some(16));
+    }
+}
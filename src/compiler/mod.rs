@@ -0,0 +1,120 @@
+pub mod asm;
+pub mod decompiler;
+pub mod ir;
+#[cfg(feature = "jit")]
+pub mod jit;
+pub mod llvm;
+pub mod rust;
+pub mod wasm;
+
+use crate::arguments::{Eof, Feature, Target};
+use crate::bf_interpreter::interpreter::to_bf_commands;
+use crate::dialects::Dialect;
+use crate::utils;
+
+/// Compile the brainfuck source code file to the given target
+/// # Arguments
+/// * `source` - The brainfuck source code file
+/// * `dialect` - The dialect of the source code file
+/// * `target` - The target to compile to
+/// * `output` - The file to write the generated code to, if `None` it will be printed to stdout
+/// * `array_size` - The brainfuck array size
+/// * `features` - The extra features to follow
+/// * `eof` - The end of input behavior, the compiled programs only exit with the IO error
+pub fn compile_file(
+    source: &String,
+    dialect: &Dialect,
+    target: Target,
+    output: Option<&String>,
+    array_size: usize,
+    features: &[Feature],
+    eof: Eof,
+) {
+    info!("Compiling brainfuck source code from file: {}", source);
+    utils::require_standard_commands(features, "compiled");
+    if eof != Eof::Error {
+        error!("The `--eof` option is only supported by the interpreter, the compiled programs exit with the IO error");
+        eprintln!("The `--eof` option is only supported by the interpreter, the compiled programs exit with the IO error");
+        std::process::exit(1);
+    }
+    let bf_commands = match to_bf_commands(utils::read_code(source, dialect, &[]).chars().collect())
+    {
+        Ok(bf_commands) => bf_commands,
+        Err(e) => {
+            error!("Failed to compile brainfuck source code from file: {}", e);
+            std::process::exit(e.code);
+        }
+    };
+
+    if target == Target::Elf {
+        let output = output.cloned().unwrap_or_else(|| {
+            std::path::Path::new(source)
+                .with_extension("")
+                .to_string_lossy()
+                .to_string()
+        });
+        info!("Linking the generated code to the executable: {}", output);
+        if let Err(e) = asm::link(&asm::compile(&bf_commands, array_size, features), &output) {
+            error!("Failed to link the executable: {}", e);
+            eprintln!("Failed to link the executable: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let code = match target {
+        Target::Rust => rust::compile(
+            &bf_commands,
+            array_size,
+            features,
+            rust::RustOutput::Program,
+        )
+        .into_bytes(),
+        Target::RustModule => {
+            rust::compile(&bf_commands, array_size, features, rust::RustOutput::Module).into_bytes()
+        }
+        Target::Wat => wasm::compile_text(&bf_commands, array_size, features).into_bytes(),
+        Target::Wasm => wasm::compile_binary(&bf_commands, array_size, features),
+        Target::Asm => asm::compile(&bf_commands, array_size, features).into_bytes(),
+        Target::Llvm => llvm::compile(&bf_commands, array_size, features).into_bytes(),
+        Target::Elf => unreachable!("The elf target is linked above"),
+    };
+
+    utils::write_output(&code, output);
+}
+
+/// Decompile the brainfuck source code file to a readable pseudo-code
+/// # Arguments
+/// * `source` - The brainfuck source code file
+/// * `output` - The file to write the pseudo-code to, if `None` it will be printed to stdout
+/// * `features` - The features of the header or the config, the ones with extra commands can't be decompiled
+pub fn decompile_file(source: &String, output: Option<&String>, features: &[Feature]) {
+    info!("Decompiling brainfuck source code from file: {}", source);
+    utils::require_standard_commands(features, "decompiled");
+    let bf_commands = match to_bf_commands(utils::read_brainfuck_code(source).chars().collect()) {
+        Ok(bf_commands) => bf_commands,
+        Err(e) => {
+            error!("Failed to decompile brainfuck source code from file: {}", e);
+            std::process::exit(e.code);
+        }
+    };
+
+    utils::write_output(decompiler::decompile(&bf_commands).as_bytes(), output);
+}
+
+/// Whether the external tool can be run, the tests that need it are skipped without it
+/// # Arguments
+/// * `tool` - The command of the tool, e.g. `rustc`
+#[cfg(test)]
+pub fn tool_exists(tool: &str) -> bool {
+    let exists = std::process::Command::new(tool)
+        .arg("--version")
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status()
+        .is_ok();
+    if !exists {
+        eprintln!("Skipping the test, `{}` is not installed", tool);
+    }
+    exists
+}
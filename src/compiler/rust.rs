@@ -0,0 +1,420 @@
+use crate::arguments::Feature;
+use crate::bf_interpreter::cell::Cell;
+use crate::bf_interpreter::error::InterpreterErrorKind;
+use crate::bf_interpreter::interpreter::BfCommand;
+use crate::compiler::ir::{self, Instr};
+use std::fmt::Write;
+
+/// What kind of rust source code to generate
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum RustOutput {
+    /// A self-contained program with a `main` function that exits with the error code on failure
+    Program,
+    /// A module that exposes `run` and `run_with` functions, to be included in another crate
+    Module,
+}
+
+/// Generate rust source code from the brainfuck commands
+/// # Arguments
+/// * `bf_commands` - The parsed brainfuck program
+/// * `array_size` - The brainfuck array size
+/// * `features` - The extra features to follow, the same ones the interpreter uses
+/// * `output` - Generate a whole program or a module
+pub fn compile(
+    bf_commands: &[BfCommand],
+    array_size: usize,
+    features: &[Feature],
+    output: RustOutput,
+) -> String {
+    let mut code = String::new();
+    write_prelude(&mut code, array_size, features);
+
+    code.push_str(
+        "/// Run the program using the process stdin and stdout\n\
+        pub fn run() -> Result<(), Error> {\n    \
+            run_with(&mut std::io::stdin().lock(), &mut std::io::stdout().lock())\n\
+        }\n\n\
+        /// Run the program using the given input and output\n\
+        pub fn run_with<R: Read, W: Write>(input: &mut R, output: &mut W) -> Result<(), Error> {\n    \
+            let mut cells: Vec<Cell> = vec![0; ARRAY_SIZE];\n    \
+            let mut pointer: usize = 0;\n",
+    );
+    write_instrs(&mut code, &ir::optimize(bf_commands, features), 1);
+    code.push_str("    Ok(())\n}\n");
+
+    if output == RustOutput::Program {
+        code.push_str(
+            "\nfn main() {\n    \
+                if let Err(e) = run() {\n        \
+                    eprintln!(\"{}\", e.message);\n        \
+                    std::process::exit(e.code);\n    \
+                }\n\
+            }\n",
+        );
+    }
+
+    code
+}
+
+fn write_prelude(code: &mut String, array_size: usize, features: &[Feature]) {
+    let utf8 = features.contains(&Feature::AllowUtf8);
+    let pointer_code = InterpreterErrorKind::PointerOutOfBounds(0).code();
+    let value_code = InterpreterErrorKind::ValueOutOfBounds.code();
+    let io_code = InterpreterErrorKind::IoError(std::io::Error::other("")).code();
+    let flush_code = InterpreterErrorKind::FlushError(std::io::Error::other("")).code();
+    let utf8_code = InterpreterErrorKind::InvalidUtf8.code();
+
+    writeln!(
+        code,
+        "// Generated by bfy v{} from brainfuck source code, do not edit by hand.\n\
+        #![allow(dead_code, unused_mut, unused_variables)]\n\n\
+        use std::io::{{Read, Write}};\n\n\
+        type Cell = {};\n\
+        const ARRAY_SIZE: usize = {};\n\
+        const MAX_VALUE: u64 = {};\n\n\
+        /// The error returned when the program fails, the codes are the same as the bfy interpreter ones\n\
+        #[derive(Debug)]\n\
+        pub struct Error {{\n    \
+            pub message: String,\n    \
+            pub code: i32,\n\
+        }}\n\n\
+        fn error(message: String, code: i32) -> Error {{\n    \
+            Error {{ message, code }}\n\
+        }}\n",
+        clap::crate_version!(),
+        if utf8 { "u32" } else { "u8" },
+        array_size,
+        if utf8 { 1114111 } else { u8::MAX as u32 },
+    )
+    .unwrap();
+
+    if features.contains(&Feature::ReversePointer) {
+        code.push_str(
+            "fn increment_pointer(pointer: &mut usize, n: usize) -> Result<(), Error> {\n    \
+                *pointer = (*pointer + n) % ARRAY_SIZE;\n    \
+                Ok(())\n\
+            }\n\n\
+            fn decrement_pointer(pointer: &mut usize, n: usize) -> Result<(), Error> {\n    \
+                *pointer = (*pointer + ARRAY_SIZE - n % ARRAY_SIZE) % ARRAY_SIZE;\n    \
+                Ok(())\n\
+            }\n\n",
+        );
+    } else {
+        writeln!(
+            code,
+            "fn increment_pointer(pointer: &mut usize, n: usize) -> Result<(), Error> {{\n    \
+                if *pointer + n >= ARRAY_SIZE {{\n        \
+                    return Err(error(format!(\"Pointer out of bounds {{}}\", ARRAY_SIZE), {pointer_code}));\n    \
+                }}\n    \
+                *pointer += n;\n    \
+                Ok(())\n\
+            }}\n\n\
+            fn decrement_pointer(pointer: &mut usize, n: usize) -> Result<(), Error> {{\n    \
+                if *pointer < n {{\n        \
+                    return Err(error(\"Pointer out of bounds 0\".to_string(), {pointer_code}));\n    \
+                }}\n    \
+                *pointer -= n;\n    \
+                Ok(())\n\
+            }}\n"
+        )
+        .unwrap();
+    }
+
+    if !Cell::wraps(features) {
+        writeln!(
+            code,
+            "fn increment_value(cell: &mut Cell, n: u64) -> Result<(), Error> {{\n    \
+                if *cell as u64 + n > MAX_VALUE {{\n        \
+                    return Err(error(\"Value out of bounds\".to_string(), {value_code}));\n    \
+                }}\n    \
+                *cell += n as Cell;\n    \
+                Ok(())\n\
+            }}\n\n\
+            fn decrement_value(cell: &mut Cell, n: u64) -> Result<(), Error> {{\n    \
+                if (*cell as u64) < n {{\n        \
+                    return Err(error(\"Value out of bounds\".to_string(), {value_code}));\n    \
+                }}\n    \
+                *cell -= n as Cell;\n    \
+                Ok(())\n\
+            }}\n"
+        )
+        .unwrap();
+    } else {
+        code.push_str(
+            "fn increment_value(cell: &mut Cell, n: u64) -> Result<(), Error> {\n    \
+                *cell = ((*cell as u64 + n) % (MAX_VALUE + 1)) as Cell;\n    \
+                Ok(())\n\
+            }\n\n\
+            fn decrement_value(cell: &mut Cell, n: u64) -> Result<(), Error> {\n    \
+                *cell = ((*cell as u64 + MAX_VALUE + 1 - n % (MAX_VALUE + 1)) % (MAX_VALUE + 1)) as Cell;\n    \
+                Ok(())\n\
+            }\n\n",
+        );
+    }
+
+    writeln!(
+        code,
+        "fn output_value<W: Write>(output: &mut W, cell: Cell) -> Result<(), Error> {{\n    \
+            {}\n    \
+            write!(output, \"{{}}\", c)\n        \
+                .and_then(|_| output.flush())\n        \
+                .map_err(|e| error(format!(\"Failed to flush stdout: {{}}\", e), {flush_code}))\n\
+        }}\n\n\
+        fn input_value<R: Read>(input: &mut R, cell: &mut Cell) -> Result<(), Error> {{\n    \
+            let mut bytes = [0; 4];\n    \
+            let read_error = |e: std::io::Error| {{\n        \
+                error(\n            \
+                    format!(\"Failed to read byte from stdin: no bytes available: {{}}\", e),\n            \
+                    {io_code},\n        \
+                )\n    \
+            }};\n    \
+            input.read_exact(&mut bytes[..1]).map_err(read_error)?;\n    \
+            {}\n    \
+            Ok(())\n\
+        }}\n",
+        if utf8 {
+            format!(
+                "let c = char::from_u32(cell)\n        \
+                    .ok_or_else(|| error(\"Invalid utf8\".to_string(), {utf8_code}))?;"
+            )
+        } else {
+            "let c = cell as char;".to_string()
+        },
+        if utf8 {
+            format!(
+                "let len = match bytes[0] {{\n        \
+                    0x00..=0x7f => 1,\n        \
+                    0xc0..=0xdf => 2,\n        \
+                    0xe0..=0xef => 3,\n        \
+                    0xf0..=0xf7 => 4,\n        \
+                    _ => return Err(error(\"Invalid utf8\".to_string(), {utf8_code})),\n    \
+                }};\n    \
+                input.read_exact(&mut bytes[1..len]).map_err(read_error)?;\n    \
+                match std::str::from_utf8(&bytes[..len]).ok().and_then(|s| s.chars().next()) {{\n        \
+                    Some(ch) => *cell = ch as Cell,\n        \
+                    None => return Err(error(\"Invalid utf8\".to_string(), {utf8_code})),\n    \
+                }}"
+            )
+        } else {
+            "*cell = bytes[0];".to_string()
+        }
+    )
+    .unwrap();
+}
+
+fn write_instrs(code: &mut String, instrs: &[Instr], depth: usize) {
+    let indent = "    ".repeat(depth);
+    for instr in instrs {
+        match instr {
+            Instr::Add(n) => write_add(code, &indent, "pointer", *n, ""),
+            Instr::Move(n) => write_move(code, &indent, "pointer", *n),
+            Instr::Clear => writeln!(code, "{indent}cells[pointer] = 0;").unwrap(),
+            Instr::MulLoop(targets) => {
+                writeln!(code, "{indent}if cells[pointer] != 0 {{").unwrap();
+                writeln!(code, "{indent}    let value = cells[pointer] as u64;").unwrap();
+                for (offset, factor) in targets {
+                    writeln!(code, "{indent}    let mut target = pointer;").unwrap();
+                    write_move(code, &format!("{indent}    "), "target", *offset);
+                    write_add(
+                        code,
+                        &format!("{indent}    "),
+                        "target",
+                        *factor,
+                        " * value",
+                    );
+                }
+                writeln!(code, "{indent}    cells[pointer] = 0;").unwrap();
+                writeln!(code, "{indent}}}").unwrap();
+            }
+            Instr::Print => {
+                writeln!(code, "{indent}output_value(output, cells[pointer])?;").unwrap()
+            }
+            Instr::Read => {
+                writeln!(code, "{indent}input_value(input, &mut cells[pointer])?;").unwrap()
+            }
+            Instr::Loop(loop_body) => {
+                writeln!(code, "{indent}while cells[pointer] != 0 {{").unwrap();
+                write_instrs(code, loop_body, depth + 1);
+                writeln!(code, "{indent}}}").unwrap();
+            }
+        }
+    }
+}
+
+fn write_add(code: &mut String, indent: &str, pointer: &str, n: i64, multiplier: &str) {
+    let function = if n < 0 {
+        "decrement_value"
+    } else {
+        "increment_value"
+    };
+    writeln!(
+        code,
+        "{indent}{function}(&mut cells[{pointer}], {}{multiplier})?;",
+        n.unsigned_abs()
+    )
+    .unwrap();
+}
+
+fn write_move(code: &mut String, indent: &str, pointer: &str, n: isize) {
+    let function = if n < 0 {
+        "decrement_pointer"
+    } else {
+        "increment_pointer"
+    };
+    writeln!(
+        code,
+        "{indent}{function}(&mut {pointer}, {})?;",
+        n.unsigned_abs()
+    )
+    .unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bf_interpreter::interpreter::to_bf_commands;
+    use crate::utils;
+    use pretty_assertions::assert_eq;
+    use std::io::Write;
+    use std::process::{Command, Output, Stdio};
+
+    fn compile_code(code: &str, features: &[Feature], output: RustOutput) -> String {
+        compile(
+            &to_bf_commands(code.chars().collect()).unwrap(),
+            30000,
+            features,
+            output,
+        )
+    }
+
+    #[test]
+    fn fold_runs_of_the_same_command() {
+        let code = compile_code("+++>>-<.,", &[], RustOutput::Module);
+
+        assert!(code.contains("    increment_value(&mut cells[pointer], 3)?;\n"));
+        assert!(code.contains("    increment_pointer(&mut pointer, 2)?;\n"));
+        assert!(code.contains("    decrement_value(&mut cells[pointer], 1)?;\n"));
+        assert!(code.contains("    decrement_pointer(&mut pointer, 1)?;\n"));
+        assert!(code.contains("    output_value(output, cells[pointer])?;\n"));
+        assert!(code.contains("    input_value(input, &mut cells[pointer])?;\n"));
+    }
+
+    #[test]
+    fn nested_loops() {
+        let code = compile_code("++[>++[>.<-]<-]", &[], RustOutput::Module);
+
+        assert!(code.contains(
+            "    while cells[pointer] != 0 {\n        \
+                    increment_pointer(&mut pointer, 1)?;\n        \
+                    increment_value(&mut cells[pointer], 2)?;\n        \
+                    while cells[pointer] != 0 {\n            \
+                        increment_pointer(&mut pointer, 1)?;\n"
+        ));
+    }
+
+    #[test]
+    fn multiplication_loops() {
+        let code = compile_code("[->++<]", &[Feature::NoReverseValue], RustOutput::Module);
+
+        assert!(code.contains(
+            "    if cells[pointer] != 0 {\n        \
+                    let value = cells[pointer] as u64;\n        \
+                    let mut target = pointer;\n        \
+                    increment_pointer(&mut target, 1)?;\n        \
+                    increment_value(&mut cells[target], 2 * value)?;\n        \
+                    cells[pointer] = 0;\n    \
+                }\n"
+        ));
+    }
+
+    #[test]
+    fn module_has_no_main() {
+        let module = compile_code("+.", &[], RustOutput::Module);
+        let program = compile_code("+.", &[], RustOutput::Program);
+
+        assert!(!module.contains("fn main()"));
+        assert!(program.contains("fn main()"));
+        assert!(program.starts_with(&module));
+    }
+
+    #[test]
+    fn features_select_the_cell_type_and_the_helpers() {
+        let code = compile_code("+", &[], RustOutput::Module);
+        assert!(code.contains("type Cell = u8;\n"));
+        assert!(code.contains("return Err(error(\"Pointer out of bounds 0\".to_string(), 11));"));
+        assert!(code.contains("return Err(error(\"Value out of bounds\".to_string(), 12));"));
+
+        let code = compile_code(
+            "+",
+            &[
+                Feature::AllowUtf8,
+                Feature::NoReverseValue,
+                Feature::ReversePointer,
+            ],
+            RustOutput::Module,
+        );
+        assert!(code.contains("type Cell = u32;\n"));
+        assert!(code.contains("const MAX_VALUE: u64 = 1114111;\n"));
+        assert!(!code.contains("Value out of bounds"));
+        assert!(!code.contains("Pointer out of bounds"));
+    }
+
+    /// Compile the program with rustc and run it with the input piped to stdin
+    fn compile_and_run(name: &str, code: &str, features: &[Feature], input: &[u8]) -> Output {
+        let dir = std::env::temp_dir().join(format!("bfy-rust-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let source = dir.join(format!("{}.rs", name));
+        let binary = dir.join(name);
+        std::fs::write(&source, compile_code(code, features, RustOutput::Program)).unwrap();
+
+        let status = Command::new("rustc")
+            .arg("-o")
+            .arg(&binary)
+            .arg(&source)
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let mut child = Command::new(&binary)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child.stdin.take().unwrap().write_all(input).unwrap();
+        let output = child.wait_with_output().unwrap();
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        output
+    }
+
+    #[test]
+    fn compile_and_run_hello_world() {
+        if !crate::compiler::tool_exists("rustc") {
+            return;
+        }
+        let code = utils::read_brainfuck_code(&String::from("test_code/hello_world.bf"));
+
+        let output = compile_and_run("hello_world", &code, &[], b"");
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "Hello world!\n");
+        assert_eq!(output.status.code(), Some(0));
+    }
+
+    #[test]
+    fn compile_and_run_non_ascii_input() {
+        if !crate::compiler::tool_exists("rustc") {
+            return;
+        }
+        // A byte cell reads the piped bytes one by one, like the interpreter
+        let output = compile_and_run("bytes", ",.,.", &[], b"\xe9A");
+        assert_eq!(output.stdout, "\u{e9}A".as_bytes());
+        assert_eq!(output.status.code(), Some(0));
+
+        // The utf8 cells read the chars, and fail on an invalid lead byte
+        let output = compile_and_run("chars", ",.,.", &[Feature::AllowUtf8], "éA".as_bytes());
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "éA");
+        assert_eq!(output.status.code(), Some(0));
+        let output = compile_and_run("invalid", ",.", &[Feature::AllowUtf8], b"\x80AAA");
+        assert_eq!(output.stdout, b"");
+        assert_eq!(output.status.code(), Some(16));
+    }
+}
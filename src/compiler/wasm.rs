@@ -0,0 +1,599 @@
+use crate::arguments::Feature;
+use crate::bf_interpreter::cell::Cell;
+use crate::bf_interpreter::error::InterpreterErrorKind;
+use crate::bf_interpreter::interpreter::BfCommand;
+use crate::compiler::ir::{self, Instr};
+use std::fmt::Write;
+
+/// The module the `output` and `input` functions are imported from
+pub const IMPORT_MODULE: &str = "bfy";
+
+/// Generate a WebAssembly module in the text format (`.wat`) from the brainfuck commands.
+///
+/// The module imports `bfy.output(code_point: i32)` for `.` and `bfy.input() -> i32` for `,`
+/// (it returns a negative value at the end of the input), exports the array as `memory`,
+/// and exports `run() -> i32` that returns 0 or the interpreter error code
+/// # Arguments
+/// * `bf_commands` - The parsed brainfuck program
+/// * `array_size` - The brainfuck array size
+/// * `features` - The extra features to follow, the same ones the interpreter uses
+pub fn compile_text(bf_commands: &[BfCommand], array_size: usize, features: &[Feature]) -> String {
+    let module = Module::new(bf_commands, array_size, features);
+
+    let mut code = String::new();
+    writeln!(
+        code,
+        "(module\n  \
+            (import \"{IMPORT_MODULE}\" \"output\" (func $output (param i32)))\n  \
+            (import \"{IMPORT_MODULE}\" \"input\" (func $input (result i32)))\n  \
+            (memory (export \"memory\") {})\n  \
+            (func (export \"run\") (result i32)\n    \
+                (local $pointer i32) (local $value i32) (local $target i32)",
+        module.pages
+    )
+    .unwrap();
+    let mut depth = 2;
+    for op in &module.ops {
+        if matches!(op, Op::End) {
+            depth -= 1;
+        }
+        writeln!(code, "{}{}", "  ".repeat(depth), op.text(module.utf8)).unwrap();
+        if matches!(op, Op::Block | Op::Loop | Op::If) {
+            depth += 1;
+        }
+    }
+    code.push_str("  )\n)\n");
+    code
+}
+
+/// Generate a WebAssembly module in the binary format (`.wasm`), the same module as `compile_text`
+/// # Arguments
+/// * `bf_commands` - The parsed brainfuck program
+/// * `array_size` - The brainfuck array size
+/// * `features` - The extra features to follow, the same ones the interpreter uses
+pub fn compile_binary(
+    bf_commands: &[BfCommand],
+    array_size: usize,
+    features: &[Feature],
+) -> Vec<u8> {
+    let module = Module::new(bf_commands, array_size, features);
+    const I32: u8 = 0x7f;
+    const FUNC: u8 = 0x60;
+
+    let mut wasm = b"\0asm".to_vec();
+    wasm.extend_from_slice(&1u32.to_le_bytes());
+
+    // The types: `(param i32)` and `(result i32)`
+    section(&mut wasm, 1, &[2, FUNC, 1, I32, 0, FUNC, 0, 1, I32]);
+
+    let mut imports = vec![2];
+    for (name, type_index) in [("output", 0), ("input", 1)] {
+        name_bytes(&mut imports, IMPORT_MODULE);
+        name_bytes(&mut imports, name);
+        imports.extend_from_slice(&[0, type_index]);
+    }
+    section(&mut wasm, 2, &imports);
+
+    // The `run` function has the `(result i32)` type
+    section(&mut wasm, 3, &[1, 1]);
+
+    let mut memory = vec![1, 0];
+    unsigned(&mut memory, module.pages as u64);
+    section(&mut wasm, 5, &memory);
+
+    let mut exports = vec![2];
+    name_bytes(&mut exports, "memory");
+    exports.extend_from_slice(&[2, 0]);
+    name_bytes(&mut exports, "run");
+    exports.extend_from_slice(&[0, 2]);
+    section(&mut wasm, 7, &exports);
+
+    // Three i32 locals, then the body
+    let mut body = vec![1, 3, I32];
+    for op in &module.ops {
+        op.binary(&mut body, module.utf8);
+    }
+    body.push(0x0b);
+    let mut code = vec![1];
+    unsigned(&mut code, body.len() as u64);
+    code.extend(body);
+    section(&mut wasm, 10, &code);
+
+    wasm
+}
+
+/// The locals of the `run` function
+#[derive(Debug, PartialEq, Copy, Clone)]
+enum Local {
+    Pointer,
+    Value,
+    Target,
+}
+
+impl Local {
+    fn name(self) -> &'static str {
+        match self {
+            Local::Pointer => "$pointer",
+            Local::Value => "$value",
+            Local::Target => "$target",
+        }
+    }
+}
+
+/// The WebAssembly instructions used by the generated code
+#[derive(Debug, PartialEq, Copy, Clone)]
+enum Op {
+    /// A numeric instruction without immediates, e.g. `i32.add`
+    Numeric(&'static str, u8),
+    Get(Local),
+    Set(Local),
+    Tee(Local),
+    I32Const(i32),
+    I64Const(i64),
+    /// Load the cell at the address on the stack
+    Load,
+    /// Store the value on the stack to the cell at the address under it
+    Store,
+    CallOutput,
+    CallInput,
+    Block,
+    Loop,
+    If,
+    End,
+    Br(u32),
+    BrIf(u32),
+    Return,
+}
+
+const I32_ADD: Op = Op::Numeric("i32.add", 0x6a);
+const I32_SUB: Op = Op::Numeric("i32.sub", 0x6b);
+const I32_MUL: Op = Op::Numeric("i32.mul", 0x6c);
+const I32_REM_U: Op = Op::Numeric("i32.rem_u", 0x70);
+const I32_SHL: Op = Op::Numeric("i32.shl", 0x74);
+const I32_EQZ: Op = Op::Numeric("i32.eqz", 0x45);
+const I32_EQ: Op = Op::Numeric("i32.eq", 0x46);
+const I32_GT_U: Op = Op::Numeric("i32.gt_u", 0x4b);
+const I32_LT_U: Op = Op::Numeric("i32.lt_u", 0x49);
+const I32_GE_U: Op = Op::Numeric("i32.ge_u", 0x4f);
+const I32_LT_S: Op = Op::Numeric("i32.lt_s", 0x48);
+const I32_AND: Op = Op::Numeric("i32.and", 0x71);
+const I32_OR: Op = Op::Numeric("i32.or", 0x72);
+const I32_WRAP_I64: Op = Op::Numeric("i32.wrap_i64", 0xa7);
+const I64_EXTEND_I32_U: Op = Op::Numeric("i64.extend_i32_u", 0xad);
+const I64_ADD: Op = Op::Numeric("i64.add", 0x7c);
+const I64_MUL: Op = Op::Numeric("i64.mul", 0x7e);
+const I64_REM_U: Op = Op::Numeric("i64.rem_u", 0x82);
+
+impl Op {
+    fn text(&self, utf8: bool) -> String {
+        match self {
+            Op::Numeric(name, _) => name.to_string(),
+            Op::Get(local) => format!("local.get {}", local.name()),
+            Op::Set(local) => format!("local.set {}", local.name()),
+            Op::Tee(local) => format!("local.tee {}", local.name()),
+            Op::I32Const(value) => format!("i32.const {}", value),
+            Op::I64Const(value) => format!("i64.const {}", value),
+            Op::Load if utf8 => "i32.load".to_string(),
+            Op::Load => "i32.load8_u".to_string(),
+            Op::Store if utf8 => "i32.store".to_string(),
+            Op::Store => "i32.store8".to_string(),
+            Op::CallOutput => "call $output".to_string(),
+            Op::CallInput => "call $input".to_string(),
+            Op::Block => "block".to_string(),
+            Op::Loop => "loop".to_string(),
+            Op::If => "if".to_string(),
+            Op::End => "end".to_string(),
+            Op::Br(depth) => format!("br {}", depth),
+            Op::BrIf(depth) => format!("br_if {}", depth),
+            Op::Return => "return".to_string(),
+        }
+    }
+
+    fn binary(&self, wasm: &mut Vec<u8>, utf8: bool) {
+        const LOCALS: [Local; 3] = [Local::Pointer, Local::Value, Local::Target];
+        let index = |local: &Local| LOCALS.iter().position(|l| l == local).unwrap() as u8;
+        match self {
+            Op::Numeric(_, opcode) => wasm.push(*opcode),
+            Op::Get(local) => wasm.extend_from_slice(&[0x20, index(local)]),
+            Op::Set(local) => wasm.extend_from_slice(&[0x21, index(local)]),
+            Op::Tee(local) => wasm.extend_from_slice(&[0x22, index(local)]),
+            Op::I32Const(value) => {
+                wasm.push(0x41);
+                signed(wasm, *value as i64);
+            }
+            Op::I64Const(value) => {
+                wasm.push(0x42);
+                signed(wasm, *value);
+            }
+            // The memory argument is the alignment (as a power of 2) and the offset
+            Op::Load if utf8 => wasm.extend_from_slice(&[0x28, 2, 0]),
+            Op::Load => wasm.extend_from_slice(&[0x2d, 0, 0]),
+            Op::Store if utf8 => wasm.extend_from_slice(&[0x36, 2, 0]),
+            Op::Store => wasm.extend_from_slice(&[0x3a, 0, 0]),
+            Op::CallOutput => wasm.extend_from_slice(&[0x10, 0]),
+            Op::CallInput => wasm.extend_from_slice(&[0x10, 1]),
+            Op::Block => wasm.extend_from_slice(&[0x02, 0x40]),
+            Op::Loop => wasm.extend_from_slice(&[0x03, 0x40]),
+            Op::If => wasm.extend_from_slice(&[0x04, 0x40]),
+            Op::End => wasm.push(0x0b),
+            Op::Br(depth) => {
+                wasm.push(0x0c);
+                unsigned(wasm, *depth as u64);
+            }
+            Op::BrIf(depth) => {
+                wasm.push(0x0d);
+                unsigned(wasm, *depth as u64);
+            }
+            Op::Return => wasm.push(0x0f),
+        }
+    }
+}
+
+/// The body of the `run` function, shared by the text and the binary formats
+struct Module {
+    ops: Vec<Op>,
+    pages: usize,
+    utf8: bool,
+    array_size: usize,
+    wrap_value: bool,
+    wrap_pointer: bool,
+    max_value: i64,
+}
+
+impl Module {
+    fn new(bf_commands: &[BfCommand], array_size: usize, features: &[Feature]) -> Self {
+        let utf8 = features.contains(&Feature::AllowUtf8);
+        let cell_size = if utf8 { 4 } else { 1 };
+        let mut module = Self {
+            ops: Vec::new(),
+            pages: (array_size * cell_size).div_ceil(65536).max(1),
+            utf8,
+            array_size,
+            wrap_value: Cell::wraps(features),
+            wrap_pointer: features.contains(&Feature::ReversePointer),
+            max_value: Cell::default_cell(features).max_value() as i64,
+        };
+        module.instrs(&ir::optimize(bf_commands, features));
+        module.ops.push(Op::I32Const(0));
+        module
+    }
+
+    fn instrs(&mut self, instrs: &[Instr]) {
+        for instr in instrs {
+            match instr {
+                Instr::Add(n) => {
+                    self.address(Local::Pointer);
+                    self.load(Local::Pointer);
+                    self.add(*n);
+                    self.ops.push(Op::Store);
+                }
+                Instr::Move(n) => self.move_pointer(Local::Pointer, *n),
+                Instr::Clear => {
+                    self.address(Local::Pointer);
+                    self.ops.extend([Op::I32Const(0), Op::Store]);
+                }
+                Instr::MulLoop(targets) => {
+                    self.load(Local::Pointer);
+                    self.ops.extend([Op::Tee(Local::Value), Op::If]);
+                    for (offset, factor) in targets {
+                        self.ops
+                            .extend([Op::Get(Local::Pointer), Op::Set(Local::Target)]);
+                        self.move_pointer(Local::Target, *offset);
+                        self.address(Local::Target);
+                        self.mul_add(*factor);
+                        self.ops.push(Op::Store);
+                    }
+                    self.address(Local::Pointer);
+                    self.ops.extend([Op::I32Const(0), Op::Store, Op::End]);
+                }
+                Instr::Print => {
+                    self.load(Local::Pointer);
+                    if self.utf8 {
+                        // The code points over 0x10ffff and the surrogates are not chars
+                        self.ops.extend([
+                            Op::Tee(Local::Value),
+                            Op::I32Const(0x10ffff),
+                            I32_GT_U,
+                            Op::Get(Local::Value),
+                            Op::I32Const(-0x800),
+                            I32_AND,
+                            Op::I32Const(0xd800),
+                            I32_EQ,
+                            I32_OR,
+                            Op::If,
+                        ]);
+                        self.fail(InterpreterErrorKind::InvalidUtf8.code());
+                        self.ops.extend([Op::End, Op::Get(Local::Value)]);
+                    }
+                    self.ops.push(Op::CallOutput);
+                }
+                Instr::Read => {
+                    self.ops.extend([
+                        Op::CallInput,
+                        Op::Tee(Local::Value),
+                        Op::I32Const(0),
+                        I32_LT_S,
+                        Op::If,
+                    ]);
+                    self.fail(InterpreterErrorKind::IoError(std::io::Error::other("")).code());
+                    self.ops.push(Op::End);
+                    self.address(Local::Pointer);
+                    self.ops.extend([Op::Get(Local::Value), Op::Store]);
+                }
+                Instr::Loop(loop_body) => {
+                    self.ops.extend([Op::Block, Op::Loop]);
+                    self.load(Local::Pointer);
+                    self.ops.extend([I32_EQZ, Op::BrIf(1)]);
+                    self.instrs(loop_body);
+                    self.ops.extend([Op::Br(0), Op::End, Op::End]);
+                }
+            }
+        }
+    }
+
+    /// Push the memory address of the cell at the local
+    fn address(&mut self, local: Local) {
+        self.ops.push(Op::Get(local));
+        if self.utf8 {
+            self.ops.extend([Op::I32Const(2), I32_SHL]);
+        }
+    }
+
+    /// Push the value of the cell at the local
+    fn load(&mut self, local: Local) {
+        self.address(local);
+        self.ops.push(Op::Load);
+    }
+
+    /// Add to the value on the stack, or return the value error code if it goes out of bounds
+    fn add(&mut self, n: i64) {
+        if self.wrap_value {
+            let n = n.rem_euclid(self.max_value + 1);
+            self.ops.extend([Op::I32Const(n as i32), I32_ADD]);
+            if self.utf8 {
+                self.ops
+                    .extend([Op::I32Const(self.max_value as i32 + 1), I32_REM_U]);
+            }
+            return;
+        }
+
+        let value_code = InterpreterErrorKind::ValueOutOfBounds.code();
+        let value = n.unsigned_abs() as i64;
+        if value > self.max_value {
+            self.fail(value_code);
+            return;
+        }
+        self.ops.push(Op::Tee(Local::Value));
+        if n > 0 {
+            self.ops
+                .extend([Op::I32Const((self.max_value - value) as i32), I32_GT_U]);
+        } else {
+            self.ops.extend([Op::I32Const(value as i32), I32_LT_U]);
+        }
+        self.ops.push(Op::If);
+        self.fail(value_code);
+        self.ops.extend([
+            Op::End,
+            Op::Get(Local::Value),
+            Op::I32Const(value as i32),
+            if n > 0 { I32_ADD } else { I32_SUB },
+        ]);
+    }
+
+    /// Push the cell at the target plus `$value` multiplied by the factor,
+    /// the multiplication loops are only used when the values wrap
+    fn mul_add(&mut self, factor: i64) {
+        let factor = factor.rem_euclid(self.max_value + 1);
+        if self.utf8 {
+            self.load(Local::Target);
+            self.ops.extend([
+                I64_EXTEND_I32_U,
+                Op::Get(Local::Value),
+                I64_EXTEND_I32_U,
+                Op::I64Const(factor),
+                I64_MUL,
+                I64_ADD,
+                Op::I64Const(self.max_value + 1),
+                I64_REM_U,
+                I32_WRAP_I64,
+            ]);
+        } else {
+            self.load(Local::Target);
+            self.ops.extend([
+                Op::Get(Local::Value),
+                Op::I32Const(factor as i32),
+                I32_MUL,
+                I32_ADD,
+            ]);
+        }
+    }
+
+    /// Move the pointer in the local, or return the pointer error code if it goes out of bounds
+    fn move_pointer(&mut self, local: Local, n: isize) {
+        let size = self.array_size as i32;
+        if self.wrap_pointer {
+            let n = n.rem_euclid(self.array_size as isize) as i32;
+            self.ops.extend([
+                Op::Get(local),
+                Op::I32Const(n),
+                I32_ADD,
+                Op::I32Const(size),
+                I32_REM_U,
+                Op::Set(local),
+            ]);
+            return;
+        }
+
+        let pointer_code = InterpreterErrorKind::PointerOutOfBounds(0).code();
+        self.ops.extend([
+            Op::Get(local),
+            Op::I32Const(n as i32),
+            I32_ADD,
+            Op::Tee(local),
+        ]);
+        if n > 0 {
+            self.ops.extend([Op::I32Const(size), I32_GE_U, Op::If]);
+        } else {
+            self.ops.extend([Op::I32Const(0), I32_LT_S, Op::If]);
+        }
+        self.fail(pointer_code);
+        self.ops.push(Op::End);
+    }
+
+    fn fail(&mut self, code: i32) {
+        self.ops.extend([Op::I32Const(code), Op::Return]);
+    }
+}
+
+fn section(wasm: &mut Vec<u8>, id: u8, content: &[u8]) {
+    wasm.push(id);
+    unsigned(wasm, content.len() as u64);
+    wasm.extend_from_slice(content);
+}
+
+fn name_bytes(wasm: &mut Vec<u8>, name: &str) {
+    unsigned(wasm, name.len() as u64);
+    wasm.extend_from_slice(name.as_bytes());
+}
+
+/// Write the value as an unsigned LEB128 number
+fn unsigned(wasm: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            wasm.push(byte);
+            return;
+        }
+        wasm.push(byte | 0x80);
+    }
+}
+
+/// Write the value as a signed LEB128 number
+fn signed(wasm: &mut Vec<u8>, mut value: i64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0) {
+            wasm.push(byte);
+            return;
+        }
+        wasm.push(byte | 0x80);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bf_interpreter::interpreter::to_bf_commands;
+    use crate::utils;
+    use pretty_assertions::assert_eq;
+
+    fn compile_code(code: &str, features: &[Feature]) -> String {
+        compile_text(
+            &to_bf_commands(code.chars().collect()).unwrap(),
+            30000,
+            features,
+        )
+    }
+
+    #[test]
+    fn leb128() {
+        let mut bytes = Vec::new();
+        unsigned(&mut bytes, 624485);
+        assert_eq!(bytes, vec![0xe5, 0x8e, 0x26]);
+
+        let mut bytes = Vec::new();
+        signed(&mut bytes, -123456);
+        assert_eq!(bytes, vec![0xc0, 0xbb, 0x78]);
+
+        let mut bytes = Vec::new();
+        signed(&mut bytes, 64);
+        assert_eq!(bytes, vec![0xc0, 0x00]);
+    }
+
+    #[test]
+    fn loops_and_io() {
+        let code = compile_code("[.,]", &[Feature::NoReverseValue]);
+
+        assert!(code.contains(
+            "    block\n      \
+                   loop\n        \
+                     local.get $pointer\n        \
+                     i32.load8_u\n        \
+                     i32.eqz\n        \
+                     br_if 1\n        \
+                     local.get $pointer\n        \
+                     i32.load8_u\n        \
+                     call $output\n        \
+                     call $input\n"
+        ));
+        assert!(code.contains("        br 0\n      end\n    end\n    i32.const 0\n  )\n)\n"));
+    }
+
+    #[test]
+    fn features_select_the_cell_width_and_the_checks() {
+        let code = compile_code("+>", &[]);
+        assert!(code.contains("(memory (export \"memory\") 1)"));
+        assert!(code.contains("i32.store8\n"));
+        assert!(code.contains("      i32.const 12\n      return\n"));
+        assert!(code.contains("      i32.const 11\n      return\n"));
+
+        let code = compile_code(
+            "+>",
+            &[
+                Feature::AllowUtf8,
+                Feature::NoReverseValue,
+                Feature::ReversePointer,
+            ],
+        );
+        assert!(code.contains("(memory (export \"memory\") 2)"));
+        assert!(code.contains("i32.const 2\n    i32.shl\n    i32.load\n"));
+        assert!(code.contains("i32.const 1114112\n    i32.rem_u\n"));
+        assert!(!code.contains("return"));
+    }
+
+    #[test]
+    fn run_with_node() {
+        if !crate::compiler::tool_exists("node") {
+            return;
+        }
+        let bf_commands = to_bf_commands(
+            utils::read_brainfuck_code(&String::from("test_code/read_name_and_print_hi.bf"))
+                .chars()
+                .collect(),
+        )
+        .unwrap();
+        let dir = std::env::temp_dir().join(format!("bfy-wasm-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let module = dir.join("read_name_and_print_hi.wasm");
+        std::fs::write(&module, compile_binary(&bf_commands, 30000, &[])).unwrap();
+
+        let output = std::process::Command::new("node")
+            .arg("-e")
+            .arg(
+                "const input = [...'Anas\\n'].map(c => c.codePointAt(0));\n\
+                let output = '';\n\
+                WebAssembly.instantiate(require('fs').readFileSync(process.argv[1]), {\n\
+                    bfy: {\n\
+                        output: c => { output += String.fromCodePoint(c); },\n\
+                        input: () => input.length ? input.shift() : -1,\n\
+                    },\n\
+                }).then(({ instance }) => {\n\
+                    const code = instance.exports.run();\n\
+                    console.log(JSON.stringify([output, code]));\n\
+                });",
+            )
+            .arg(&module)
+            .output()
+            .unwrap();
+
+        // The program reads until the end of the input, that fails like the interpreter does
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            "[\"Hi Anas\\n\",13]\n"
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
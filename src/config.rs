@@ -0,0 +1,182 @@
+use crate::arguments::{Eof, Feature};
+use crate::repl::repl::{HISTORY_FILE, PROMPT};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+/// The project config file name, it is searched in the current directory and its parents
+pub const PROJECT_CONFIG_FILE: &str = ".bfy.toml";
+
+/// The default options from the config files, the command line and the source file header override them
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Config {
+    pub features: Option<Vec<Feature>>,
+    pub array_size: Option<usize>,
+    pub eof: Option<Eof>,
+    pub without_tiles: bool,
+    /// Print the colours, `false` disables them everywhere
+    pub color: bool,
+    pub repl: ReplConfig,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct ReplConfig {
+    pub prompt: String,
+    /// The file of the `!save` and `!load` commands
+    pub history: String,
+    /// The prefix of the REPL commands, by default `!` (or `/` with the Extended Type I feature)
+    pub command_prefix: Option<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            features: None,
+            array_size: None,
+            eof: None,
+            without_tiles: false,
+            color: true,
+            repl: ReplConfig::default(),
+        }
+    }
+}
+
+impl Default for ReplConfig {
+    fn default() -> Self {
+        Self {
+            prompt: PROMPT.to_string(),
+            history: HISTORY_FILE.to_string(),
+            command_prefix: None,
+        }
+    }
+}
+
+/// The user config file, `$XDG_CONFIG_HOME/bfy/config.toml` or `~/.config/bfy/config.toml`
+pub fn user_config_file() -> Option<PathBuf> {
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+    Some(config_home.join("bfy").join("config.toml"))
+}
+
+/// The nearest project config file, in the directory or in one of its parents
+pub fn project_config_file(directory: &Path) -> Option<PathBuf> {
+    directory
+        .ancestors()
+        .map(|directory| directory.join(PROJECT_CONFIG_FILE))
+        .find(|file| file.is_file())
+}
+
+/// Load the config from the user config file and the project config file, the project one overrides the user one
+/// # Returns
+/// The config, or an error message if a config file is invalid
+pub fn load() -> Result<Config, String> {
+    let files = [
+        user_config_file(),
+        std::env::current_dir()
+            .ok()
+            .and_then(|directory| project_config_file(&directory)),
+    ];
+    let mut table = toml::Table::new();
+    for file in files.into_iter().flatten().filter(|file| file.is_file()) {
+        info!("Reading the config file: {}", file.display());
+        let content = std::fs::read_to_string(&file)
+            .map_err(|e| format!("Failed to read the config file {}: {}", file.display(), e))?;
+        let file_table = content
+            .parse::<toml::Table>()
+            .map_err(|e| format!("Invalid config file {}: {}", file.display(), e))?;
+        merge(&mut table, file_table);
+    }
+    parse(table)
+}
+
+/// Parse the merged config tables
+fn parse(table: toml::Table) -> Result<Config, String> {
+    let config = Config::deserialize(toml::Value::Table(table))
+        .map_err(|e| format!("Invalid config: {}", e))?;
+    // Every input would be a REPL command with an empty prefix
+    if config.repl.command_prefix.as_deref() == Some("") {
+        return Err("Invalid config: the `repl.command-prefix` can't be empty".to_string());
+    }
+    Ok(config)
+}
+
+/// Merge the tables, the values of the overriding table replace the values of the base table,
+/// and the sub tables (e.g. `[repl]`) are merged
+fn merge(base: &mut toml::Table, overriding: toml::Table) {
+    for (key, value) in overriding {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base_table)), toml::Value::Table(table)) => {
+                merge(base_table, table)
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn merge_the_config_files() {
+        let mut table: toml::Table =
+            "features = [\"nrv\"]\narray-size = 100\n[repl]\nprompt = \"bf> \""
+                .parse()
+                .unwrap();
+        merge(
+            &mut table,
+            "array-size = 10\neof = \"zero\"\ncolor = false\n[repl]\nhistory = \"h.bfr\""
+                .parse()
+                .unwrap(),
+        );
+        assert_eq!(
+            parse(table),
+            Ok(Config {
+                features: Some(vec![Feature::NoReverseValue]),
+                array_size: Some(10),
+                eof: Some(Eof::Zero),
+                without_tiles: false,
+                color: false,
+                repl: ReplConfig {
+                    prompt: "bf> ".to_string(),
+                    history: "h.bfr".to_string(),
+                    command_prefix: None,
+                },
+            })
+        );
+        assert_eq!(parse(toml::Table::new()), Ok(Config::default()));
+    }
+
+    #[test]
+    fn invalid_config() {
+        assert!(parse("features = [\"abc\"]".parse().unwrap())
+            .unwrap_err()
+            .contains("abc"));
+        assert!(parse("array_size = 10".parse().unwrap())
+            .unwrap_err()
+            .contains("array_size"));
+        assert!(parse("[repl]\ncommand-prefix = \"\"".parse().unwrap())
+            .unwrap_err()
+            .contains("command-prefix"));
+    }
+
+    #[test]
+    fn find_the_project_config_file() {
+        let directory =
+            std::env::temp_dir().join(format!("bfy_config_test_{}", std::process::id()));
+        let nested = directory.join("a").join("b");
+        std::fs::create_dir_all(&nested).unwrap();
+        std::fs::write(directory.join(PROJECT_CONFIG_FILE), "").unwrap();
+        assert_eq!(
+            project_config_file(&nested),
+            Some(directory.join(PROJECT_CONFIG_FILE))
+        );
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
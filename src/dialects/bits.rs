@@ -0,0 +1,398 @@
+use crate::arguments::Lang;
+use crate::dialects::{Translated, COMMANDS_PER_LINE};
+
+// Boolfuck and Smallfuck work on a tape of bits. Boolfuck `+` flips the current bit, `,` reads a bit
+// and `;` writes a bit (the bits of the bytes are read and written from the least significant one),
+// Smallfuck `*` flips the current bit and has no input and output. The interpreter runs them on the bit cells.
+//
+// In the translated brainfuck every bit is a group of 5 cells: a marker (1 for the bits the pointer visited),
+// a breadcrumb, the bit, a temporary cell and a carry. The groups start after a sentinel group (its marker is 0),
+// and the cells before the sentinel hold the state of the bits input and output. To read or write a bit,
+// the pointer walks to the sentinel carrying the bit and leaving the breadcrumbs, runs the input or the output
+// on the state, and follows the breadcrumbs back.
+
+/// The cells of a bit in the translated brainfuck, and the offsets of the cells in the group
+const GROUP: usize = 5;
+const MARKER: usize = 0;
+const BREADCRUMB: usize = 1;
+const BIT: usize = 2;
+const CARRY: usize = 4;
+
+/// The output state: the byte being written, the weight of the next bit, the number of the bits left
+/// in the byte, and the temporary cells (the cells don't wrap, so the weight doesn't overflow to count the bits)
+const OUTPUT_BYTE: usize = 0;
+const WEIGHT: usize = 1;
+const LEFT: usize = 2;
+const COPY: usize = 3;
+const TEMP: usize = 4;
+const FLAG: usize = 5;
+/// The input state: the number of the bits left in the byte being read, the temporary cells,
+/// and the cells of the division of the byte by 2 (the byte, its copy, the divisor, the remainder, the quotient
+/// and 2 zeros)
+const REMAINING: usize = 6;
+const READ_FLAG: usize = 7;
+const READ_COPY: usize = 8;
+const INPUT_BYTE: usize = 9;
+const DIVIDEND: usize = 10;
+const DIVISOR: usize = 11;
+const REMAINDER: usize = 12;
+const QUOTIENT: usize = 13;
+const SENTINEL: usize = 16;
+
+/// The brainfuck commands and their Boolfuck code, every brainfuck cell is 9 bits: a temporary bit and the 8 bits
+const FROM_BRAINFUCK: [(char, &str); 8] = [
+    ('+', ">[>]+<[+<]>>>>>>>>>[+]<<<<<<<<<"),
+    ('-', ">>>>>>>>>+<<<<<<<<+[>+]<[<]>>>>>>>>>[+]<<<<<<<<<"),
+    ('<', "<<<<<<<<<"),
+    ('>', ">>>>>>>>>"),
+    (',', ">,>,>,>,>,>,>,>,<<<<<<<<"),
+    ('.', ">;>;>;>;>;>;>;>;<<<<<<<<"),
+    (
+        '[',
+        ">>>>>>>>>+<<<<<<<<+[>+]<[<]>>>>>>>>>[+<<<<<<<<[>]+<[+<]",
+    ),
+    (']', ">>>>>>>>>+<<<<<<<<+[>+]<[<]>>>>>>>>>]<[+<]"),
+];
+
+/// The brainfuck code that moves the pointer between the known cells
+struct Builder {
+    code: String,
+    at: usize,
+}
+
+impl Builder {
+    fn go(&mut self, cell: usize) -> &mut Self {
+        let (command, count) = match cell > self.at {
+            true => ('>', cell - self.at),
+            false => ('<', self.at - cell),
+        };
+        self.code.extend(std::iter::repeat_n(command, count));
+        self.at = cell;
+        self
+    }
+
+    fn push(&mut self, code: &str) -> &mut Self {
+        self.code.push_str(code);
+        self
+    }
+}
+
+/// The brainfuck command the interpreter runs on the bit cells for the char of the bit language, the flips are `+`
+fn command(lang: Lang, c: char) -> Option<char> {
+    match (lang, c) {
+        (_, '<' | '>' | '[' | ']') => Some(c),
+        (Lang::Boolfuck, '+' | ',') => Some(c),
+        (Lang::Boolfuck, ';') => Some('.'),
+        (Lang::Smallfuck, '*') => Some('+'),
+        _ => None,
+    }
+}
+
+/// The commands of the bit language as the brainfuck commands the interpreter runs on the bit cells,
+/// the other chars are comments
+pub fn to_commands(lang: Lang, source: &str) -> String {
+    source.chars().filter_map(|c| command(lang, c)).collect()
+}
+
+/// Translate the source code of the bit language to brainfuck, the 8 bit cells are needed
+pub fn to_brainfuck(lang: Lang, source: &str) -> Translated {
+    // Set the weight and the number of the bits of the first output byte, and mark the first bit
+    let mut prologue = Builder {
+        code: String::new(),
+        at: 0,
+    };
+    prologue
+        .go(WEIGHT)
+        .push("+")
+        .go(LEFT)
+        .push("++++++++")
+        .go(SENTINEL + GROUP + MARKER)
+        .push("+")
+        .go(SENTINEL + GROUP + BIT);
+    let mut translated = Translated {
+        locations: vec![0; prologue.code.len()],
+        code: prologue.code,
+    };
+    let commands = source
+        .chars()
+        .enumerate()
+        .filter_map(|(index, c)| command(lang, c).map(|command| (index, command)));
+    for (index, command) in commands {
+        let code = match command {
+            '>' => ">>>>><<[-]+>>".to_string(),
+            '<' => "<<<<<".to_string(),
+            '+' => ">+<[>-<-]>[<+>-]<".to_string(),
+            '[' | ']' => command.to_string(),
+            // Copy the bit to the carry
+            '.' => format!("[->+>+<<]>[-<+>]<{}", visit_sentinel(write_bit)),
+            ',' => format!("{}[-]>>[-<<+>>]<<", visit_sentinel(read_bit)),
+            _ => unreachable!("The bit languages have no other commands"),
+        };
+        translated
+            .locations
+            .extend(std::iter::repeat_n(index, code.len()));
+        translated.code.push_str(&code);
+    }
+    if lang == Lang::Boolfuck {
+        // Write the last byte if it has some bits, the missing bits are 0
+        let mut epilogue = Builder {
+            code: "<<[<<<<<]".to_string(),
+            at: SENTINEL,
+        };
+        epilogue
+            .go(TEMP)
+            .push("++++++++")
+            .go(LEFT)
+            .push("[-")
+            .go(TEMP)
+            .push("-")
+            .go(LEFT)
+            .push("]")
+            .go(TEMP)
+            .push("[")
+            .go(OUTPUT_BYTE)
+            .push(".")
+            .go(TEMP)
+            .push("[-]]");
+        translated.locations.extend(std::iter::repeat_n(
+            source.chars().count(),
+            epilogue.code.len(),
+        ));
+        translated.code.push_str(&epilogue.code);
+    }
+    translated
+}
+
+/// Walk from the bit to the sentinel carrying the carry, run the code on the state, and walk back
+/// carrying the sentinel carry to the bit carry
+fn visit_sentinel(run: fn(&mut Builder)) -> String {
+    // Leave a breadcrumb and move the carry to the previous group, until the sentinel
+    let mut builder = Builder {
+        code: "<<[>+>>>[-<<<<<+>>>>>]<<<<<<<<<]".to_string(),
+        at: SENTINEL,
+    };
+    run(&mut builder);
+    builder
+        .go(SENTINEL + GROUP + BREADCRUMB)
+        // Clear the breadcrumbs and move the carry to the next group, until the bit after the breadcrumbs
+        .push("[-<<[->>>>>+<<<<<]>>>>>>>]<<<<<>");
+    builder.code
+}
+
+/// Write the sentinel carry bit, and the byte after its 8th bit
+fn write_bit(builder: &mut Builder) {
+    builder
+        // Add the weight to the byte if the bit is set
+        .go(SENTINEL + CARRY)
+        .push("[")
+        .go(WEIGHT)
+        .push("[-")
+        .go(OUTPUT_BYTE)
+        .push("+")
+        .go(COPY)
+        .push("+")
+        .go(WEIGHT)
+        .push("]")
+        .go(COPY)
+        .push("[-")
+        .go(WEIGHT)
+        .push("+")
+        .go(COPY)
+        .push("]")
+        .go(SENTINEL + CARRY)
+        .push("-]")
+        // Double the weight if there are bits left in the byte, otherwise write the byte
+        .go(LEFT)
+        .push("-")
+        .go(FLAG)
+        .push("+")
+        .go(LEFT)
+        .push("[-")
+        .go(COPY)
+        .push("+")
+        .go(TEMP)
+        .push("+")
+        .go(LEFT)
+        .push("]")
+        .go(TEMP)
+        .push("[-")
+        .go(LEFT)
+        .push("+")
+        .go(TEMP)
+        .push("]")
+        .go(COPY)
+        .push("[")
+        .go(FLAG)
+        .push("-")
+        .go(WEIGHT)
+        .push("[-")
+        .go(TEMP)
+        .push("++")
+        .go(WEIGHT)
+        .push("]")
+        .go(TEMP)
+        .push("[-")
+        .go(WEIGHT)
+        .push("+")
+        .go(TEMP)
+        .push("]")
+        .go(COPY)
+        .push("[-]]")
+        .go(FLAG)
+        .push("[")
+        .go(OUTPUT_BYTE)
+        .push(".[-]")
+        .go(WEIGHT)
+        .push("[-]+")
+        .go(LEFT)
+        .push("++++++++")
+        .go(FLAG)
+        .push("-]")
+        .go(SENTINEL);
+}
+
+/// Read a bit to the sentinel carry, and the next byte when the bits of the byte are all read
+fn read_bit(builder: &mut Builder) {
+    builder
+        // Read the byte if there is no bit left
+        .go(READ_FLAG)
+        .push("+")
+        .go(REMAINING)
+        .push("[-")
+        .go(READ_COPY)
+        .push("+")
+        .go(REMAINDER)
+        .push("+")
+        .go(REMAINING)
+        .push("]")
+        .go(REMAINDER)
+        .push("[-")
+        .go(REMAINING)
+        .push("+")
+        .go(REMAINDER)
+        .push("]")
+        .go(READ_COPY)
+        .push("[")
+        .go(READ_FLAG)
+        .push("-")
+        .go(READ_COPY)
+        .push("[-]]")
+        .go(READ_FLAG)
+        .push("[")
+        .go(INPUT_BYTE)
+        .push(",")
+        .go(REMAINING)
+        .push("++++++++")
+        .go(READ_FLAG)
+        .push("-]")
+        // Divide the byte by 2, the remainder is the bit
+        .go(DIVISOR)
+        .push("++")
+        .go(INPUT_BYTE)
+        .push("[->+>-[>+>>]>[+[-<+>]>+>>]<<<<<<]")
+        .go(DIVIDEND)
+        .push("[-]")
+        .go(DIVISOR)
+        .push("[-]")
+        .go(QUOTIENT)
+        .push("[-")
+        .go(INPUT_BYTE)
+        .push("+")
+        .go(QUOTIENT)
+        .push("]")
+        .go(REMAINDER)
+        .push("[-")
+        .go(SENTINEL + CARRY)
+        .push("+")
+        .go(REMAINDER)
+        .push("]")
+        .go(REMAINING)
+        .push("-")
+        .go(SENTINEL);
+}
+
+/// Translate the brainfuck code to the bit language, Smallfuck has no input and output
+pub fn from_brainfuck(lang: Lang, code: &str) -> String {
+    code.chars()
+        .filter_map(|c| FROM_BRAINFUCK.iter().find(|(command, _)| *command == c))
+        .map(|(_, code)| match lang {
+            Lang::Smallfuck => code.replace('+', "*"),
+            _ => code.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .chunks(COMMANDS_PER_LINE)
+        .map(|line| line.concat())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bf_interpreter::cell::Cell;
+    use crate::bf_interpreter::error::InterpreterErrorKind;
+    use crate::bf_interpreter::interpreter::Interpreter;
+    use console::Term;
+    use pretty_assertions::assert_eq;
+
+    fn bits(cells: &[Cell]) -> Vec<u32> {
+        cells.iter().map(|cell| cell.get_value_utf8()).collect()
+    }
+
+    #[test]
+    fn run_the_bit_languages() {
+        assert_eq!(to_commands(Lang::Boolfuck, "+;,*-"), "+.,");
+        assert_eq!(to_commands(Lang::Smallfuck, "+;,*-"), "+");
+
+        let mut interpreter = Interpreter::new(8, vec![], Term::stdout());
+        interpreter.set_lang(Lang::Smallfuck);
+        assert_eq!(interpreter.run(String::from("*>*>>*+[<]")), Ok(0));
+        assert_eq!(bits(&interpreter.cells[..4]), vec![1, 1, 0, 1]);
+        assert_eq!(interpreter.pointer, 2);
+        assert_eq!(
+            interpreter.run(String::from("<<<")),
+            Err(InterpreterErrorKind::PointerOutOfBounds(0).to_error())
+        );
+    }
+
+    #[test]
+    fn translate_the_bit_languages() {
+        // The bits are in the groups after the sentinel
+        let translated = to_brainfuck(Lang::Smallfuck, "*>*>>*+[<]");
+        assert_eq!(translated.code.len(), translated.locations.len());
+        let mut interpreter = Interpreter::new(100, vec![], Term::stdout());
+        assert_eq!(interpreter.run(translated.code), Ok(0));
+        let groups = (1..5).map(|group| interpreter.cells[SENTINEL + group * GROUP + BIT]);
+        assert_eq!(bits(&groups.collect::<Vec<_>>()), vec![1, 1, 0, 1]);
+
+        // The 8 bits of `A` are written, the least significant bit first, and the last 4 bits
+        // of `\n` are written with the missing bits at the end of the program like the interpreter does
+        let boolfuck = "+;+;;;;;+;+;;+;+;+;";
+        let translated = to_brainfuck(Lang::Boolfuck, boolfuck);
+        interpreter.reset();
+        interpreter.output = Some(Vec::new());
+        assert_eq!(interpreter.run(translated.code), Ok(0));
+        assert_eq!(interpreter.output.take(), Some(b"A\n".to_vec()));
+        let mut bit_interpreter = Interpreter::new(100, vec![], Term::stdout());
+        bit_interpreter.set_lang(Lang::Boolfuck);
+        bit_interpreter.output = Some(Vec::new());
+        assert_eq!(bit_interpreter.run(boolfuck.to_string()), Ok(0));
+        assert_eq!(bit_interpreter.output, Some(b"A\n".to_vec()));
+
+        // Every brainfuck cell is a temporary bit and the 8 bits, the least significant bit first
+        let mut interpreter = Interpreter::new(100, vec![], Term::stdout());
+        interpreter.set_lang(Lang::Boolfuck);
+        let boolfuck = from_brainfuck(Lang::Boolfuck, "+++>++[-<+>]");
+        assert_eq!(interpreter.run(boolfuck), Ok(0));
+        assert_eq!(bits(&interpreter.cells[..19]), {
+            let mut cells = vec![0; 19];
+            cells[1] = 1;
+            cells[3] = 1;
+            cells
+        });
+        assert_eq!(
+            from_brainfuck(Lang::Smallfuck, "<>"),
+            "<<<<<<<<<>>>>>>>>>".to_string()
+        );
+    }
+}
@@ -0,0 +1,179 @@
+use crate::dialects::{DialectError, Translated, COMMANDS_PER_LINE};
+use crate::utils::is_command;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+// The dialects that rename the 8 commands are defined in a TOML file, e.g. Pikalang:
+// name = "Pikalang"
+// words = true
+// [commands]
+// ">" = "pipi"
+// "<" = "pichu"
+// ...
+// With `words`, the tokens are the whole words between the whitespace, otherwise they are found
+// anywhere in the text (the longest token first), so they don't need the spaces between them
+
+/// A dialect from a definition file, a token for every brainfuck command
+#[derive(Debug, PartialEq, Clone)]
+pub struct Mapping {
+    pub name: String,
+    /// The brainfuck commands and their tokens
+    pub tokens: Vec<(char, String)>,
+    /// The tokens are the whole words between the whitespace
+    pub words: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DialectFile {
+    name: Option<String>,
+    #[serde(default)]
+    words: bool,
+    commands: BTreeMap<String, String>,
+}
+
+/// Parse the dialect definition file
+/// # Arguments
+/// * `default_name` - The name of the dialect if it is not in the file, e.g. the file name
+/// * `content` - The TOML content of the definition file
+/// # Returns
+/// The dialect, or an error message if the definition is invalid
+pub fn parse(default_name: &str, content: &str) -> Result<Mapping, String> {
+    let file: DialectFile = toml::from_str(content).map_err(|e| e.to_string())?;
+    let mut tokens: Vec<(char, String)> = Vec::new();
+    for (command, token) in file.commands {
+        let mut chars = command.chars();
+        let (Some(command), None) = (chars.next(), chars.next()) else {
+            return Err(format!("`{}` is not a brainfuck command", command));
+        };
+        if !is_command(command) {
+            return Err(format!("`{}` is not a brainfuck command", command));
+        }
+        if token.is_empty() || (file.words && token.contains(char::is_whitespace)) {
+            return Err(format!(
+                "The token of `{}` is empty or has a whitespace: {:?}",
+                command, token
+            ));
+        }
+        if let Some((other, _)) = tokens.iter().find(|(_, other)| *other == token) {
+            return Err(format!(
+                "The token `{}` is used for both `{}` and `{}`",
+                token, other, command
+            ));
+        }
+        tokens.push((command, token));
+    }
+    if let Some(command) = "><+-.,[]"
+        .chars()
+        .find(|command| !tokens.iter().any(|(c, _)| c == command))
+    {
+        return Err(format!("There is no token for the command `{}`", command));
+    }
+    // The longest tokens first, so a token is not found in a longer one
+    tokens.sort_by_key(|(_, token)| std::cmp::Reverse(token.chars().count()));
+    Ok(Mapping {
+        name: file.name.unwrap_or_else(|| default_name.to_string()),
+        tokens,
+        words: file.words,
+    })
+}
+
+impl Mapping {
+    /// Translate the source code of the dialect to brainfuck, the text that is not a token is a comment
+    pub fn decode(&self, source: &str) -> Result<Translated, DialectError> {
+        let chars = source.chars().collect::<Vec<_>>();
+        let mut translated = Translated {
+            code: String::new(),
+            locations: Vec::new(),
+        };
+        let mut index = 0;
+        while index < chars.len() {
+            if self.words && chars[index].is_whitespace() {
+                index += 1;
+                continue;
+            }
+            let end = match self.words {
+                true => chars[index..]
+                    .iter()
+                    .position(|c| c.is_whitespace())
+                    .map_or(chars.len(), |length| index + length),
+                false => chars.len(),
+            };
+            let found = self.tokens.iter().find(|(_, token)| {
+                let length = token.chars().count();
+                index + length <= end
+                    && (!self.words || index + length == end)
+                    && token
+                        .chars()
+                        .eq(chars[index..index + length].iter().copied())
+            });
+            match found {
+                Some((command, token)) => {
+                    translated.code.push(*command);
+                    translated.locations.push(index);
+                    index += token.chars().count();
+                }
+                None if self.words => index = end,
+                None => index += 1,
+            }
+        }
+        Ok(translated)
+    }
+
+    /// Translate the brainfuck code to the dialect, the tokens are separated by spaces
+    pub fn encode(&self, code: &str) -> String {
+        code.chars()
+            .filter_map(|c| self.tokens.iter().find(|(command, _)| *command == c))
+            .map(|(_, token)| token.as_str())
+            .collect::<Vec<_>>()
+            .chunks(COMMANDS_PER_LINE)
+            .map(|line| line.join(" "))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const SPOON: &str = "[commands]\n\
+        '+' = '1'\n'-' = '000'\n'>' = '010'\n'<' = '011'\n\
+        ']' = '0011'\n'[' = '00100'\n'.' = '001010'\n',' = '0010110'";
+
+    #[test]
+    fn translate_the_mappings() {
+        let pikalang = parse(
+            "pikalang",
+            &std::fs::read_to_string("test_code/pikalang.toml").unwrap(),
+        )
+        .unwrap();
+        assert_eq!(pikalang.name, "Pikalang");
+        let translated = pikalang.decode("pika pi pikachu\n chu pipi").unwrap();
+        assert_eq!(translated.code, "[+.]>");
+        assert_eq!(translated.locations, vec![0, 5, 8, 17, 21]);
+        assert_eq!(pikalang.encode("+[-]"), "pi pika ka chu".to_string());
+
+        let spoon = parse("spoon", SPOON).unwrap();
+        assert_eq!(spoon.name, "spoon");
+        assert_eq!(spoon.decode("1 1 001000000011").unwrap().code, "++[-]");
+        assert_eq!(spoon.decode(&spoon.encode(",[.,]")).unwrap().code, ",[.,]");
+    }
+
+    #[test]
+    fn invalid_mappings() {
+        assert_eq!(
+            parse("a", "[commands]\n'+' = 'a'"),
+            Err("There is no token for the command `>`".to_string())
+        );
+        assert_eq!(
+            parse("a", "[commands]\n'+' = 'a'\n'-' = 'a'"),
+            Err("The token `a` is used for both `+` and `-`".to_string())
+        );
+        assert_eq!(
+            parse("a", "[commands]\n'add' = 'a'"),
+            Err("`add` is not a brainfuck command".to_string())
+        );
+    }
+}
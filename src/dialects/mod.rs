@@ -0,0 +1,172 @@
+pub mod bits;
+pub mod mapping;
+pub mod ook;
+
+use crate::arguments::Lang;
+use crate::dialects::mapping::Mapping;
+use crate::tools::diagnostic::Position;
+use crate::utils;
+use std::fmt::{Display, Formatter};
+
+// The dialects are the languages with other tokens for the 8 brainfuck commands,
+// their source code is translated to brainfuck before it is run, compiled or analysed
+
+/// The number of the commands in a line of the translated dialect code
+const COMMANDS_PER_LINE: usize = 8;
+
+/// The language of the source code, a built-in language or a dialect from a definition file
+#[derive(Debug, PartialEq, Clone)]
+pub enum Dialect {
+    Lang(Lang),
+    Mapping(Mapping),
+}
+
+impl From<Lang> for Dialect {
+    fn from(lang: Lang) -> Self {
+        Dialect::Lang(lang)
+    }
+}
+
+impl Display for Dialect {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            Dialect::Lang(lang) => write!(f, "{:?}", lang),
+            Dialect::Mapping(mapping) => write!(f, "{}", mapping.name),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct DialectError {
+    pub message: String,
+    pub position: Position,
+}
+
+impl Display for DialectError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "{}: {}", self.position, self.message)
+    }
+}
+
+/// The brainfuck code translated from the source code of a dialect
+#[derive(Debug, PartialEq)]
+pub struct Translated {
+    pub code: String,
+    /// The char index in the source code of every command of the brainfuck code
+    pub locations: Vec<usize>,
+}
+
+/// The language of the source file from its extension, brainfuck if the extension is not known
+pub fn detect(source: &str) -> Lang {
+    match std::path::Path::new(source)
+        .extension()
+        .and_then(|extension| extension.to_str())
+    {
+        Some("ook") => Lang::Ook,
+        _ => Lang::Brainfuck,
+    }
+}
+
+/// Translate the source code of the dialect to brainfuck, the comments are dropped
+pub fn to_brainfuck(dialect: &Dialect, source: &str) -> Result<Translated, DialectError> {
+    match dialect {
+        Dialect::Lang(Lang::Brainfuck) => {
+            let (locations, code) = source
+                .chars()
+                .enumerate()
+                .filter(|(_, c)| utils::is_command(*c))
+                .unzip();
+            Ok(Translated { code, locations })
+        }
+        Dialect::Lang(Lang::Ook) => ook::to_brainfuck(source),
+        Dialect::Lang(lang @ (Lang::Boolfuck | Lang::Smallfuck)) => {
+            Ok(bits::to_brainfuck(*lang, source))
+        }
+        Dialect::Mapping(mapping) => mapping.decode(source),
+    }
+}
+
+/// Translate the brainfuck code (without comments) to the dialect
+pub fn from_brainfuck(dialect: &Dialect, code: &str) -> String {
+    match dialect {
+        Dialect::Lang(Lang::Brainfuck) => code.to_string(),
+        Dialect::Lang(Lang::Ook) => ook::from_brainfuck(code),
+        Dialect::Lang(lang @ (Lang::Boolfuck | Lang::Smallfuck)) => {
+            bits::from_brainfuck(*lang, code)
+        }
+        Dialect::Mapping(mapping) => mapping.encode(code),
+    }
+}
+
+/// Convert the source code file from its dialect to another one
+/// # Arguments
+/// * `source` - The source code file
+/// * `dialect` - The dialect of the source code file
+/// * `to` - The dialect to convert to
+/// * `output` - The file to write the converted code to, if `None` it will be printed to stdout
+pub fn convert_file(source: &String, dialect: &Dialect, to: &Dialect, output: Option<&String>) {
+    info!("Converting the source code from file: {} to {}", source, to);
+    let code = utils::read_code(source, dialect, &[]);
+    if *to == Dialect::Lang(Lang::Smallfuck) && code.contains(['.', ',']) {
+        error!(
+            "The code can't be converted to Smallfuck, it has input or output commands: {}",
+            source
+        );
+        eprintln!(
+            "The code can't be converted to Smallfuck, it has input or output commands: {}",
+            source
+        );
+        std::process::exit(1);
+    }
+    let mut code = from_brainfuck(to, &code);
+    code.push('\n');
+    utils::write_output(code.as_bytes(), output);
+}
+
+/// The position of the char index in the source code
+fn position(source: &str, char_index: usize) -> Position {
+    let mut position = Position { line: 1, column: 1 };
+    for c in source.chars().take(char_index) {
+        match c {
+            '\n' => {
+                position.line += 1;
+                position.column = 1;
+            }
+            _ => position.column += 1,
+        }
+    }
+    position
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn translate_the_dialects() {
+        assert_eq!(detect("test_code/hello_world.ook"), Lang::Ook);
+        assert_eq!(detect("test_code/hello_world.bf"), Lang::Brainfuck);
+        assert_eq!(
+            to_brainfuck(&Lang::Brainfuck.into(), "+a\n[-]"),
+            Ok(Translated {
+                code: "+[-]".to_string(),
+                locations: vec![0, 3, 4, 5],
+            })
+        );
+        let hello_world = utils::read_brainfuck_code(&"test_code/hello_world.bf".to_string());
+        assert_eq!(
+            utils::read_brainfuck_code(&"test_code/hello_world.ook".to_string()),
+            hello_world
+        );
+        assert_eq!(
+            to_brainfuck(
+                &Lang::Ook.into(),
+                &from_brainfuck(&Lang::Ook.into(), &hello_world)
+            )
+            .unwrap()
+            .code,
+            hello_world
+        );
+    }
+}
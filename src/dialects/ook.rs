@@ -0,0 +1,106 @@
+use crate::dialects::{position, DialectError, Translated, COMMANDS_PER_LINE};
+
+// Ook! is brainfuck for the orang-utans, every command is a pair of the `Ook.`, `Ook?` and `Ook!` tokens:
+// `Ook. Ook?` is `>`, and `Ook? Ook.` is `<` ... The other text is a comment
+
+/// The brainfuck commands and the punctuations of their Ook! tokens
+const COMMANDS: [(char, [char; 2]); 8] = [
+    ('>', ['.', '?']),
+    ('<', ['?', '.']),
+    ('+', ['.', '.']),
+    ('-', ['!', '!']),
+    ('.', ['!', '.']),
+    (',', ['.', '!']),
+    ('[', ['!', '?']),
+    (']', ['?', '!']),
+];
+
+/// Translate the Ook! source code to brainfuck
+pub fn to_brainfuck(source: &str) -> Result<Translated, DialectError> {
+    let chars = source.chars().collect::<Vec<_>>();
+    let mut tokens = Vec::new();
+    let mut index = 0;
+    while index < chars.len() {
+        match chars[index..] {
+            ['O', 'o', 'k', punctuation @ ('.' | '?' | '!'), ..] => {
+                tokens.push((punctuation, index));
+                index += 4;
+            }
+            _ => index += 1,
+        }
+    }
+
+    let mut translated = Translated {
+        code: String::new(),
+        locations: Vec::new(),
+    };
+    for pair in tokens.chunks(2) {
+        let [(first, index), (second, _)] = *pair else {
+            return Err(DialectError {
+                message: format!("The token `Ook{}` has no pair", pair[0].0),
+                position: position(source, pair[0].1),
+            });
+        };
+        let Some((command, _)) = COMMANDS
+            .iter()
+            .find(|(_, punctuations)| *punctuations == [first, second])
+        else {
+            return Err(DialectError {
+                message: format!("`Ook{} Ook{}` is not an Ook! command", first, second),
+                position: position(source, index),
+            });
+        };
+        translated.code.push(*command);
+        translated.locations.push(index);
+    }
+    Ok(translated)
+}
+
+/// Translate the brainfuck code to Ook!
+pub fn from_brainfuck(code: &str) -> String {
+    code.chars()
+        .filter_map(|c| COMMANDS.iter().find(|(command, _)| *command == c))
+        .map(|(_, [first, second])| format!("Ook{} Ook{}", first, second))
+        .collect::<Vec<_>>()
+        .chunks(COMMANDS_PER_LINE)
+        .map(|line| line.join(" "))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::diagnostic::Position;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn translate_ook() {
+        assert_eq!(
+            to_brainfuck("Ook. Ook? The orang-utan\nOook! Ook! Ook?Ook? Ook!"),
+            Ok(Translated {
+                code: ">[]".to_string(),
+                locations: vec![0, 31, 40],
+            })
+        );
+        assert_eq!(
+            from_brainfuck(",[.,]+++++"),
+            "Ook. Ook! Ook! Ook? Ook! Ook. Ook. Ook! Ook? Ook! Ook. Ook. Ook. Ook. Ook. Ook.\nOok. Ook. Ook. Ook."
+        );
+    }
+
+    #[test]
+    fn invalid_ook() {
+        assert_eq!(
+            to_brainfuck("Ook. Ook?\nOok? Ook?"),
+            Err(DialectError {
+                message: "`Ook? Ook?` is not an Ook! command".to_string(),
+                position: Position { line: 2, column: 1 },
+            })
+        );
+        assert_eq!(
+            to_brainfuck("Ook. Ook? Ook!").unwrap_err().to_string(),
+            "1:11: The token `Ook!` has no pair"
+        );
+    }
+}
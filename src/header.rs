@@ -0,0 +1,125 @@
+use crate::arguments::Feature;
+use clap::ValueEnum;
+
+// The header at the top of the source file configures the interpreter for the program:
+// #!/usr/bin/env bfy
+// bfy: features=nrv,rp array-size=1000 without-tiles
+// The shebang must be the first line, and the pragma lines follow it (or start the file).
+// The header lines are not brainfuck code, even with the `-` and `,` in them
+
+/// The start of a header pragma line, the lint pragma `bfy:allow(...)` has no space after the colon
+const PRAGMA: &str = "bfy:";
+
+/// The interpreter options from the header of the source file
+#[derive(Debug, PartialEq, Default)]
+pub struct Header {
+    pub features: Option<Vec<Feature>>,
+    pub array_size: Option<usize>,
+    pub without_tiles: bool,
+}
+
+/// The number of the header lines at the start of the source code
+pub fn header_lines(source: &str) -> usize {
+    source
+        .lines()
+        .enumerate()
+        .take_while(|(index, line)| (*index == 0 && line.starts_with("#!")) || is_pragma(line))
+        .count()
+}
+
+/// Replace the header lines with empty lines, so the positions of the code stay the same
+pub fn strip(source: &str) -> String {
+    let header_lines = header_lines(source);
+    source
+        .split('\n')
+        .enumerate()
+        .map(|(index, line)| if index < header_lines { "" } else { line })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Parse the options of the header pragma lines
+/// # Returns
+/// The options, or an error message for an unknown option or an invalid value
+pub fn parse(source: &str) -> Result<Header, String> {
+    let mut header = Header::default();
+    for line in source.lines().take(header_lines(source)) {
+        if !is_pragma(line) {
+            continue;
+        }
+        for option in line.trim()[PRAGMA.len()..].split_whitespace() {
+            let (key, value) = option.split_once('=').unwrap_or((option, ""));
+            match (key, value) {
+                ("features", _) => {
+                    header.features = Some(
+                        value
+                            .split(',')
+                            .filter(|feature| !feature.is_empty())
+                            .map(|feature| {
+                                Feature::from_str(feature, true).map_err(|_| {
+                                    format!("Unknown feature in the header: {}", feature)
+                                })
+                            })
+                            .collect::<Result<_, _>>()?,
+                    )
+                }
+                ("array-size", _) => {
+                    header.array_size = Some(
+                        value
+                            .parse()
+                            .map_err(|_| format!("Invalid array size in the header: {}", value))?,
+                    )
+                }
+                ("without-tiles", "") => header.without_tiles = true,
+                _ => return Err(format!("Unknown option in the header: {}", option)),
+            }
+        }
+    }
+    Ok(header)
+}
+
+fn is_pragma(line: &str) -> bool {
+    line.trim()
+        .strip_prefix(PRAGMA)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with(char::is_whitespace))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn parse_the_header() {
+        let source = "#!/usr/bin/env bfy -w\nbfy: features=nrv,rp array-size=1000\nbfy: without-tiles\n+.\nbfy: array-size=5";
+        assert_eq!(header_lines(source), 3);
+        assert_eq!(
+            parse(source),
+            Ok(Header {
+                features: Some(vec![Feature::NoReverseValue, Feature::ReversePointer]),
+                array_size: Some(1000),
+                without_tiles: true,
+            })
+        );
+        assert_eq!(strip(source), "\n\n\n+.\nbfy: array-size=5");
+        // The lint pragma and the comments are not the header
+        assert_eq!(header_lines("bfy:allow(L001)\nbfy: array-size=5"), 0);
+        assert_eq!(strip("+-\n"), "+-\n");
+    }
+
+    #[test]
+    fn invalid_header() {
+        assert_eq!(
+            parse("bfy: features=nrv,abc"),
+            Err("Unknown feature in the header: abc".to_string())
+        );
+        assert_eq!(
+            parse("bfy: array-size=big"),
+            Err("Invalid array size in the header: big".to_string())
+        );
+        assert_eq!(
+            parse("bfy: jit"),
+            Err("Unknown option in the header: jit".to_string())
+        );
+    }
+}
@@ -0,0 +1,510 @@
+use crate::arguments::Feature;
+use crate::bf_interpreter::cell::Cell;
+use crate::bf_interpreter::interpreter::{to_bf_commands, BfCommand};
+use crate::lang::parser::{BinaryOp, Expr, PrintItem, Stmt};
+use crate::lang::LangError;
+use crate::tools::diagnostic::Position;
+use crate::tools::generator;
+use std::collections::HashMap;
+
+// The tape: the variables are on the first cells, and the temporary cells are allocated after them like a stack.
+// Every loop ends on the cell it starts on, so the pointer position is known while generating the code.
+// The operations never move a value out of bounds by themselves, only `+`, `-` and `*` can overflow like brainfuck
+
+/// Generate the brainfuck commands of the program
+/// # Arguments
+/// * `program` - The parsed statements
+/// * `features` - The extra features to follow, the cell size and the wrapping of the values
+pub fn generate(program: &[Stmt], features: &[Feature]) -> Result<Vec<BfCommand>, LangError> {
+    let mut variables = Vec::new();
+    declarations(program, &mut variables);
+    let mut codegen = Codegen {
+        variables: variables
+            .into_iter()
+            .enumerate()
+            .map(|(cell, name)| (name, cell))
+            .collect(),
+        declared: Vec::new(),
+        top: 0,
+        pointer: 0,
+        blocks: vec![Vec::new()],
+        features: features.to_vec(),
+        max_value: Cell::default_cell(features).max_value() as i64,
+    };
+    codegen.top = codegen.variables.len();
+    codegen.statements(program)?;
+    Ok(codegen.blocks.pop().unwrap_or_default())
+}
+
+/// Collect the names of the declared variables, in the order of the declarations
+fn declarations(statements: &[Stmt], variables: &mut Vec<String>) {
+    for statement in statements {
+        match statement {
+            Stmt::Var { name, .. } if !variables.contains(name) => variables.push(name.clone()),
+            Stmt::If {
+                then, otherwise, ..
+            } => {
+                declarations(then, variables);
+                declarations(otherwise, variables);
+            }
+            Stmt::While { body, .. } => declarations(body, variables),
+            _ => {}
+        }
+    }
+}
+
+struct Codegen {
+    /// The cells of the variables
+    variables: HashMap<String, usize>,
+    /// The variables declared so far, a variable can't be used before its declaration
+    declared: Vec<String>,
+    /// The first free cell, all the cells from it are zero
+    top: usize,
+    pointer: usize,
+    /// The commands of the loops that are being generated, the last one is the innermost
+    blocks: Vec<Vec<BfCommand>>,
+    features: Vec<Feature>,
+    max_value: i64,
+}
+
+impl Codegen {
+    fn statements(&mut self, statements: &[Stmt]) -> Result<(), LangError> {
+        for statement in statements {
+            self.statement(statement)?;
+        }
+        Ok(())
+    }
+
+    fn statement(&mut self, statement: &Stmt) -> Result<(), LangError> {
+        match statement {
+            Stmt::Var {
+                name,
+                value,
+                position,
+            } => {
+                if self.declared.contains(name) {
+                    return Err(LangError::new(
+                        format!("The variable `{}` is already declared", name),
+                        *position,
+                    ));
+                }
+                self.declared.push(name.clone());
+                let cell = self.variables[name];
+                match value {
+                    Some(value) => self.assign(cell, value)?,
+                    None => self.clear(cell),
+                }
+            }
+            Stmt::Assign {
+                name,
+                value,
+                position,
+            } => {
+                let cell = self.variable(name, *position)?;
+                self.assign(cell, value)?;
+            }
+            Stmt::If {
+                condition,
+                then,
+                otherwise,
+            } => {
+                let condition = self.expr(condition)?;
+                if otherwise.is_empty() {
+                    self.loop_on(condition, |codegen| {
+                        codegen.clear(condition);
+                        codegen.statements(then)
+                    })?;
+                } else {
+                    let is_else = self.alloc();
+                    self.add(is_else, 1);
+                    self.loop_on(condition, |codegen| {
+                        codegen.clear(condition);
+                        codegen.add(is_else, -1);
+                        codegen.statements(then)
+                    })?;
+                    self.loop_on(is_else, |codegen| {
+                        codegen.add(is_else, -1);
+                        codegen.statements(otherwise)
+                    })?;
+                    self.free(is_else);
+                }
+                self.free(condition);
+            }
+            Stmt::While { condition, body } => {
+                let cell = self.expr(condition)?;
+                self.loop_on(cell, |codegen| {
+                    codegen.clear(cell);
+                    codegen.statements(body)?;
+                    let next = codegen.expr(condition)?;
+                    codegen.move_to(next, &[cell]);
+                    codegen.free(next);
+                    Ok(())
+                })?;
+                self.free(cell);
+            }
+            Stmt::Print(items) => {
+                for item in items {
+                    match item {
+                        PrintItem::Expr(expr) => {
+                            let cell = self.expr(expr)?;
+                            self.go(cell);
+                            self.push(BfCommand::Print);
+                            self.clear(cell);
+                            self.free(cell);
+                        }
+                        PrintItem::Text(text, position) => {
+                            let code = generator::generate(text, &self.features, false)
+                                .map_err(|e| LangError::new(e, *position))?;
+                            self.text(&code);
+                        }
+                    }
+                }
+            }
+            Stmt::Read { name, position } => {
+                let cell = self.variable(name, *position)?;
+                self.go(cell);
+                self.push(BfCommand::Read);
+            }
+        }
+        Ok(())
+    }
+
+    fn variable(&self, name: &str, position: Position) -> Result<usize, LangError> {
+        match self.declared.iter().any(|declared| declared == name) {
+            true => Ok(self.variables[name]),
+            false => Err(LangError::new(
+                format!("The variable `{}` is not declared", name),
+                position,
+            )),
+        }
+    }
+
+    fn assign(&mut self, cell: usize, value: &Expr) -> Result<(), LangError> {
+        let value = self.expr(value)?;
+        self.clear(cell);
+        self.move_to(value, &[cell]);
+        self.free(value);
+        Ok(())
+    }
+
+    /// Evaluate the expression to a new temporary cell
+    fn expr(&mut self, expr: &Expr) -> Result<usize, LangError> {
+        match expr {
+            Expr::Number(number, position) => {
+                if *number > self.max_value {
+                    return Err(LangError::new(
+                        format!(
+                            "The number {} is bigger than the cell maximum {}",
+                            number, self.max_value
+                        ),
+                        *position,
+                    ));
+                }
+                let cell = self.alloc();
+                self.go(cell);
+                self.code(&generator::constant(*number, &self.features));
+                Ok(cell)
+            }
+            Expr::Var(name, position) => {
+                let variable = self.variable(name, *position)?;
+                let cell = self.alloc();
+                self.copy(variable, cell);
+                Ok(cell)
+            }
+            Expr::Not(expr) => {
+                let cell = self.expr(expr)?;
+                self.not(cell);
+                Ok(cell)
+            }
+            Expr::Binary(op, left, right) => {
+                let left = self.expr(left)?;
+                let right = self.expr(right)?;
+                self.binary(*op, left, right);
+                Ok(left)
+            }
+        }
+    }
+
+    /// Apply the operator to the two top temporary cells, the result is in the left cell and the right cell is freed
+    fn binary(&mut self, op: BinaryOp, left: usize, right: usize) {
+        match op {
+            BinaryOp::Add => self.move_to(right, &[left]),
+            BinaryOp::Sub => self
+                .loop_on(right, |codegen| {
+                    codegen.add(right, -1);
+                    codegen.add(left, -1);
+                    Ok(())
+                })
+                .unwrap(),
+            BinaryOp::Mul => {
+                let product = self.alloc();
+                self.loop_on(left, |codegen| {
+                    codegen.add(left, -1);
+                    codegen.copy(right, product);
+                    Ok(())
+                })
+                .unwrap();
+                self.clear(right);
+                self.move_to(product, &[left]);
+                self.free(product);
+            }
+            BinaryOp::Div | BinaryOp::Mod => {
+                let quotient = self.alloc();
+                let condition = self.alloc();
+                self.divisible(left, right, condition);
+                self.loop_on(condition, |codegen| {
+                    codegen.add(condition, -1);
+                    let divisor = codegen.alloc();
+                    codegen.copy(right, divisor);
+                    codegen.binary(BinaryOp::Sub, left, divisor);
+                    codegen.add(quotient, 1);
+                    codegen.divisible(left, right, condition);
+                    Ok(())
+                })
+                .unwrap();
+                self.free(condition);
+                if op == BinaryOp::Div {
+                    self.clear(left);
+                    self.move_to(quotient, &[left]);
+                } else {
+                    self.clear(quotient);
+                }
+                self.free(quotient);
+                self.clear(right);
+            }
+            BinaryOp::Eq | BinaryOp::Ne => {
+                let difference = self.saturating_sub(left, right);
+                let other_difference = self.saturating_sub(right, left);
+                self.move_to(other_difference, &[difference]);
+                self.free(other_difference);
+                match op {
+                    BinaryOp::Eq => self.not(difference),
+                    _ => self.boolean(difference),
+                }
+                self.replace(left, right, difference);
+            }
+            BinaryOp::Lt | BinaryOp::Gt | BinaryOp::Le | BinaryOp::Ge => {
+                // `left > right` when `left - right` is positive, `left <= right` when it is zero
+                let difference = match op {
+                    BinaryOp::Gt | BinaryOp::Le => self.saturating_sub(left, right),
+                    _ => self.saturating_sub(right, left),
+                };
+                match op {
+                    BinaryOp::Lt | BinaryOp::Gt => self.boolean(difference),
+                    _ => self.not(difference),
+                }
+                self.replace(left, right, difference);
+            }
+            BinaryOp::And => {
+                self.boolean(left);
+                let result = self.alloc();
+                self.loop_on(left, |codegen| {
+                    codegen.add(left, -1);
+                    codegen.loop_on(right, |codegen| {
+                        codegen.clear(right);
+                        codegen.add(result, 1);
+                        Ok(())
+                    })
+                })
+                .unwrap();
+                self.clear(right);
+                self.move_to(result, &[left]);
+                self.free(result);
+            }
+            BinaryOp::Or => {
+                self.boolean(left);
+                self.boolean(right);
+                self.move_to(right, &[left]);
+                self.boolean(left);
+            }
+        }
+        self.free(right);
+    }
+
+    /// Set the condition cell to 1 if the right cell isn't zero and the left cell is at least the right cell
+    fn divisible(&mut self, left: usize, right: usize, condition: usize) {
+        let is_divisor = self.alloc();
+        self.copy(right, is_divisor);
+        let is_bigger = self.saturating_sub(right, left);
+        self.not(is_bigger);
+        self.binary(BinaryOp::And, is_divisor, is_bigger);
+        self.move_to(is_divisor, &[condition]);
+        self.free(is_divisor);
+    }
+
+    /// The difference `left - right` or zero in a new temporary cell, the cells are kept
+    fn saturating_sub(&mut self, left: usize, right: usize) -> usize {
+        let difference = self.alloc();
+        self.copy(left, difference);
+        let counter = self.alloc();
+        self.copy(right, counter);
+        self.loop_on(counter, |codegen| {
+            codegen.add(counter, -1);
+            let is_positive = codegen.alloc();
+            codegen.copy(difference, is_positive);
+            codegen.loop_on(is_positive, |codegen| {
+                codegen.clear(is_positive);
+                codegen.add(difference, -1);
+                Ok(())
+            })?;
+            codegen.free(is_positive);
+            Ok(())
+        })
+        .unwrap();
+        self.free(counter);
+        difference
+    }
+
+    /// Clear the operands and move the result (the top cell) to the left operand
+    fn replace(&mut self, left: usize, right: usize, result: usize) {
+        self.clear(left);
+        self.clear(right);
+        self.move_to(result, &[left]);
+        self.free(result);
+    }
+
+    /// Set the cell to 1 if it isn't zero
+    fn boolean(&mut self, cell: usize) {
+        let result = self.alloc();
+        self.loop_on(cell, |codegen| {
+            codegen.clear(cell);
+            codegen.add(result, 1);
+            Ok(())
+        })
+        .unwrap();
+        self.move_to(result, &[cell]);
+        self.free(result);
+    }
+
+    /// Set the cell to 1 if it is zero, otherwise to 0
+    fn not(&mut self, cell: usize) {
+        let result = self.alloc();
+        self.add(result, 1);
+        self.loop_on(cell, |codegen| {
+            codegen.clear(cell);
+            codegen.add(result, -1);
+            Ok(())
+        })
+        .unwrap();
+        self.move_to(result, &[cell]);
+        self.free(result);
+    }
+
+    /// Add the source cell to the target cells, the source cell becomes zero
+    fn move_to(&mut self, source: usize, targets: &[usize]) {
+        self.loop_on(source, |codegen| {
+            codegen.add(source, -1);
+            for target in targets {
+                codegen.add(*target, 1);
+            }
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    /// Add the source cell to the target cell, the source cell is kept
+    fn copy(&mut self, source: usize, target: usize) {
+        let temporary = self.alloc();
+        self.move_to(source, &[target, temporary]);
+        self.move_to(temporary, &[source]);
+        self.free(temporary);
+    }
+
+    fn clear(&mut self, cell: usize) {
+        self.loop_on(cell, |codegen| {
+            codegen.add(cell, -1);
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    /// Print the text with the generator code on the free cells, then clear them
+    fn text(&mut self, code: &str) {
+        let start = self.top;
+        self.go(start);
+        let bf_commands = to_bf_commands(code.chars().collect()).unwrap_or_default();
+        let (end, used) = extent(&bf_commands, 0, 0);
+        self.blocks.last_mut().unwrap().extend(bf_commands);
+        self.pointer = start + end;
+        for cell in (start..=start + used).rev() {
+            self.clear(cell);
+        }
+    }
+
+    /// Append the code that returns to the cell it starts on
+    fn code(&mut self, code: &str) {
+        let bf_commands = to_bf_commands(code.chars().collect()).unwrap_or_default();
+        self.blocks.last_mut().unwrap().extend(bf_commands);
+    }
+
+    /// Generate a loop on the cell, the body must keep the cells below the top as they are allocated
+    fn loop_on(
+        &mut self,
+        cell: usize,
+        body: impl FnOnce(&mut Self) -> Result<(), LangError>,
+    ) -> Result<(), LangError> {
+        self.go(cell);
+        self.blocks.push(Vec::new());
+        body(self)?;
+        self.go(cell);
+        let loop_body = self.blocks.pop().unwrap();
+        self.push(BfCommand::Loop(loop_body));
+        Ok(())
+    }
+
+    fn add(&mut self, cell: usize, value: i64) {
+        self.go(cell);
+        let command = if value > 0 {
+            BfCommand::IncVal
+        } else {
+            BfCommand::DecVal
+        };
+        for _ in 0..value.abs() {
+            self.push(command.clone());
+        }
+    }
+
+    fn go(&mut self, cell: usize) {
+        while self.pointer < cell {
+            self.push(BfCommand::IncPtr);
+            self.pointer += 1;
+        }
+        while self.pointer > cell {
+            self.push(BfCommand::DecPtr);
+            self.pointer -= 1;
+        }
+    }
+
+    fn push(&mut self, command: BfCommand) {
+        self.blocks.last_mut().unwrap().push(command);
+    }
+
+    fn alloc(&mut self) -> usize {
+        self.top += 1;
+        self.top - 1
+    }
+
+    /// Free the top cell, it must be zero
+    fn free(&mut self, cell: usize) {
+        debug_assert_eq!(
+            cell,
+            self.top - 1,
+            "the cells are freed in the reverse order"
+        );
+        self.top -= 1;
+    }
+}
+
+/// The pointer offset at the end of the commands and the biggest offset they reach, the loops must be balanced
+fn extent(bf_commands: &[BfCommand], mut offset: usize, mut used: usize) -> (usize, usize) {
+    for command in bf_commands {
+        match command {
+            BfCommand::IncPtr => offset += 1,
+            BfCommand::DecPtr => offset -= 1,
+            BfCommand::Loop(loop_body) => used = used.max(extent(loop_body, offset, used).1),
+            _ => {}
+        }
+        used = used.max(offset);
+    }
+    (offset, used)
+}
@@ -0,0 +1,163 @@
+use crate::lang::LangError;
+use crate::tools::diagnostic::Position;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum TokenKind {
+    Ident(String),
+    /// A number or a char literal (e.g. `'A'`)
+    Number(i64),
+    Str(String),
+    Var,
+    If,
+    Else,
+    While,
+    Print,
+    Read,
+    /// An operator or a punctuation (e.g. `+`, `==`, `{`)
+    Symbol(&'static str),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub position: Position,
+}
+
+/// The symbols, the longer ones first
+const SYMBOLS: [&str; 21] = [
+    "==", "!=", "<=", ">=", "&&", "||", "+", "-", "*", "/", "%", "<", ">", "!", "=", "(", ")", "{",
+    "}", ";", ",",
+];
+
+/// Split the source code to the tokens, the comments start with `//` and end at the end of the line
+pub fn tokens(source: &str) -> Result<Vec<Token>, LangError> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut index = 0;
+    let mut position = Position { line: 1, column: 1 };
+    let advance = |index: &mut usize, position: &mut Position, count: usize| {
+        for c in &chars[*index..*index + count] {
+            if *c == '\n' {
+                position.line += 1;
+                position.column = 1;
+            } else {
+                position.column += 1;
+            }
+        }
+        *index += count;
+    };
+
+    while index < chars.len() {
+        let c = chars[index];
+        let start = position;
+        if c.is_whitespace() {
+            advance(&mut index, &mut position, 1);
+            continue;
+        }
+        if chars[index..].starts_with(&['/', '/']) {
+            let length = chars[index..]
+                .iter()
+                .position(|c| *c == '\n')
+                .unwrap_or(chars.len() - index);
+            advance(&mut index, &mut position, length);
+            continue;
+        }
+
+        let (kind, length) = if c.is_ascii_alphabetic() || c == '_' {
+            let length = chars[index..]
+                .iter()
+                .position(|c| !c.is_ascii_alphanumeric() && *c != '_')
+                .unwrap_or(chars.len() - index);
+            let word: String = chars[index..index + length].iter().collect();
+            let kind = match word.as_str() {
+                "var" => TokenKind::Var,
+                "if" => TokenKind::If,
+                "else" => TokenKind::Else,
+                "while" => TokenKind::While,
+                "print" => TokenKind::Print,
+                "read" => TokenKind::Read,
+                _ => TokenKind::Ident(word),
+            };
+            (kind, length)
+        } else if c.is_ascii_digit() {
+            let length = chars[index..]
+                .iter()
+                .position(|c| !c.is_ascii_digit())
+                .unwrap_or(chars.len() - index);
+            let number: String = chars[index..index + length].iter().collect();
+            match number.parse() {
+                Ok(number) => (TokenKind::Number(number), length),
+                Err(_) => {
+                    return Err(LangError::new(
+                        format!("The number {} is too big", number),
+                        start,
+                    ))
+                }
+            }
+        } else if c == '"' || c == '\'' {
+            let (text, length) = literal(&chars[index..], start)?;
+            if c == '"' {
+                (TokenKind::Str(text), length)
+            } else {
+                let mut text_chars = text.chars();
+                match (text_chars.next(), text_chars.next()) {
+                    (Some(c), None) => (TokenKind::Number(c as i64), length),
+                    _ => {
+                        return Err(LangError::new(
+                            "A char literal must have one char".to_string(),
+                            start,
+                        ))
+                    }
+                }
+            }
+        } else if let Some(symbol) = SYMBOLS
+            .iter()
+            .find(|symbol| chars[index..].starts_with(&symbol.chars().collect::<Vec<_>>()))
+        {
+            (TokenKind::Symbol(symbol), symbol.len())
+        } else {
+            return Err(LangError::new(format!("Unexpected char {:?}", c), start));
+        };
+        tokens.push(Token {
+            kind,
+            position: start,
+        });
+        advance(&mut index, &mut position, length);
+    }
+    Ok(tokens)
+}
+
+/// Parse the string or char literal at the start of the chars, with the escapes `\n`, `\t`, `\\`, `\"`, `\'` and `\0`
+fn literal(chars: &[char], start: Position) -> Result<(String, usize), LangError> {
+    let quote = chars[0];
+    let mut text = String::new();
+    let mut index = 1;
+    while let Some(c) = chars.get(index) {
+        match c {
+            '\n' => break,
+            '\\' => {
+                let escaped = match chars.get(index + 1) {
+                    Some('n') => '\n',
+                    Some('t') => '\t',
+                    Some('0') => '\0',
+                    Some(c @ ('\\' | '"' | '\'')) => *c,
+                    _ => {
+                        return Err(LangError::new(
+                            "Unknown escape, use `\\n`, `\\t`, `\\0`, `\\\\`, `\\\"` or `\\'`"
+                                .to_string(),
+                            start,
+                        ))
+                    }
+                };
+                text.push(escaped);
+                index += 2;
+            }
+            _ if *c == quote => return Ok((text, index + 1)),
+            _ => {
+                text.push(*c);
+                index += 1;
+            }
+        }
+    }
+    Err(LangError::new(format!("Unclosed {}", quote), start))
+}
@@ -0,0 +1,150 @@
+pub mod codegen;
+pub mod lexer;
+pub mod parser;
+
+use crate::arguments::Feature;
+use crate::bf_interpreter::interpreter::to_bf_code;
+use crate::tools::diagnostic::Position;
+use crate::tools::formatter;
+use crate::utils;
+use std::fmt::{Display, Formatter};
+
+// The bfy language, a tiny structured language that compiles to brainfuck:
+// var x = 'a';               declare a variable, every variable is one cell
+// x = (x + 1) * 2 % 26;      assign, with `+ - * / %`, `== != < > <= >=`, `&& || !` and the parentheses
+// if x > 3 { } else { }      run when the condition isn't zero
+// while x != 0 { }
+// print x, "text\n";         print the values as chars and the strings
+// read x;                    read a char to the variable
+
+#[derive(Debug, PartialEq)]
+pub struct LangError {
+    pub message: String,
+    pub position: Position,
+}
+
+impl LangError {
+    pub fn new(message: String, position: Position) -> Self {
+        Self { message, position }
+    }
+}
+
+impl Display for LangError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "{}: {}", self.position, self.message)
+    }
+}
+
+/// Compile the program of the bfy language to brainfuck code
+/// # Arguments
+/// * `source` - The program source code
+/// * `features` - The extra features the brainfuck code will run with
+pub fn compile(source: &str, features: &[Feature]) -> Result<String, LangError> {
+    let tokens = lexer::tokens(source)?;
+    let program = parser::parse(&tokens)?;
+    Ok(to_bf_code(&codegen::generate(&program, features)?))
+}
+
+/// Compile the program file of the bfy language to brainfuck
+/// # Arguments
+/// * `source` - The program source code file
+/// * `output` - The file to write the brainfuck code to, if `None` it will be printed to stdout
+/// * `features` - The extra features the brainfuck code will run with
+/// * `pretty` - Format the brainfuck code, otherwise it is on one line
+pub fn build_file(source: &String, output: Option<&String>, features: &[Feature], pretty: bool) {
+    info!(
+        "Compiling the bfy language source code from file: {}",
+        source
+    );
+    match compile(&utils::read_source_file(source), features) {
+        Ok(mut code) => {
+            code = match pretty {
+                true => formatter::format(&code),
+                false => code + "\n",
+            };
+            utils::write_output(code.as_bytes(), output);
+        }
+        Err(e) => {
+            error!(
+                "Failed to compile the bfy language source code: {}:{}",
+                source, e
+            );
+            eprintln!(
+                "Failed to compile the bfy language source code: {}:{}",
+                source, e
+            );
+            std::process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arguments::DEFAULT_ARRAY_SIZE;
+    use crate::bf_interpreter::interpreter::Interpreter;
+    use console::Term;
+    use pretty_assertions::assert_eq;
+
+    fn run(source: &str, features: &[Feature], input: &str) -> String {
+        let mut interpreter =
+            Interpreter::new(DEFAULT_ARRAY_SIZE, features.to_vec(), Term::stdout());
+        interpreter.input = Some(input.chars().collect());
+        interpreter.output = Some(Vec::new());
+        assert_eq!(interpreter.run(compile(source, features).unwrap()), Ok(0));
+        String::from_utf8(interpreter.output.unwrap()).unwrap()
+    }
+
+    #[test]
+    fn arithmetic_and_comparisons() {
+        let source = "var a = 17; var b = 5;\n\
+            print 'A' + a / b, 'A' + a % b, 'A' + a * b - 70, 'A' + (a - b) * 2;\n\
+            print '0' + (a < b), '0' + (a > b), '0' + (a <= 17), '0' + (b >= 6);\n\
+            print '0' + (a == 17), '0' + (a != 17), '0' + (a > 1 && b > 1), '0' + (!a || b), \"\\n\";";
+        for features in [
+            vec![],
+            vec![Feature::NoReverseValue],
+            vec![Feature::AllowUtf8],
+        ] {
+            assert_eq!(run(source, &features, ""), "DCPY01101011\n");
+        }
+    }
+
+    #[test]
+    fn control_flow_and_input() {
+        let source = "var i = 0;\n\
+            while i < 5 {\n\
+                if i % 2 == 0 { print '0' + i; } else if i == 3 { print \"three\"; } else { print '-'; }\n\
+                i = i + 1;\n\
+            }\n\
+            var c; read c;\n\
+            while c != '.' { print c - 32; read c; }\n\
+            print \"🦀\";";
+        assert_eq!(run(source, &[Feature::AllowUtf8], "hi."), "0-2three4HI🦀");
+    }
+
+    #[test]
+    fn errors() {
+        let error = |source: &str| compile(source, &[]).unwrap_err().to_string();
+        assert_eq!(
+            error("var x = 1;\nx = y;"),
+            "2:5: The variable `y` is not declared"
+        );
+        assert_eq!(
+            error("var x = 256;"),
+            "1:9: The number 256 is bigger than the cell maximum 255"
+        );
+        assert_eq!(
+            error("print \"🦀\";"),
+            "1:7: The character '🦀' is above 255, enable the `utf8` feature to print it"
+        );
+        assert_eq!(
+            error("if 1 { print 1 }"),
+            "1:16: Expected `;` but found `}`"
+        );
+        assert_eq!(
+            error("var x; var x;"),
+            "1:12: The variable `x` is already declared"
+        );
+    }
+}
@@ -0,0 +1,293 @@
+use crate::lang::lexer::{Token, TokenKind};
+use crate::lang::LangError;
+use crate::tools::diagnostic::Position;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum BinaryOp {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Expr {
+    Number(i64, Position),
+    Var(String, Position),
+    Not(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum PrintItem {
+    /// Print the value of the expression as a char
+    Expr(Expr),
+    Text(String, Position),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Stmt {
+    /// Declare a variable, it is zero without a value
+    Var {
+        name: String,
+        value: Option<Expr>,
+        position: Position,
+    },
+    Assign {
+        name: String,
+        value: Expr,
+        position: Position,
+    },
+    If {
+        condition: Expr,
+        then: Vec<Stmt>,
+        otherwise: Vec<Stmt>,
+    },
+    While {
+        condition: Expr,
+        body: Vec<Stmt>,
+    },
+    Print(Vec<PrintItem>),
+    /// Read a char to the variable
+    Read {
+        name: String,
+        position: Position,
+    },
+}
+
+/// The binary operators by their precedence, the lowest first
+const PRECEDENCE: [&[(&str, BinaryOp)]; 5] = [
+    &[("||", BinaryOp::Or)],
+    &[("&&", BinaryOp::And)],
+    &[
+        ("==", BinaryOp::Eq),
+        ("!=", BinaryOp::Ne),
+        ("<=", BinaryOp::Le),
+        (">=", BinaryOp::Ge),
+        ("<", BinaryOp::Lt),
+        (">", BinaryOp::Gt),
+    ],
+    &[("+", BinaryOp::Add), ("-", BinaryOp::Sub)],
+    &[
+        ("*", BinaryOp::Mul),
+        ("/", BinaryOp::Div),
+        ("%", BinaryOp::Mod),
+    ],
+];
+
+/// Parse the tokens to the statements of the program
+pub fn parse(tokens: &[Token]) -> Result<Vec<Stmt>, LangError> {
+    let mut parser = Parser { tokens, index: 0 };
+    let mut program = Vec::new();
+    while parser.index < tokens.len() {
+        program.push(parser.statement()?);
+    }
+    Ok(program)
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    index: usize,
+}
+
+impl Parser<'_> {
+    fn statement(&mut self) -> Result<Stmt, LangError> {
+        let token = self.next()?;
+        let statement = match token.kind {
+            TokenKind::Var => {
+                let (name, position) = self.ident()?;
+                let value = match self.eat("=") {
+                    true => Some(self.expr(0)?),
+                    false => None,
+                };
+                Stmt::Var {
+                    name,
+                    value,
+                    position,
+                }
+            }
+            TokenKind::Ident(name) => {
+                self.expect("=")?;
+                Stmt::Assign {
+                    name,
+                    value: self.expr(0)?,
+                    position: token.position,
+                }
+            }
+            TokenKind::If => {
+                let condition = self.expr(0)?;
+                let then = self.block()?;
+                let otherwise = match self.peek().map(|token| &token.kind) {
+                    Some(TokenKind::Else) => {
+                        self.index += 1;
+                        match self.peek().map(|token| &token.kind) {
+                            Some(TokenKind::If) => vec![self.statement()?],
+                            _ => self.block()?,
+                        }
+                    }
+                    _ => Vec::new(),
+                };
+                return Ok(Stmt::If {
+                    condition,
+                    then,
+                    otherwise,
+                });
+            }
+            TokenKind::While => {
+                return Ok(Stmt::While {
+                    condition: self.expr(0)?,
+                    body: self.block()?,
+                })
+            }
+            TokenKind::Print => {
+                let mut items = Vec::new();
+                loop {
+                    match self.peek() {
+                        Some(Token {
+                            kind: TokenKind::Str(text),
+                            position,
+                        }) => {
+                            items.push(PrintItem::Text(text.clone(), *position));
+                            self.index += 1;
+                        }
+                        _ => items.push(PrintItem::Expr(self.expr(0)?)),
+                    }
+                    if !self.eat(",") {
+                        break;
+                    }
+                }
+                Stmt::Print(items)
+            }
+            TokenKind::Read => {
+                let (name, position) = self.ident()?;
+                Stmt::Read { name, position }
+            }
+            _ => return Err(unexpected(&token)),
+        };
+        self.expect(";")?;
+        Ok(statement)
+    }
+
+    fn block(&mut self) -> Result<Vec<Stmt>, LangError> {
+        self.expect("{")?;
+        let mut statements = Vec::new();
+        while !self.eat("}") {
+            statements.push(self.statement()?);
+        }
+        Ok(statements)
+    }
+
+    /// Parse the binary operators from the precedence level, then the unary operators and the primary expressions
+    fn expr(&mut self, level: usize) -> Result<Expr, LangError> {
+        let Some(operators) = PRECEDENCE.get(level) else {
+            return self.unary();
+        };
+        let mut left = self.expr(level + 1)?;
+        'operators: loop {
+            for (symbol, op) in operators.iter() {
+                if self.eat(symbol) {
+                    let right = self.expr(level + 1)?;
+                    left = Expr::Binary(*op, Box::new(left), Box::new(right));
+                    continue 'operators;
+                }
+            }
+            return Ok(left);
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, LangError> {
+        if self.eat("!") {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        let token = self.next()?;
+        match token.kind {
+            TokenKind::Number(number) => Ok(Expr::Number(number, token.position)),
+            TokenKind::Ident(name) => Ok(Expr::Var(name, token.position)),
+            TokenKind::Symbol("(") => {
+                let expr = self.expr(0)?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            _ => Err(unexpected(&token)),
+        }
+    }
+
+    fn ident(&mut self) -> Result<(String, Position), LangError> {
+        let token = self.next()?;
+        match token.kind {
+            TokenKind::Ident(name) => Ok((name, token.position)),
+            _ => Err(unexpected(&token)),
+        }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.index)
+    }
+
+    fn next(&mut self) -> Result<Token, LangError> {
+        match self.tokens.get(self.index) {
+            Some(token) => {
+                self.index += 1;
+                Ok(token.clone())
+            }
+            None => Err(LangError::new(
+                "Unexpected end of the file".to_string(),
+                self.tokens
+                    .last()
+                    .map_or(Position { line: 1, column: 1 }, |token| token.position),
+            )),
+        }
+    }
+
+    /// Skip the symbol if it is the next token
+    fn eat(&mut self, symbol: &str) -> bool {
+        let found =
+            matches!(self.peek(), Some(Token { kind: TokenKind::Symbol(s), .. }) if *s == symbol);
+        if found {
+            self.index += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<(), LangError> {
+        if self.eat(symbol) {
+            return Ok(());
+        }
+        let token = self.next()?;
+        Err(LangError::new(
+            format!("Expected `{}` but found {}", symbol, describe(&token.kind)),
+            token.position,
+        ))
+    }
+}
+
+fn unexpected(token: &Token) -> LangError {
+    LangError::new(
+        format!("Unexpected {}", describe(&token.kind)),
+        token.position,
+    )
+}
+
+fn describe(kind: &TokenKind) -> String {
+    match kind {
+        TokenKind::Ident(name) => format!("`{}`", name),
+        TokenKind::Number(number) => format!("the number {}", number),
+        TokenKind::Str(_) => "a string".to_string(),
+        TokenKind::Var => "`var`".to_string(),
+        TokenKind::If => "`if`".to_string(),
+        TokenKind::Else => "`else`".to_string(),
+        TokenKind::While => "`while`".to_string(),
+        TokenKind::Print => "`print`".to_string(),
+        TokenKind::Read => "`read`".to_string(),
+        TokenKind::Symbol(symbol) => format!("`{}`", symbol),
+    }
+}
@@ -1,6 +1,13 @@
 mod arguments;
 mod bf_interpreter;
+mod compiler;
+mod config;
+mod dialects;
+mod header;
+mod lang;
+mod preprocessor;
 mod repl;
+mod tools;
 mod utils;
 
 use clap::Parser;
@@ -9,33 +16,219 @@ extern crate pretty_env_logger;
 extern crate log;
 use colored::Colorize;
 
-use arguments::Args;
+use arguments::{Args, Command, DEFAULT_ARRAY_SIZE};
 use bf_interpreter::interpreter::Interpreter;
+use dialects::Dialect;
 
 fn main() {
     pretty_env_logger::init();
     info!("Initialized logger");
     info!("Parsing command line arguments");
-    let args = Args::parse();
+    let mut args = Args::parse();
     info!("Parsed command line arguments: {:?}", args);
+    let config = utils::read_config();
+    if !config.color {
+        colored::control::set_override(false);
+    }
+
+    if let Some(command) = args.command.take() {
+        match command {
+            Command::Decompile { source, output } => {
+                let header = utils::read_header(&source);
+                compiler::decompile_file(
+                    &source,
+                    output.as_ref(),
+                    header
+                        .features
+                        .or(config.features.clone())
+                        .as_deref()
+                        .unwrap_or_default(),
+                )
+            }
+            Command::Fmt {
+                source,
+                output,
+                check,
+            } => tools::format_file(&source, output.as_ref(), check),
+            Command::Minify {
+                source,
+                features,
+                array_size,
+                output,
+            } => {
+                let header = utils::read_header(&source);
+                tools::minify_file(
+                    &source,
+                    output.as_ref(),
+                    array_size
+                        .or(header.array_size)
+                        .or(config.array_size)
+                        .unwrap_or(DEFAULT_ARRAY_SIZE),
+                    features
+                        .or(header.features)
+                        .or(config.features.clone())
+                        .as_deref()
+                        .unwrap_or_default(),
+                )
+            }
+            Command::Lint {
+                source,
+                features,
+                allow,
+            } => {
+                let header = utils::read_header(&source);
+                tools::lint_file(
+                    &source,
+                    allow.as_deref().unwrap_or_default(),
+                    features
+                        .or(header.features)
+                        .or(config.features.clone())
+                        .as_deref()
+                        .unwrap_or_default(),
+                )
+            }
+            Command::Analyze {
+                source,
+                features,
+                array_size,
+            } => {
+                let header = utils::read_header(&source);
+                tools::analyze_file(
+                    &source,
+                    array_size
+                        .or(header.array_size)
+                        .or(config.array_size)
+                        .unwrap_or(DEFAULT_ARRAY_SIZE),
+                    features
+                        .or(header.features)
+                        .or(config.features.clone())
+                        .as_deref()
+                        .unwrap_or_default(),
+                )
+            }
+            Command::Gen {
+                text,
+                features,
+                optimize,
+                output,
+            } => tools::generate_text(
+                &text,
+                output.as_ref(),
+                features.or(config.features).as_deref().unwrap_or_default(),
+                optimize,
+            ),
+            Command::Build {
+                source,
+                features,
+                pretty,
+                output,
+            } => lang::build_file(
+                &source,
+                output.as_ref(),
+                features.or(config.features).as_deref().unwrap_or_default(),
+                pretty,
+            ),
+            Command::Convert {
+                source,
+                to,
+                to_dialect,
+                lang,
+                dialect,
+                output,
+            } => {
+                let dialect = match dialect {
+                    Some(file) => utils::read_dialect(&file),
+                    None => lang.unwrap_or_else(|| dialects::detect(&source)).into(),
+                };
+                let to = match to_dialect {
+                    Some(file) => utils::read_dialect(&file),
+                    None => to.unwrap_or_default().into(),
+                };
+                dialects::convert_file(&source, &dialect, &to, output.as_ref())
+            }
+        }
+        return;
+    }
+
+    if let Some(source) = &args.source {
+        args.apply_header(utils::read_header(source));
+    }
+    args.apply_config(&config);
+    let array_size = args.array_size.unwrap_or(DEFAULT_ARRAY_SIZE);
+    let dialect = match &args.dialect {
+        Some(file) => utils::read_dialect(file),
+        None => args
+            .lang
+            .or_else(|| args.source.as_deref().map(dialects::detect))
+            .unwrap_or_default()
+            .into(),
+    };
+
+    if let (Some(target), Some(source)) = (args.target, &args.source) {
+        compiler::compile_file(
+            source,
+            &dialect,
+            target,
+            args.output.as_ref(),
+            array_size,
+            args.features.as_deref().unwrap_or_default(),
+            args.eof.unwrap_or_default(),
+        );
+        return;
+    }
 
     info!("Initializing interpreter");
     let mut interpreter = Interpreter::new(
-        args.array_size,
-        args.features.unwrap_or_else(|| vec![]),
+        array_size,
+        args.features.unwrap_or_default(),
         console::Term::stdout(),
     );
+    interpreter.eof = args.eof.unwrap_or_default();
+    interpreter.files.root = args.file_root.map(std::path::PathBuf::from);
+    if let Dialect::Lang(lang) = dialect {
+        interpreter.set_lang(lang);
+    }
 
     match args.source {
         Some(source) => {
+            if args.safe
+                && !tools::check_file(
+                    &source,
+                    &dialect,
+                    array_size,
+                    interpreter.features.as_slice(),
+                )
+            {
+                error!(
+                    "Refusing to run brainfuck source code with errors: {}",
+                    source
+                );
+                eprintln!(
+                    "Refusing to run brainfuck source code with errors: {}",
+                    source
+                );
+                std::process::exit(1);
+            }
             info!("Running brainfuck source code from file: {}", source);
-            match interpreter.run(utils::read_brainfuck_code(&source)) {
+            let bf_code = match interpreter.lang.bit_tape() {
+                true => utils::read_bit_code(&source),
+                false => utils::read_code(&source, &dialect, interpreter.features.as_slice()),
+            };
+            #[cfg(feature = "jit")]
+            let result = if args.jit {
+                compiler::jit::run(&mut interpreter, bf_code)
+            } else {
+                interpreter.run(bf_code)
+            };
+            #[cfg(not(feature = "jit"))]
+            let result = interpreter.run(bf_code);
+            match result {
                 Ok(exit_code) => {
                     info!(
                         "Finished running brainfuck source code from file: {}",
                         source
                     );
-                    if !args.without_tiles {
+                    if !args.without_tiles.unwrap_or_default() {
                         println!(
                             "{}",
                             format!(
@@ -50,8 +243,8 @@ fn main() {
                             "Exiting with code: ".truecolor(33, 97, 61),
                             exit_code.to_string().bold().green()
                         );
-                        std::process::exit(exit_code);
                     }
+                    std::process::exit(exit_code);
                 }
                 Err(e) => {
                     error!("Failed to run brainfuck source code from file: {}", e);
@@ -59,6 +252,6 @@ fn main() {
                 }
             }
         }
-        None => repl::start_repl::start(interpreter),
+        None => repl::start_repl::start(interpreter, config.repl),
     }
 }
@@ -0,0 +1,668 @@
+use crate::header;
+use crate::tools::diagnostic::Position;
+use crate::utils::is_command;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+// The macro syntax, everything else is a comment like in brainfuck:
+// def name(a, b) { body }  define a macro, the parameters are optional
+// @name(x, y)              expand a macro, the arguments are expanded where the body uses `$a` and `$b`
+// include "file.bfm"       expand another file, relative to the current file
+// (body)                   group the commands
+// +*10 (>+)*3 @name*$a     repeat the command, the group, the macro or the argument
+
+/// The deepest nesting of the macros expansions, to stop the recursive macros
+const MAX_DEPTH: usize = 256;
+
+/// The most commands in the expanded code, to stop the repeats that don't fit in memory
+const MAX_COMMANDS: usize = 1 << 22;
+
+/// The brainfuck code expanded from the macros, with the location of every command in the macro source
+#[derive(Debug, PartialEq)]
+pub struct Preprocessed {
+    pub code: String,
+    pub source_map: SourceMap,
+}
+
+/// The location in the macro source of every command in the expanded code
+#[derive(Debug, PartialEq)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+    /// The file index and the char index of every command
+    locations: Vec<(usize, usize)>,
+}
+
+impl SourceMap {
+    /// The source map of the code translated from one file, e.g. from a dialect
+    /// # Arguments
+    /// * `name` - The file name
+    /// * `text` - The file content
+    /// * `locations` - The char index in the file of every command
+    pub fn new(name: &str, text: &str, locations: &[usize]) -> Self {
+        Self {
+            files: vec![SourceFile {
+                name: name.to_string(),
+                text: text.chars().collect(),
+            }],
+            locations: locations.iter().map(|index| (0, *index)).collect(),
+        }
+    }
+
+    /// The file and the position in it of the command at the index of the expanded code
+    pub fn location(&self, index: usize) -> Option<(&str, Position)> {
+        let (file, char_index) = *self.locations.get(index)?;
+        let file = &self.files[file];
+        Some((&file.name, file.position(char_index)))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct SourceFile {
+    name: String,
+    text: Vec<char>,
+}
+
+impl SourceFile {
+    fn position(&self, char_index: usize) -> Position {
+        let before = &self.text[..char_index];
+        let line_start = before
+            .iter()
+            .rposition(|c| *c == '\n')
+            .map_or(0, |index| index + 1);
+        Position {
+            line: before.iter().filter(|c| **c == '\n').count() + 1,
+            column: char_index - line_start + 1,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct PreprocessError {
+    pub message: String,
+    pub file: String,
+    pub position: Position,
+    /// The exit code, the same as the interpreter for the unmatched brackets, otherwise 1
+    pub code: i32,
+}
+
+impl Display for PreprocessError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "{}:{}: {}", self.file, self.position, self.message)
+    }
+}
+
+/// Expand the macros of the file to plain brainfuck
+/// # Arguments
+/// * `file` - The macro source file, the included files are relative to it
+pub fn expand_file(file: &str) -> Result<Preprocessed, PreprocessError> {
+    match std::fs::read_to_string(file) {
+        Ok(source) => expand(file, &source),
+        Err(e) => Err(PreprocessError {
+            message: format!("Failed to read the file: {}", e),
+            file: file.to_string(),
+            position: Position { line: 1, column: 1 },
+            code: 1,
+        }),
+    }
+}
+
+/// Expand the macros of the source code to plain brainfuck, the header of the source code is skipped
+/// # Arguments
+/// * `file` - The name of the source file, the included files are relative to it
+/// * `source` - The macro source code
+pub fn expand(file: &str, source: &str) -> Result<Preprocessed, PreprocessError> {
+    let mut expander = Expander {
+        files: Vec::new(),
+        macros: HashMap::new(),
+        commands: Vec::new(),
+        includes: Vec::new(),
+        depth: 0,
+    };
+    expander.include(file, Path::new(file).to_path_buf(), &header::strip(source))?;
+
+    let mut open_brackets = Vec::new();
+    for (index, (command, ..)) in expander.commands.iter().enumerate() {
+        match command {
+            '[' => open_brackets.push(index),
+            ']' if open_brackets.pop().is_none() => {
+                return Err(expander.command_error(index));
+            }
+            _ => {}
+        }
+    }
+    if let Some(index) = open_brackets.pop() {
+        return Err(expander.command_error(index));
+    }
+
+    Ok(Preprocessed {
+        code: expander.commands.iter().map(|(c, ..)| c).collect(),
+        source_map: SourceMap {
+            locations: expander
+                .commands
+                .iter()
+                .map(|(_, file, index)| (*file, *index))
+                .collect(),
+            files: expander.files,
+        },
+    })
+}
+
+/// A range of chars in a source file
+#[derive(Debug, Clone, Copy)]
+struct Span {
+    file: usize,
+    start: usize,
+    end: usize,
+}
+
+struct Macro {
+    params: Vec<String>,
+    body: Span,
+}
+
+/// The arguments of the macro that is being expanded, every argument is expanded in the environment of its call
+#[derive(Default)]
+struct Env {
+    args: HashMap<String, (Span, Rc<Env>)>,
+}
+
+struct Expander {
+    files: Vec<SourceFile>,
+    macros: HashMap<String, Macro>,
+    /// The expanded commands with the file index and the char index they come from
+    commands: Vec<(char, usize, usize)>,
+    /// The files that are being included, to stop the recursive includes
+    includes: Vec<PathBuf>,
+    depth: usize,
+}
+
+impl Expander {
+    fn include(&mut self, name: &str, path: PathBuf, source: &str) -> Result<(), PreprocessError> {
+        self.files.push(SourceFile {
+            name: name.to_string(),
+            text: source.chars().collect(),
+        });
+        let file = self.files.len() - 1;
+        self.includes.push(path.canonicalize().unwrap_or(path));
+        let span = Span {
+            file,
+            start: 0,
+            end: self.files[file].text.len(),
+        };
+        self.block(span, &Rc::new(Env::default()), false)?;
+        self.includes.pop();
+        Ok(())
+    }
+
+    /// Expand the span, a group stops at its `)` and returns its index
+    fn block(&mut self, span: Span, env: &Rc<Env>, group: bool) -> Result<usize, PreprocessError> {
+        let mut index = span.start;
+        while index < span.end {
+            let c = self.char(span.file, index);
+            let first_command = self.commands.len();
+            match c {
+                _ if is_command(c) => {
+                    if self.commands.len() >= MAX_COMMANDS {
+                        return Err(self.too_long(span.file, index));
+                    }
+                    self.commands.push((c, span.file, index));
+                    index += 1;
+                }
+                '(' => {
+                    let close = self.block(
+                        Span {
+                            start: index + 1,
+                            ..span
+                        },
+                        env,
+                        true,
+                    )?;
+                    if close >= span.end {
+                        return Err(self.error(span.file, index, "Unclosed `(`".to_string()));
+                    }
+                    index = close + 1;
+                }
+                ')' if group => return Ok(index),
+                ')' | '{' | '}' => {
+                    return Err(self.error(span.file, index, format!("Unexpected `{}`", c)));
+                }
+                '@' => index = self.call(span, index, env)?,
+                '$' => {
+                    let (name, end) = self.name(span.file, index + 1)?;
+                    let Some((arg, arg_env)) = env.args.get(&name) else {
+                        return Err(self.error(
+                            span.file,
+                            index,
+                            format!("Unknown parameter `{}`", name),
+                        ));
+                    };
+                    let (arg, arg_env) = (*arg, arg_env.clone());
+                    self.nested(span.file, index, &name, |expander| {
+                        expander.block(arg, &arg_env, false).map(|_| ())
+                    })?;
+                    index = end;
+                }
+                'd' if self.keyword(span.file, index, "def") => {
+                    index = self.define(span.file, index + "def".len())?;
+                    continue;
+                }
+                'i' if self.keyword(span.file, index, "include") => {
+                    index = self.include_directive(span.file, index + "include".len())?;
+                    continue;
+                }
+                _ => {
+                    index += 1;
+                    continue;
+                }
+            }
+
+            if index < span.end && self.char(span.file, index) == '*' {
+                let (count, end) = self.count(span.file, index + 1, env)?;
+                let repeated = self.commands.split_off(first_command);
+                if repeated.len().saturating_mul(count) > MAX_COMMANDS - first_command {
+                    return Err(self.too_long(span.file, index));
+                }
+                for _ in 0..count {
+                    self.commands.extend_from_slice(&repeated);
+                }
+                index = end;
+            }
+        }
+        Ok(index)
+    }
+
+    /// Expand the macro call at the `@`, and return the index after it
+    fn call(&mut self, span: Span, at: usize, env: &Rc<Env>) -> Result<usize, PreprocessError> {
+        let (name, mut index) = self.name(span.file, at + 1)?;
+        let mut args = Vec::new();
+        if index < span.end && self.char(span.file, index) == '(' {
+            let (spans, end) = self.args(span.file, index)?;
+            args = spans;
+            index = end;
+        }
+        let Some(Macro { params, body }) = self.macros.get(&name) else {
+            return Err(self.error(span.file, at, format!("Unknown macro `{}`", name)));
+        };
+        if params.len() != args.len() {
+            return Err(self.error(
+                span.file,
+                at,
+                format!(
+                    "The macro `{}` takes {} argument(s) but {} were given",
+                    name,
+                    params.len(),
+                    args.len()
+                ),
+            ));
+        }
+
+        let body = *body;
+        let macro_env = Rc::new(Env {
+            args: params
+                .iter()
+                .cloned()
+                .zip(args.into_iter().map(|arg| (arg, env.clone())))
+                .collect(),
+        });
+        self.nested(span.file, at, &name, |expander| {
+            expander.block(body, &macro_env, false).map(|_| ())
+        })?;
+        Ok(index)
+    }
+
+    /// Run the expansion one level deeper, it fails when the macros are too deep (e.g. recursive)
+    fn nested(
+        &mut self,
+        file: usize,
+        at: usize,
+        name: &str,
+        expand: impl FnOnce(&mut Self) -> Result<(), PreprocessError>,
+    ) -> Result<(), PreprocessError> {
+        if self.depth >= MAX_DEPTH {
+            return Err(self.error(
+                file,
+                at,
+                format!("The expansion of `{}` is too deep, is it recursive?", name),
+            ));
+        }
+        self.depth += 1;
+        let result = expand(self);
+        self.depth -= 1;
+        result
+    }
+
+    /// Parse the arguments from the `(`, split by the top level commas
+    fn args(&self, file: usize, open: usize) -> Result<(Vec<Span>, usize), PreprocessError> {
+        let text = &self.files[file].text;
+        let mut args = Vec::new();
+        let mut start = open + 1;
+        let mut nesting = 0;
+        for (index, c) in text.iter().enumerate().skip(open + 1) {
+            match c {
+                '(' | '{' => nesting += 1,
+                ')' if nesting == 0 => {
+                    let span = Span {
+                        file,
+                        start,
+                        end: index,
+                    };
+                    if !args.is_empty() || !self.text(span).trim().is_empty() {
+                        args.push(span);
+                    }
+                    return Ok((args, index + 1));
+                }
+                ')' | '}' => nesting -= 1,
+                ',' if nesting == 0 => {
+                    args.push(Span {
+                        file,
+                        start,
+                        end: index,
+                    });
+                    start = index + 1;
+                }
+                _ => {}
+            }
+        }
+        Err(self.error(file, open, "Unclosed `(`".to_string()))
+    }
+
+    /// Parse the macro definition after the `def`, and return the index after its body
+    fn define(&mut self, file: usize, mut index: usize) -> Result<usize, PreprocessError> {
+        index = self.skip_whitespace(file, index);
+        let at = index;
+        let (name, end) = self.name(file, index)?;
+        index = self.skip_whitespace(file, end);
+
+        let mut params = Vec::new();
+        if self.files[file].text.get(index) == Some(&'(') {
+            let (spans, end) = self.args(file, index)?;
+            for span in spans {
+                let param = self.text(span).trim().to_string();
+                let (param_name, param_end) =
+                    self.name(file, self.skip_whitespace(file, span.start))?;
+                if param_name != param || self.skip_whitespace(file, param_end) != span.end {
+                    return Err(self.error(
+                        file,
+                        span.start,
+                        format!("Invalid parameter name `{}`", param),
+                    ));
+                }
+                params.push(param);
+            }
+            index = self.skip_whitespace(file, end);
+        }
+
+        let text = &self.files[file].text;
+        if text.get(index) != Some(&'{') {
+            return Err(self.error(
+                file,
+                index,
+                format!("Expected `{{` after the macro `{}`", name),
+            ));
+        }
+        let mut nesting = 0;
+        let Some(close) = (index..text.len()).find(|i| {
+            match text[*i] {
+                '{' => nesting += 1,
+                '}' => nesting -= 1,
+                _ => {}
+            }
+            nesting == 0
+        }) else {
+            return Err(self.error(file, index, "Unclosed `{`".to_string()));
+        };
+
+        if self.macros.contains_key(&name) {
+            return Err(self.error(file, at, format!("The macro `{}` is already defined", name)));
+        }
+        self.macros.insert(
+            name,
+            Macro {
+                params,
+                body: Span {
+                    file,
+                    start: index + 1,
+                    end: close,
+                },
+            },
+        );
+        Ok(close + 1)
+    }
+
+    /// Expand the file of the include directive after the `include`, and return the index after it
+    fn include_directive(&mut self, file: usize, index: usize) -> Result<usize, PreprocessError> {
+        let start = self.skip_whitespace(file, index);
+        let text = &self.files[file].text;
+        if text.get(start) != Some(&'"') {
+            return Err(self.error(
+                file,
+                start,
+                "Expected a quoted file after `include`".to_string(),
+            ));
+        }
+        let Some(end) = (start + 1..text.len()).find(|i| matches!(text[*i], '"' | '\n')) else {
+            return Err(self.error(file, start, "Unclosed `\"`".to_string()));
+        };
+        if text[end] != '"' {
+            return Err(self.error(file, start, "Unclosed `\"`".to_string()));
+        }
+
+        let included: String = text[start + 1..end].iter().collect();
+        let path = Path::new(&self.files[file].name)
+            .parent()
+            .unwrap_or(Path::new(""))
+            .join(&included);
+        let canonical = path.canonicalize().unwrap_or(path.clone());
+        if self.includes.contains(&canonical) {
+            return Err(self.error(file, start, format!("Recursive include of `{}`", included)));
+        }
+        let source = std::fs::read_to_string(&path).map_err(|e| {
+            self.error(
+                file,
+                start,
+                format!("Failed to include `{}`: {}", included, e),
+            )
+        })?;
+        let name = path.to_string_lossy().to_string();
+        self.include(&name, path, &source)?;
+        Ok(end + 1)
+    }
+
+    /// Parse the repeat count after the `*`, a number or a parameter with a number
+    fn count(
+        &self,
+        file: usize,
+        index: usize,
+        env: &Env,
+    ) -> Result<(usize, usize), PreprocessError> {
+        let text = &self.files[file].text;
+        if text.get(index) == Some(&'$') {
+            let (name, end) = self.name(file, index + 1)?;
+            let Some((arg, _)) = env.args.get(&name) else {
+                return Err(self.error(file, index, format!("Unknown parameter `{}`", name)));
+            };
+            let value = self.text(*arg);
+            return match value.trim().parse() {
+                Ok(count) => Ok((count, end)),
+                Err(_) => Err(self.error(
+                    file,
+                    index,
+                    format!("The repeat count `{}` is not a number", value.trim()),
+                )),
+            };
+        }
+
+        let end = (index..text.len())
+            .find(|i| !text[*i].is_ascii_digit())
+            .unwrap_or(text.len());
+        match text[index..end].iter().collect::<String>().parse() {
+            Ok(count) => Ok((count, end)),
+            Err(_) => Err(self.error(file, index, "Expected a repeat count after `*`".to_string())),
+        }
+    }
+
+    /// Parse the identifier at the index, and return the index after it
+    fn name(&self, file: usize, index: usize) -> Result<(String, usize), PreprocessError> {
+        let text = &self.files[file].text;
+        let end = (index..text.len())
+            .find(|i| !is_name_char(text[*i]))
+            .unwrap_or(text.len());
+        if end == index || text[index].is_ascii_digit() {
+            return Err(self.error(file, index, "Expected a name".to_string()));
+        }
+        Ok((text[index..end].iter().collect(), end))
+    }
+
+    /// The keyword is a whole word at the index, followed by a whitespace
+    fn keyword(&self, file: usize, index: usize, keyword: &str) -> bool {
+        let text = &self.files[file].text;
+        let end = index + keyword.len();
+        (index == 0 || !is_name_char(text[index - 1]))
+            && text
+                .get(index..end)
+                .is_some_and(|word| word.iter().copied().eq(keyword.chars()))
+            && text.get(end).is_some_and(|c| c.is_whitespace())
+    }
+
+    fn skip_whitespace(&self, file: usize, index: usize) -> usize {
+        let text = &self.files[file].text;
+        (index..text.len())
+            .find(|i| !text[*i].is_whitespace())
+            .unwrap_or(text.len())
+    }
+
+    fn char(&self, file: usize, index: usize) -> char {
+        self.files[file].text[index]
+    }
+
+    fn text(&self, span: Span) -> String {
+        self.files[span.file].text[span.start..span.end]
+            .iter()
+            .collect()
+    }
+
+    fn error(&self, file: usize, index: usize, message: String) -> PreprocessError {
+        PreprocessError {
+            message,
+            file: self.files[file].name.clone(),
+            position: self.files[file].position(index),
+            code: 1,
+        }
+    }
+
+    fn too_long(&self, file: usize, index: usize) -> PreprocessError {
+        self.error(
+            file,
+            index,
+            format!("The expanded code is longer than {} commands", MAX_COMMANDS),
+        )
+    }
+
+    fn command_error(&self, index: usize) -> PreprocessError {
+        let (_, file, char_index) = self.commands[index];
+        PreprocessError {
+            code: 15,
+            ..self.error(file, char_index, "Unmatched bracket".to_string())
+        }
+    }
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn expand_code(source: &str) -> Result<String, String> {
+        expand("test.bfm", source)
+            .map(|preprocessed| preprocessed.code)
+            .map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn repeat_and_macros() {
+        assert_eq!(
+            expand_code("+*3 (>-)*2 comment ."),
+            Ok("+++>->-.".to_string())
+        );
+        assert_eq!(
+            expand_code(
+                "def clear { [-] }\n\
+                def move(from, to) { $from[-$to+$from] }\n\
+                def add(n) { +*$n }\n\
+                @add(5) @move(, >*2) @clear*0 @clear"
+            ),
+            Ok("+++++[->>+][-]".to_string())
+        );
+        // A def in a comment is not a definition without a space after it
+        assert_eq!(expand_code("define +"), Ok("+".to_string()));
+    }
+
+    #[test]
+    fn errors_refer_to_the_macro_source() {
+        assert_eq!(
+            expand_code("def open { [+ }\n+\n@open"),
+            Err("test.bfm:1:12: Unmatched bracket".to_string())
+        );
+        assert_eq!(
+            expand_code("def a(x) { $y }\n@a(+)"),
+            Err("test.bfm:1:12: Unknown parameter `y`".to_string())
+        );
+        assert_eq!(
+            expand_code("@b(+)"),
+            Err("test.bfm:1:1: Unknown macro `b`".to_string())
+        );
+        assert_eq!(
+            expand_code("def r { @r }\n@r"),
+            Err("test.bfm:1:9: The expansion of `r` is too deep, is it recursive?".to_string())
+        );
+        assert_eq!(
+            expand_code("+*4000000000"),
+            Err("test.bfm:1:2: The expanded code is longer than 4194304 commands".to_string())
+        );
+        assert_eq!(
+            expand_code("((+*1000)*1000)*1000"),
+            Err("test.bfm:1:16: The expanded code is longer than 4194304 commands".to_string())
+        );
+        assert_eq!(
+            expand_code("def a { +*2048 }\ndef b { @a*2048 }\n@b @b"),
+            Err("test.bfm:1:9: The expanded code is longer than 4194304 commands".to_string())
+        );
+    }
+
+    #[test]
+    fn source_map_and_includes() {
+        let directory =
+            std::env::temp_dir().join(format!("bfy_preprocessor_test_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(directory.join("lib.bfm"), "def print { . }\n").unwrap();
+        let main = directory.join("main.bfm");
+        std::fs::write(&main, "include \"lib.bfm\"\n+ @print").unwrap();
+
+        let preprocessed = expand_file(&main.to_string_lossy()).unwrap();
+        assert_eq!(preprocessed.code, "+.");
+        let (file, position) = preprocessed.source_map.location(0).unwrap();
+        assert_eq!(
+            (file.ends_with("main.bfm"), position.to_string()),
+            (true, "2:1".to_string())
+        );
+        let (file, position) = preprocessed.source_map.location(1).unwrap();
+        assert_eq!(
+            (file.ends_with("lib.bfm"), position.to_string()),
+            (true, "1:13".to_string())
+        );
+
+        std::fs::write(directory.join("lib.bfm"), "include \"main.bfm\"\n").unwrap();
+        assert!(expand_file(&main.to_string_lossy())
+            .unwrap_err()
+            .message
+            .starts_with("Recursive include"));
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
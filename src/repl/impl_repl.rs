@@ -1,18 +1,26 @@
 use super::repl::Repl;
+use crate::arguments::Feature;
 use crate::bf_interpreter::interpreter::Interpreter;
-use crate::repl::repl::{COMMAND_PREFIX, HISTORY_FILE, PROMPT};
+use crate::repl::repl::{COMMAND_PREFIX, EXTENDED_COMMAND_PREFIX, HISTORY_FILE, PROMPT};
 use colored::Colorize;
 use console::Key;
 use std::io::Write;
 
 impl Repl {
     pub fn new(interpreter: Interpreter) -> Repl {
+        let command_prefix = match interpreter.features.contains(&Feature::ExtendedType1) {
+            true => EXTENDED_COMMAND_PREFIX,
+            false => COMMAND_PREFIX,
+        };
         Repl {
             term: interpreter.term.clone(),
             interpreter,
             history: Vec::new(),
             loop_body: String::new(),
             loop_depth: 0,
+            prompt: PROMPT.to_string(),
+            history_file: HISTORY_FILE.to_string(),
+            command_prefix: command_prefix.to_string(),
         }
     }
 
@@ -27,7 +35,7 @@ impl Repl {
                 Ok(input) => {
                     let user_input = input.trim().to_string(); // Remove trailing newline
 
-                    if !user_input.is_empty() && user_input.len() > 0 {
+                    if !user_input.is_empty() {
                         self.history.push(user_input.clone()); // Save input to history
                         self.process(user_input); // Process the input
                     }
@@ -45,7 +53,7 @@ impl Repl {
             if self.loop_depth != 0 {
                 "............... ".yellow()
             } else {
-                PROMPT.to_string().truecolor(54, 76, 76)
+                self.prompt.truecolor(54, 76, 76)
             }
         );
     }
@@ -58,28 +66,24 @@ impl Repl {
             let key = self.term.read_key()?; // Read key from terminal
 
             match key {
-                Key::ArrowUp => {
-                    if !self.history.is_empty() && rev_index < self.history.len() {
-                        let last = self
-                            .history
-                            .get(self.history.len() - 1 - rev_index)
-                            .unwrap();
-                        rev_index += 1;
-                        self.term.clear_line()?;
-                        self.print_prompt();
-                        self.term.write_str(last)?;
-                        input = last.clone();
-                    }
+                Key::ArrowUp if !self.history.is_empty() && rev_index < self.history.len() => {
+                    let last = self
+                        .history
+                        .get(self.history.len() - 1 - rev_index)
+                        .unwrap();
+                    rev_index += 1;
+                    self.term.clear_line()?;
+                    self.print_prompt();
+                    self.term.write_str(last)?;
+                    input = last.clone();
                 }
-                Key::ArrowDown => {
-                    if !self.history.is_empty() && rev_index > 0 {
-                        let first = self.history.get(self.history.len() - rev_index).unwrap();
-                        rev_index -= 1;
-                        self.term.clear_line()?;
-                        self.print_prompt();
-                        self.term.write_str(first)?;
-                        input = first.clone();
-                    }
+                Key::ArrowDown if !self.history.is_empty() && rev_index > 0 => {
+                    let first = self.history.get(self.history.len() - rev_index).unwrap();
+                    rev_index -= 1;
+                    self.term.clear_line()?;
+                    self.print_prompt();
+                    self.term.write_str(first)?;
+                    input = first.clone();
                 }
                 Key::Char(c) => {
                     self.term.write_str(&c.to_string())?;
@@ -122,12 +126,10 @@ impl Repl {
                 return;
             }
             _ => {
-                if user_input.contains(']') {
-                    if self.loop_depth == 0 {
-                        self.loop_body.push_str(&user_input);
-                        user_input = self.loop_body.clone();
-                        self.loop_body = String::new();
-                    }
+                if user_input.contains(']') && self.loop_depth == 0 {
+                    self.loop_body.push_str(&user_input);
+                    user_input = self.loop_body.clone();
+                    self.loop_body = String::new();
                 }
                 if self.loop_depth != 0 {
                     self.loop_body.push_str(&user_input);
@@ -136,11 +138,11 @@ impl Repl {
             }
         }
 
-        if user_input.is_empty() || user_input.len() == 0 {
+        if user_input.is_empty() {
             return;
         }
 
-        if user_input.starts_with(COMMAND_PREFIX) {
+        if user_input.starts_with(&self.command_prefix) {
             self.run_repl_cmd(user_input);
         } else {
             match self.interpreter.run(user_input) {
@@ -159,138 +161,138 @@ impl Repl {
 
     fn run_repl_cmd(&mut self, user_input: String) {
         let mut cmd = user_input.split_whitespace();
-        match cmd.next() {
-            Some(repl_cmd) => {
-                match repl_cmd.get(COMMAND_PREFIX.len()..).unwrap_or("") {
-                    "fuck" => {
-                        println!("{}", "Bye bye :D".green());
-                        std::process::exit(0);
-                    }
-                    "array" | "a" => {
-                        println!("{}", format!("Current array: {:?}", self.interpreter.cells));
-                    }
-                    "array_size" | "as" => {
-                        println!(
-                            "{}",
-                            format!(
-                                "Current array size: {}",
-                                self.interpreter.cells.len().to_string().bold().green()
-                            )
-                        );
-                    }
-                    "pointer" | "p" => {
-                        println!(
-                            "{}",
-                            format!(
-                                "Current pointer: {}",
-                                self.interpreter.pointer.to_string().bold().green()
-                            )
-                        );
-                    }
-                    "pointer_value" | "pv" => {
-                        println!(
-                            "Current pointer value: {} = \'{}\' (char)",
-                            self.interpreter.cells[self.interpreter.pointer],
-                            self.interpreter.cells[self.interpreter.pointer]
-                                .to_char()
-                                .unwrap_or_else(|_| '?')
-                        );
-                    }
-                    "history" | "h" => {
-                        println!("{}", "History:".underline().green());
-                        for (i, cmd) in self.history.iter().enumerate() {
-                            println!("{}", format!("{}: {}", i, cmd));
+        if let Some(repl_cmd) = cmd.next() {
+            match repl_cmd.get(self.command_prefix.len()..).unwrap_or("") {
+                "fuck" => {
+                    println!("{}", "Bye bye :D".green());
+                    std::process::exit(0);
+                }
+                "array" | "a" => {
+                    println!("Current array: {:?}", self.interpreter.cells);
+                }
+                "array_size" | "as" => {
+                    println!(
+                        "Current array size: {}",
+                        self.interpreter.cells.len().to_string().bold().green()
+                    );
+                }
+                "pointer" | "p" => {
+                    println!(
+                        "Current pointer: {}",
+                        self.interpreter.pointer.to_string().bold().green()
+                    );
+                    if self.interpreter.thread_pointers.len() > 1 {
+                        println!("Threads pointers at the end of the last run:");
+                        for (i, pointer) in self.interpreter.thread_pointers.iter().enumerate() {
+                            println!(
+                                "  thread {}: {} = {}",
+                                i,
+                                pointer.to_string().bold().green(),
+                                self.interpreter.cells[*pointer]
+                            );
                         }
                     }
-                    "save" | "s" => {
-                        let file_name = cmd.next().unwrap_or(HISTORY_FILE);
+                }
+                "pointer_value" | "pv" => {
+                    println!(
+                        "Current pointer value: {} = \'{}\' (char)",
+                        self.interpreter.cells[self.interpreter.pointer],
+                        self.interpreter.cells[self.interpreter.pointer]
+                            .to_char()
+                            .unwrap_or('?')
+                    );
+                }
+                "history" | "h" => {
+                    println!("{}", "History:".underline().green());
+                    for (i, cmd) in self.history.iter().enumerate() {
+                        println!("{}: {}", i, cmd);
+                    }
+                }
+                "save" | "s" => {
+                    let file_name = cmd.next().unwrap_or(&self.history_file);
 
-                        println!(
-                            "{}",
-                            format!("Saving history to file: {file_name}").yellow()
-                        );
-                        match std::fs::write(file_name, self.history.join("\n")) {
-                            Ok(_) => {
-                                println!(
-                                    "{}",
-                                    format!("Successfully saved history to file: {file_name}")
-                                        .green()
-                                );
-                            }
-                            Err(e) => {
-                                error!("Failed to save history to file: {}", e);
-                            }
+                    println!(
+                        "{}",
+                        format!("Saving history to file: {file_name}").yellow()
+                    );
+                    match std::fs::write(file_name, self.history.join("\n")) {
+                        Ok(_) => {
+                            println!(
+                                "{}",
+                                format!("Successfully saved history to file: {file_name}").green()
+                            );
+                        }
+                        Err(e) => {
+                            error!("Failed to save history to file: {}", e);
                         }
                     }
-                    "load" | "l" => {
-                        let file_name = cmd.next().unwrap_or(HISTORY_FILE);
+                }
+                "load" | "l" => {
+                    let file_name = cmd.next().unwrap_or(&self.history_file);
 
-                        println!(
-                            "{}",
-                            format!("Loading history from file: {file_name}").yellow()
-                        );
-                        match std::fs::read_to_string(file_name) {
-                            Ok(history) => {
-                                println!(
-                                    "{}",
-                                    format!("Successfully loaded history from file: {file_name}")
-                                        .green()
-                                );
-                                self.history = history.split("\n").map(|s| s.to_string()).collect();
+                    println!(
+                        "{}",
+                        format!("Loading history from file: {file_name}").yellow()
+                    );
+                    match std::fs::read_to_string(file_name) {
+                        Ok(history) => {
+                            println!(
+                                "{}",
+                                format!("Successfully loaded history from file: {file_name}")
+                                    .green()
+                            );
+                            self.history = history.split("\n").map(|s| s.to_string()).collect();
 
-                                // Run all commands in history
-                                for cmd in self.history.iter() {
-                                    match self.interpreter.run(cmd.clone()) {
-                                        Ok(_) => {
-                                            info!(
-                                                "Successfully ran brainfuck source code from REPL"
-                                            );
-                                        }
-                                        Err(e) => {
-                                            error!(
-                                                "Failed to run brainfuck source code from REPL: {}",
-                                                e
-                                            );
-                                        }
+                            // Run all commands in history
+                            for cmd in self.history.iter() {
+                                match self.interpreter.run(cmd.clone()) {
+                                    Ok(_) => {
+                                        info!("Successfully ran brainfuck source code from REPL");
+                                    }
+                                    Err(e) => {
+                                        error!(
+                                            "Failed to run brainfuck source code from REPL: {}",
+                                            e
+                                        );
                                     }
                                 }
                             }
-                            Err(e) => {
-                                error!("Failed to load history from file: {}", e);
-                            }
+                        }
+                        Err(e) => {
+                            error!("Failed to load history from file: {}", e);
                         }
                     }
-                    "reset" | "r" => {
-                        println!("{}", "Resetting REPL".truecolor(56, 33, 102));
-                        self.interpreter.reset();
-                        self.history = Vec::new();
-                    }
-                    "help" => {
-                        println!(
-                            "!array, !a: print the current array\n\
-                        !array_size, !as: print the current array size\n\
-                        !pointer, !p: print the current pointer\n\
-                        !pointer_value, !pv: print the current pointer value\n\
-                        !history, !h: print the REPL history\n\
-                        !save, !s: save the REPL history to a file\n\
-                        !load, !l: load the REPL history from a file\n\
-                        !reset, !r: reset the REPL\n\
-                        !help: print this help message\n\
-                        !fuck: exit the REPL"
-                        );
-                    }
-                    _ => println!(
-                        "{}",
-                        format!(
-                            "Unknown command: {}, type {} to show the help",
-                            user_input,
-                            (COMMAND_PREFIX.to_string() + "help").green()
-                        )
-                        .red()
-                    ),
                 }
+                "reset" | "r" => {
+                    println!("{}", "Resetting REPL".truecolor(56, 33, 102));
+                    self.interpreter.reset();
+                    self.history = Vec::new();
+                }
+                "help" => {
+                    println!(
+                        "{p}array, {p}a: print the current array\n\
+                        {p}array_size, {p}as: print the current array size\n\
+                        {p}pointer, {p}p: print the current pointer (and the brainfork threads pointers)\n\
+                        {p}pointer_value, {p}pv: print the current pointer value\n\
+                        {p}history, {p}h: print the REPL history\n\
+                        {p}save, {p}s: save the REPL history to a file\n\
+                        {p}load, {p}l: load the REPL history from a file\n\
+                        {p}reset, {p}r: reset the REPL\n\
+                        {p}help: print this help message\n\
+                        {p}fuck: exit the REPL",
+                        p = self.command_prefix
+                    );
+                }
+                _ => println!(
+                    "{}",
+                    format!(
+                        "Unknown command: {}, type {} to show the help",
+                        user_input,
+                        (self.command_prefix.clone() + "help").green()
+                    )
+                    .red()
+                ),
             }
-            None => {}
         }
     }
 }
@@ -1,3 +1,4 @@
 mod impl_repl;
-mod repl;
+#[allow(clippy::module_inception)]
+pub mod repl;
 pub mod start_repl;
@@ -7,6 +7,12 @@ pub struct Repl {
     pub history: Vec<String>,
     pub loop_body: String,
     pub loop_depth: usize,
+    /// The prompt, `PROMPT` if it is not in the config file
+    pub prompt: String,
+    /// The default file of the `save` and `load` commands, `HISTORY_FILE` if it is not in the config file
+    pub history_file: String,
+    /// The prefix of the REPL commands, `COMMAND_PREFIX` or `EXTENDED_COMMAND_PREFIX`
+    pub command_prefix: String,
 }
 
 /// The REPL prompt
@@ -15,6 +21,8 @@ pub const PROMPT: &str = "bf-interpreter> ";
 pub const HISTORY_FILE: &str = "bf-interpreter-history.bfr";
 /// The command prefix
 pub const COMMAND_PREFIX: &str = "!";
+/// The command prefix with the Extended Type I feature, where `!` is a command of the code
+pub const EXTENDED_COMMAND_PREFIX: &str = "/";
 
 /// Tests :D
 #[cfg(test)]
@@ -37,9 +45,9 @@ mod tests {
 
         let cells = &repl.interpreter.cells;
 
-        assert_eq!(cells[0], Cell::default_cell(&vec![]));
-        assert_eq!(cells[1], Cell::default_cell(&vec![]));
-        assert_eq!(cells[2], Cell::new(4, &vec![]));
+        assert_eq!(cells[0], Cell::default_cell(&[]));
+        assert_eq!(cells[1], Cell::default_cell(&[]));
+        assert_eq!(cells[2], Cell::new(4, &[]));
     }
 
     #[test]
@@ -59,9 +67,9 @@ mod tests {
 
         let cells = &repl.interpreter.cells;
 
-        assert_eq!(cells[0], Cell::default_cell(&vec![]));
-        assert_eq!(cells[1], Cell::default_cell(&vec![]));
-        assert_eq!(cells[2], Cell::new(4, &vec![]));
+        assert_eq!(cells[0], Cell::default_cell(&[]));
+        assert_eq!(cells[1], Cell::default_cell(&[]));
+        assert_eq!(cells[2], Cell::new(4, &[]));
     }
 
     #[test]
@@ -119,8 +127,7 @@ mod tests {
             <<-
         ]
         >>. Print s"
-            .to_string()
-            .split("\n")
+            .split('\n')
             .map(|s| s.to_string())
             .collect::<Vec<String>>();
 
@@ -128,12 +135,12 @@ mod tests {
             repl.process(line);
         }
 
-        assert_eq!(repl.interpreter.cells[0], Cell::default_cell(&vec![]));
-        assert_eq!(repl.interpreter.cells[1], Cell::default_cell(&vec![]));
-        assert_eq!(repl.interpreter.cells[2], Cell::new(115, &vec![]));
-        assert_eq!(repl.interpreter.cells[3], Cell::new(96, &vec![]));
-        assert_eq!(repl.interpreter.cells[4], Cell::new(112, &vec![]));
-        assert_eq!(repl.interpreter.cells[5], Cell::new(32, &vec![]));
+        assert_eq!(repl.interpreter.cells[0], Cell::default_cell(&[]));
+        assert_eq!(repl.interpreter.cells[1], Cell::default_cell(&[]));
+        assert_eq!(repl.interpreter.cells[2], Cell::new(115, &[]));
+        assert_eq!(repl.interpreter.cells[3], Cell::new(96, &[]));
+        assert_eq!(repl.interpreter.cells[4], Cell::new(112, &[]));
+        assert_eq!(repl.interpreter.cells[5], Cell::new(32, &[]));
     }
 
     #[test]
@@ -149,12 +156,12 @@ mod tests {
 
         repl.process(code);
 
-        assert_eq!(repl.interpreter.cells[0], Cell::default_cell(&vec![]));
-        assert_eq!(repl.interpreter.cells[1], Cell::default_cell(&vec![]));
-        assert_eq!(repl.interpreter.cells[2], Cell::new(115, &vec![]));
-        assert_eq!(repl.interpreter.cells[3], Cell::new(96, &vec![]));
-        assert_eq!(repl.interpreter.cells[4], Cell::new(112, &vec![]));
-        assert_eq!(repl.interpreter.cells[5], Cell::new(32, &vec![]));
+        assert_eq!(repl.interpreter.cells[0], Cell::default_cell(&[]));
+        assert_eq!(repl.interpreter.cells[1], Cell::default_cell(&[]));
+        assert_eq!(repl.interpreter.cells[2], Cell::new(115, &[]));
+        assert_eq!(repl.interpreter.cells[3], Cell::new(96, &[]));
+        assert_eq!(repl.interpreter.cells[4], Cell::new(112, &[]));
+        assert_eq!(repl.interpreter.cells[5], Cell::new(32, &[]));
     }
 
     #[test]
@@ -164,7 +171,7 @@ mod tests {
 
         let mut repl = Repl::new(interpreter);
 
-        let _ = "[ This program prints \"Hello World!\" and a newline to the screen, its
+        "[ This program prints \"Hello World!\" and a newline to the screen, its
                 length is 106 active command characters. [It is not the shortest.]
                 ]
                 ++++++++               Set Cell #0 to 8
@@ -201,8 +208,22 @@ mod tests {
                 >>+.                    Add 1 to Cell #5 gives us an exclamation point
                 >++.                    And finally a newline from Cell #6
             "
-        .to_string()
-        .split("\n")
+        .split('\n')
         .for_each(|s| repl.process(s.to_string()));
     }
+
+    #[test]
+    fn extended_type_1_command_prefix() {
+        let features = vec![crate::arguments::Feature::ExtendedType1];
+        let interpreter = Interpreter::new(4, features.clone(), Term::stdout());
+
+        let mut repl = Repl::new(interpreter);
+        assert_eq!(repl.command_prefix, EXTENDED_COMMAND_PREFIX);
+
+        // `!` loads the storage, it is not a REPL command
+        repl.process("++$>!".to_string());
+        repl.process("/reset".to_string());
+        assert_eq!(repl.history, Vec::<String>::new());
+        assert_eq!(repl.interpreter.cells[1], Cell::default_cell(&features));
+    }
 }
@@ -1,12 +1,20 @@
 use crate::bf_interpreter::interpreter::Interpreter;
-use crate::repl::repl::{Repl, COMMAND_PREFIX};
+use crate::config::ReplConfig;
+use crate::repl::repl::Repl;
 use colored::Colorize;
 
 /// Run the REPL
 /// # Arguments
 /// * `interpreter` - The interpreter to use
-pub fn start(interpreter: Interpreter) {
+/// * `config` - The prompt, the history file and the command prefix from the config files
+pub fn start(interpreter: Interpreter, config: ReplConfig) {
     info!("Entering REPL mode");
+    let mut repl = Repl::new(interpreter);
+    repl.prompt = config.prompt;
+    repl.history_file = config.history;
+    if let Some(command_prefix) = config.command_prefix {
+        repl.command_prefix = command_prefix;
+    }
     println!(
         "{}\n\
             Brainfuck interpreter v {}\nBy {}\n\
@@ -19,11 +27,11 @@ pub fn start(interpreter: Interpreter) {
         "Enter your brainfuck code and press enter to run it."
             .italic()
             .blue(),
-        (COMMAND_PREFIX.to_string() + "fuck").bold().red(),
-        (COMMAND_PREFIX.to_string() + "help").bold().green(),
+        (repl.command_prefix.clone() + "fuck").bold().red(),
+        (repl.command_prefix.clone() + "help").bold().green(),
     );
 
-    match Repl::new(interpreter).run() {
+    match repl.run() {
         Ok(_) => {
             info!("Successfully ran REPL");
         }
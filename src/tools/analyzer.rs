@@ -0,0 +1,309 @@
+use crate::arguments::Feature;
+use crate::bf_interpreter::cell::Cell;
+use crate::tools::diagnostic::{self, Diagnostic, Level, Node, Token};
+use std::collections::HashMap;
+
+// The diagnostics:
+// A001: loop that never changes its cell and keeps the pointer balanced, so it never ends once it starts
+// A002: the pointer moves below the cell 0
+// A003: the pointer moves beyond the array size
+// A004: the value of a cell goes out of bounds
+// They are errors when the code surely runs with the known values, otherwise they are warnings
+
+/// The known values of the cells by their positions, the missing cells are zero and `None` is unknown
+type Cells = HashMap<isize, Option<i64>>;
+
+/// Analyse the brainfuck source code without running it, the brackets must be balanced
+/// # Arguments
+/// * `source` - The brainfuck source code, with the comments
+/// * `array_size` - The brainfuck array size
+/// * `features` - The extra features to follow, the same ones the interpreter uses
+pub fn analyze(source: &str, array_size: usize, features: &[Feature]) -> Vec<Diagnostic> {
+    let tokens = diagnostic::tokens(source);
+    let mut analyzer = Analyzer {
+        tokens: &tokens,
+        diagnostics: Vec::new(),
+        array_size: array_size as isize,
+        max_value: Cell::default_cell(features).max_value() as i64,
+        wrap_value: Cell::wraps(features),
+        wrap_pointer: features.contains(&Feature::ReversePointer),
+        stopped: false,
+    };
+    let mut state = State {
+        pointer: Some(0),
+        cells: Some(Cells::new()),
+        definite: true,
+    };
+    analyzer.block(&diagnostic::nodes(&tokens), &mut state);
+    analyzer.diagnostics
+}
+
+/// What is known when a command runs
+struct State {
+    /// The position of the pointer
+    pointer: Option<isize>,
+    cells: Option<Cells>,
+    /// The command surely runs if the previous commands end without errors (it is not in a loop body)
+    definite: bool,
+}
+
+impl State {
+    fn value(&self) -> Option<i64> {
+        let cells = self.cells.as_ref()?;
+        cells.get(&self.pointer?).copied().unwrap_or(Some(0))
+    }
+
+    fn set_value(&mut self, value: Option<i64>) {
+        if let (Some(cells), Some(pointer)) = (self.cells.as_mut(), self.pointer) {
+            cells.insert(pointer, value);
+        }
+    }
+}
+
+struct Analyzer<'a> {
+    tokens: &'a [Token],
+    diagnostics: Vec<Diagnostic>,
+    array_size: isize,
+    max_value: i64,
+    wrap_value: bool,
+    wrap_pointer: bool,
+    /// A definite error is found, the code after it never runs
+    stopped: bool,
+}
+
+impl Analyzer<'_> {
+    fn block(&mut self, nodes: &[Node], state: &mut State) {
+        for node in nodes {
+            if self.stopped {
+                return;
+            }
+            match node {
+                Node::Command(index) => self.command(*index, state),
+                Node::Loop { open, close, body } => {
+                    if state.value() == Some(0) {
+                        continue;
+                    }
+                    if is_infinite(self.tokens, body) {
+                        let level = match state.definite && state.value().is_some() {
+                            true => Level::Error,
+                            false => Level::Warning,
+                        };
+                        self.push(
+                            level,
+                            "A001",
+                            "This loop never changes its cell and keeps the pointer balanced, \
+                            it never ends once it starts"
+                                .to_string(),
+                            *open,
+                            *close,
+                        );
+                    }
+
+                    // The first iteration starts at the known cell, the next ones start at the same cell
+                    // only if the loop is balanced, so the unbalanced loops are checked on the first iteration
+                    let balanced = offset(self.tokens, body) == Some(0);
+                    let mut body_state = State {
+                        pointer: state.pointer,
+                        cells: None,
+                        definite: false,
+                    };
+                    self.block(body, &mut body_state);
+
+                    if !balanced {
+                        state.pointer = None;
+                    }
+                    state.cells = None;
+                }
+            }
+        }
+    }
+
+    fn command(&mut self, index: usize, state: &mut State) {
+        let level = match state.definite {
+            true => Level::Error,
+            false => Level::Warning,
+        };
+        match self.tokens[index].command {
+            '>' | '<' => {
+                let Some(pointer) = state.pointer else {
+                    return;
+                };
+                let pointer = pointer
+                    + if self.tokens[index].command == '>' {
+                        1
+                    } else {
+                        -1
+                    };
+                if self.wrap_pointer {
+                    state.pointer = Some(pointer.rem_euclid(self.array_size));
+                    return;
+                }
+                if pointer < 0 {
+                    self.push(
+                        level,
+                        "A002",
+                        "The pointer moves below the cell 0".to_string(),
+                        index,
+                        index,
+                    );
+                } else if pointer >= self.array_size {
+                    self.push(
+                        level,
+                        "A003",
+                        format!(
+                            "The pointer moves beyond the array size ({})",
+                            self.array_size
+                        ),
+                        index,
+                        index,
+                    );
+                } else {
+                    state.pointer = Some(pointer);
+                    return;
+                }
+                // Don't report the same error for the next moves
+                state.pointer = None;
+                state.cells = None;
+            }
+            '+' | '-' => {
+                let Some(value) = state.value() else {
+                    return;
+                };
+                let value = value
+                    + if self.tokens[index].command == '+' {
+                        1
+                    } else {
+                        -1
+                    };
+                if self.wrap_value {
+                    state.set_value(Some(value.rem_euclid(self.max_value + 1)));
+                } else if value < 0 || value > self.max_value {
+                    self.push(
+                        level,
+                        "A004",
+                        format!("The value goes out of bounds (0 to {})", self.max_value),
+                        index,
+                        index,
+                    );
+                    state.set_value(None);
+                } else {
+                    state.set_value(Some(value));
+                }
+            }
+            ',' => state.set_value(None),
+            _ => {}
+        }
+    }
+
+    fn push(
+        &mut self,
+        level: Level,
+        code: &'static str,
+        message: String,
+        start: usize,
+        end: usize,
+    ) {
+        self.stopped |= level == Level::Error;
+        self.diagnostics.push(Diagnostic {
+            level,
+            code,
+            message,
+            start: self.tokens[start].position,
+            end: self.tokens[end].position,
+        });
+    }
+}
+
+/// The pointer offset of one run of the nodes, `None` if a nested loop is not balanced
+fn offset(tokens: &[Token], nodes: &[Node]) -> Option<isize> {
+    let mut offset = 0;
+    for node in nodes {
+        match node {
+            Node::Command(index) => match tokens[*index].command {
+                '>' => offset += 1,
+                '<' => offset -= 1,
+                _ => {}
+            },
+            Node::Loop { body, .. } => {
+                if self::offset(tokens, body)? != 0 {
+                    return None;
+                }
+            }
+        }
+    }
+    Some(offset)
+}
+
+/// The loop body has no nested loops and no input, keeps the pointer balanced and doesn't change the loop cell
+fn is_infinite(tokens: &[Token], body: &[Node]) -> bool {
+    let mut offset = 0;
+    let mut change = 0;
+    for node in body {
+        let Node::Command(index) = node else {
+            return false;
+        };
+        match tokens[*index].command {
+            '>' => offset += 1,
+            '<' => offset -= 1,
+            '+' if offset == 0 => change += 1,
+            '-' if offset == 0 => change -= 1,
+            ',' => return false,
+            _ => {}
+        }
+    }
+    offset == 0 && change == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn analyze_code(code: &str, features: &[Feature]) -> Vec<(Level, &'static str, String)> {
+        analyze(code, 10, features)
+            .into_iter()
+            .map(|d| (d.level, d.code, d.start.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn infinite_loops() {
+        assert_eq!(
+            analyze_code("+[>+<]", &[]),
+            vec![(Level::Error, "A001", "1:2".to_string())]
+        );
+        assert_eq!(
+            analyze_code(",[.]", &[]),
+            vec![(Level::Warning, "A001", "1:2".to_string())]
+        );
+        // It never runs
+        assert!(analyze_code("[.]", &[]).is_empty());
+    }
+
+    #[test]
+    fn pointer_out_of_bounds() {
+        assert_eq!(
+            analyze_code("><<>", &[]),
+            vec![(Level::Error, "A002", "1:3".to_string())]
+        );
+        assert_eq!(
+            analyze_code(">>>>>>>>>[-]>", &[]),
+            vec![(Level::Error, "A003", "1:13".to_string())]
+        );
+        assert_eq!(
+            analyze_code(",[<-]+[>-<[-<]]", &[]),
+            vec![(Level::Warning, "A002", "1:3".to_string())]
+        );
+        assert!(analyze_code("<", &[Feature::ReversePointer]).is_empty());
+    }
+
+    #[test]
+    fn value_out_of_bounds() {
+        assert_eq!(
+            analyze_code("+--", &[]),
+            vec![(Level::Error, "A004", "1:3".to_string())]
+        );
+        assert!(analyze_code("+--", &[Feature::NoReverseValue]).is_empty());
+        assert!(analyze_code(",-[-]-", &[]).is_empty());
+    }
+}
@@ -0,0 +1,147 @@
+use crate::header;
+use crate::utils::is_command;
+use colored::{Color, Colorize};
+use std::fmt::{Display, Formatter};
+
+/// A position in the source code, the line and the column start from 1
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Display for Position {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// A brainfuck command with its position in the source code
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Token {
+    pub command: char,
+    pub position: Position,
+}
+
+/// The source code split to the commands tokens, the comments and the header are skipped
+pub fn tokens(source: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    for (line_index, line) in source
+        .lines()
+        .enumerate()
+        .skip(header::header_lines(source))
+    {
+        for (column_index, c) in line.chars().enumerate() {
+            if is_command(c) {
+                tokens.push(Token {
+                    command: c,
+                    position: Position {
+                        line: line_index + 1,
+                        column: column_index + 1,
+                    },
+                });
+            }
+        }
+    }
+    tokens
+}
+
+/// A brainfuck command, or a loop with the indexes of its brackets in the tokens
+#[derive(Debug, PartialEq, Clone)]
+pub enum Node {
+    Command(usize),
+    Loop {
+        open: usize,
+        close: usize,
+        body: Vec<Node>,
+    },
+}
+
+/// Build the loops tree from the tokens, the brackets must be balanced
+pub fn nodes(tokens: &[Token]) -> Vec<Node> {
+    let mut stack: Vec<(usize, Vec<Node>)> = vec![(0, Vec::new())];
+    for (index, token) in tokens.iter().enumerate() {
+        match token.command {
+            '[' => stack.push((index, Vec::new())),
+            ']' if stack.len() > 1 => {
+                let (open, body) = stack.pop().unwrap();
+                stack.last_mut().unwrap().1.push(Node::Loop {
+                    open,
+                    close: index,
+                    body,
+                });
+            }
+            _ => stack.last_mut().unwrap().1.push(Node::Command(index)),
+        }
+    }
+    stack.swap_remove(0).1
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Level {
+    /// The code may be wrong
+    Warning,
+    /// The code is definitely wrong when it runs
+    Error,
+}
+
+impl Display for Level {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            Level::Warning => write!(f, "warning"),
+            Level::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A warning or an error found in the source code without running it
+#[derive(Debug, PartialEq, Clone)]
+pub struct Diagnostic {
+    pub level: Level,
+    /// The code of the diagnostic, to suppress it (e.g. `L001`)
+    pub code: &'static str,
+    pub message: String,
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Diagnostic {
+    /// Print the diagnostic with the source file name and the line of the source code
+    pub fn print(&self, file: &str, source: &str) {
+        let line = source.lines().nth(self.start.line - 1).unwrap_or_default();
+        let length = if self.end.line == self.start.line {
+            self.end.column - self.start.column + 1
+        } else {
+            line.chars().count() + 1 - self.start.column
+        };
+        let color = match self.level {
+            Level::Warning => Color::Yellow,
+            Level::Error => Color::Red,
+        };
+        eprintln!(
+            "{}{} {}",
+            format!("{}[{}]", self.level, self.code).bold().color(color),
+            ":".bold(),
+            self.message.bold()
+        );
+        eprintln!("  {} {}:{}", "-->".blue(), file, self.start);
+        eprintln!("   {}", "|".blue());
+        eprintln!("   {} {}", "|".blue(), line);
+        eprintln!(
+            "   {} {}{}",
+            "|".blue(),
+            " ".repeat(self.start.column - 1),
+            "^".repeat(length.max(1)).color(color)
+        );
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {}[{}] {}",
+            self.start, self.level, self.code, self.message
+        )
+    }
+}
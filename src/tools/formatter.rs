@@ -0,0 +1,196 @@
+use crate::header;
+use crate::utils::is_command;
+
+/// The indentation of one loop depth
+const INDENT: &str = "    ";
+
+/// Format the brainfuck source code, every line is a pointer move followed by the commands on that cell,
+/// the loop brackets are on their own lines and the loop bodies are indented by the depth.
+/// The comments stay at the end of the code they are written after, and the comment lines stay on their own lines
+/// (the header lines stay at the top)
+/// # Arguments
+/// * `source` - The brainfuck source code, with the comments
+pub fn format(source: &str) -> String {
+    let mut formatter = Formatter {
+        lines: Vec::new(),
+        line: String::new(),
+        depth: 0,
+    };
+
+    // The header lines stay as they are at the top
+    let header_lines = header::header_lines(source);
+    formatter
+        .lines
+        .extend(source.lines().take(header_lines).map(String::from));
+    for source_line in source.lines().skip(header_lines) {
+        if source_line.trim().is_empty() {
+            formatter.blank_line();
+            continue;
+        }
+
+        let mut comments = Vec::new();
+        let mut comment = String::new();
+        let mut has_code = false;
+        for c in source_line.chars() {
+            if is_command(c) {
+                has_code = true;
+                comments.push(std::mem::take(&mut comment));
+                formatter.command(c);
+            } else {
+                comment.push(c);
+            }
+        }
+        comments.push(comment);
+
+        let comment = comments
+            .iter()
+            .map(|comment| comment.trim())
+            .filter(|comment| !comment.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ");
+        if comment.is_empty() {
+            continue;
+        }
+        if has_code {
+            formatter.trailing_comment(&comment);
+        } else {
+            formatter.flush();
+            formatter.push_line(comment);
+        }
+    }
+
+    formatter.flush();
+    while formatter.lines.last().is_some_and(|line| line.is_empty()) {
+        formatter.lines.pop();
+    }
+    let mut formatted = formatter.lines.join("\n");
+    formatted.push('\n');
+    formatted
+}
+
+struct Formatter {
+    lines: Vec<String>,
+    /// The line that is being built
+    line: String,
+    depth: usize,
+}
+
+impl Formatter {
+    fn command(&mut self, command: char) {
+        match command {
+            '[' => {
+                self.flush();
+                self.push_line("[".to_string());
+                self.depth += 1;
+            }
+            ']' => {
+                self.flush();
+                self.depth = self.depth.saturating_sub(1);
+                self.push_line("]".to_string());
+            }
+            // A pointer move starts a new line, unless the line is only pointer moves
+            '<' | '>' => {
+                if self.line.contains(|c| !matches!(c, '<' | '>')) {
+                    self.flush();
+                }
+                self.line.push(command);
+            }
+            _ => self.line.push(command),
+        }
+    }
+
+    /// Attach the comment to the end of the current line, or to the last line if the current line is empty
+    fn trailing_comment(&mut self, comment: &str) {
+        if !self.line.is_empty() {
+            self.line.push(' ');
+            self.line.push_str(comment);
+            self.flush();
+        } else if let Some(last_line) = self.lines.last_mut().filter(|line| !line.is_empty()) {
+            last_line.push(' ');
+            last_line.push_str(comment);
+        } else {
+            self.push_line(comment.to_string());
+        }
+    }
+
+    /// Keep one blank line between the paragraphs of the code
+    fn blank_line(&mut self) {
+        self.flush();
+        if self.lines.last().is_some_and(|line| !line.is_empty()) {
+            self.lines.push(String::new());
+        }
+    }
+
+    fn flush(&mut self) {
+        if !self.line.is_empty() {
+            let line = std::mem::take(&mut self.line);
+            self.push_line(line);
+        }
+    }
+
+    fn push_line(&mut self, line: String) {
+        self.lines
+            .push(format!("{}{}", INDENT.repeat(self.depth), line));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn indent_loops_and_group_commands() {
+        assert_eq!(
+            format("++++++++[>++++[>++>+++<<-]>>+.<<<-]"),
+            "++++++++\n\
+            [\n    \
+                >++++\n    \
+                [\n        \
+                    >++\n        \
+                    >+++\n        \
+                    <<-\n    \
+                ]\n    \
+                >>+.\n    \
+                <<<-\n\
+            ]\n"
+        );
+    }
+
+    #[test]
+    fn keep_the_comments() {
+        assert_eq!(
+            format("My program\n\n\n++++ ++++ 8\n[ loop\n>+ A\n comment line\n<-]\n>. print\n"),
+            "My program\n\
+            \n\
+            ++++++++ 8\n\
+            [ loop\n    \
+                >+ A\n    \
+                comment line\n    \
+                <-\n\
+            ]\n\
+            >. print\n"
+        );
+    }
+
+    #[test]
+    fn formatting_is_idempotent() {
+        for file in [
+            "test_code/print_my_first_name_formatted.bf",
+            "test_code/print_hello_world_2_formated.bf",
+            "test_code/fibonacci.bf",
+        ] {
+            let source = utils::read_source_file(&file.to_string());
+            let formatted = format(&source);
+            assert_eq!(format(&formatted), formatted);
+            assert_eq!(
+                utils::read_brainfuck_code(&file.to_string()),
+                formatted
+                    .chars()
+                    .filter(|c| is_command(*c))
+                    .collect::<String>()
+            );
+        }
+    }
+}
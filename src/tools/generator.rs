@@ -0,0 +1,292 @@
+use crate::arguments::Feature;
+use crate::bf_interpreter::cell::Cell;
+use std::collections::HashMap;
+
+/// Generate a brainfuck program that prints the text.
+/// Every character is built on the cell with the closest value (the cells are reused across the characters),
+/// and the big changes are made with multiplication loops on the temporary cells after the data cells
+/// # Arguments
+/// * `text` - The text to print
+/// * `features` - The extra features to follow, `utf8` allows the characters above 255
+/// * `optimize` - Search more cells layouts and loops factors, and look one character ahead, for a shorter program
+/// # Returns
+/// The brainfuck code, or an error message if a character can't be printed with the features
+pub fn generate(text: &str, features: &[Feature], optimize: bool) -> Result<String, String> {
+    let cell = Cell::default_cell(features);
+    let max_value = cell.max_value() as i64;
+    let targets = text
+        .chars()
+        .map(|c| match c as i64 {
+            value if value <= max_value => Ok(value),
+            _ => Err(format!(
+                "The character {:?} is above {}, enable the `utf8` feature to print it",
+                c, max_value
+            )),
+        })
+        .collect::<Result<Vec<i64>, String>>()?;
+
+    let mut generator = Generator {
+        max_value,
+        wraps: Cell::wraps(features),
+        max_factor: if optimize { 64 } else { 16 },
+        // Enough nested multiplications for the biggest value with the smallest factors
+        depth: if max_value > u8::MAX as i64 { 4 } else { 2 },
+        lookahead: optimize,
+        memo: HashMap::new(),
+    };
+    let (max_cells, max_counter) = if optimize { (8, 32) } else { (4, 16) };
+    let mut programs = Vec::new();
+    for cells in 1..=max_cells {
+        // Without the setup loop, all the cells start at zero
+        programs.push(generator.program(&targets, 0, &vec![0; cells - 1]));
+        for counter in 2..=max_counter {
+            let factors = setup_factors(&targets, counter, cells - 1, max_value);
+            programs.push(generator.program(&targets, counter, &factors));
+        }
+    }
+    Ok(programs
+        .into_iter()
+        .min_by_key(|code| code.len())
+        .unwrap_or_default())
+}
+
+/// The factors of the setup loop, the most common multiples of the counter near the targets,
+/// in the order they are first printed
+fn setup_factors(targets: &[i64], counter: i64, cells: usize, max_value: i64) -> Vec<i64> {
+    let multiples: Vec<i64> = targets
+        .iter()
+        .map(|target| ((target + counter / 2) / counter).min(max_value / counter))
+        .filter(|multiple| *multiple > 0)
+        .collect();
+    let mut common: Vec<i64> = Vec::new();
+    for multiple in &multiples {
+        if !common.contains(multiple) {
+            common.push(*multiple);
+        }
+    }
+    // A stable sort keeps the first printed multiples first between the equally common ones
+    common.sort_by_key(|multiple| {
+        std::cmp::Reverse(multiples.iter().filter(|m| *m == multiple).count())
+    });
+    common.truncate(cells);
+    common.sort_by_key(|multiple| multiples.iter().position(|m| m == multiple));
+    common.resize(cells, 0);
+    common
+}
+
+/// The code that sets the current cell from zero to the value, and returns to it.
+/// The cells on the right must be zero, they are used by the multiplication loops
+/// # Arguments
+/// * `value` - The value to set, up to the maximum value of the cell
+/// * `features` - The extra features to follow
+pub fn constant(value: i64, features: &[Feature]) -> String {
+    let max_value = Cell::default_cell(features).max_value() as i64;
+    let mut generator = Generator {
+        max_value,
+        wraps: Cell::wraps(features),
+        max_factor: 16,
+        depth: if max_value > u8::MAX as i64 { 4 } else { 2 },
+        lookahead: false,
+        memo: HashMap::new(),
+    };
+    generator.add(value, 1, generator.depth, max_value)
+}
+
+struct Generator {
+    max_value: i64,
+    wraps: bool,
+    /// The biggest factor to try in a multiplication loop
+    max_factor: i64,
+    /// The number of the temporary cells, the nesting of the multiplication loops
+    depth: usize,
+    /// Choose the cell by the cost of the current and the next character
+    lookahead: bool,
+    /// The shortest code of the `add` arguments
+    memo: HashMap<(i64, usize, usize, i64), String>,
+}
+
+impl Generator {
+    /// The program that prints the targets, it starts with a setup loop that runs `counter` times
+    /// and adds the factors to the cells after the counter cell, then every cell is reused
+    fn program(&mut self, targets: &[i64], counter: i64, factors: &[i64]) -> String {
+        let cells = factors.len() + 1;
+        let mut values: Vec<i64> = std::iter::once(0)
+            .chain(factors.iter().map(|factor| factor * counter))
+            .collect();
+        let mut pointer = 0;
+        let mut code = String::new();
+        if let Some(last) = factors.iter().rposition(|factor| *factor > 0) {
+            code.push_str(&"+".repeat(counter as usize));
+            code.push('[');
+            for factor in &factors[..=last] {
+                code.push('>');
+                code.push_str(&"+".repeat(*factor as usize));
+            }
+            code.push_str(&"<".repeat(last + 1));
+            code.push_str("-]");
+        } else {
+            values.fill(0);
+        }
+
+        for (index, target) in targets.iter().enumerate() {
+            let (cell, step) = (0..cells)
+                .map(|cell| {
+                    let step = self.step(&values, pointer, cell, *target);
+                    let mut cost = step.len();
+                    if self.lookahead {
+                        if let Some(next) = targets.get(index + 1) {
+                            let mut next_values = values.clone();
+                            next_values[cell] = *target;
+                            cost += (0..cells)
+                                .map(|next_cell| {
+                                    self.step(&next_values, cell, next_cell, *next).len()
+                                })
+                                .min()
+                                .unwrap_or_default();
+                        }
+                    }
+                    (cost, cell, step)
+                })
+                .min_by_key(|(cost, ..)| *cost)
+                .map(|(_, cell, step)| (cell, step))
+                .unwrap();
+            code.push_str(&step);
+            values[cell] = *target;
+            pointer = cell;
+        }
+        code
+    }
+
+    /// The code that moves from the pointer to the cell, changes its value to the target and prints it
+    fn step(&mut self, values: &[i64], pointer: usize, cell: usize, target: i64) -> String {
+        let mut code = if cell >= pointer {
+            ">".repeat(cell - pointer)
+        } else {
+            "<".repeat(pointer - cell)
+        };
+
+        let max_value = self.max_value;
+        let delta = target - values[cell];
+        let deltas = match self.wraps {
+            true => vec![delta, delta - max_value - 1, delta + max_value + 1],
+            false => vec![delta],
+        };
+        let add = deltas
+            .into_iter()
+            .filter(|delta| delta.abs() <= max_value)
+            .map(|delta| {
+                let headroom = match (self.wraps, delta >= 0) {
+                    (true, _) => i64::MAX,
+                    (false, true) => max_value - values[cell],
+                    (false, false) => values[cell],
+                };
+                self.add(delta, values.len() - cell, self.depth, headroom)
+            })
+            .min_by_key(|add| add.len())
+            .unwrap_or_default();
+        code.push_str(&add);
+        code.push('.');
+        code
+    }
+
+    /// The shortest code that adds `n` to the current cell and returns to it, with the cells after it unchanged
+    /// # Arguments
+    /// * `n` - The value to add, negative to subtract
+    /// * `distance` - The distance to the first free temporary cell on the right
+    /// * `depth` - The number of the free temporary cells
+    /// * `headroom` - How far the cell can go in the direction of `n` without going out of bounds
+    fn add(&mut self, n: i64, distance: usize, depth: usize, headroom: i64) -> String {
+        let amount = n.abs();
+        // A multiplication loop never overshoots by more than the factor
+        let key = (n, distance, depth, headroom.min(amount + self.max_factor));
+        if let Some(code) = self.memo.get(&key) {
+            return code.clone();
+        }
+
+        let (up, down) = if n >= 0 { ('+', '-') } else { ('-', '+') };
+        let mut best = up.to_string().repeat(amount as usize);
+        // The shortest loop moves to the temporary cell and back twice
+        if depth > 0 && amount > 4 * distance as i64 + 5 {
+            let (right, left) = (">".repeat(distance), "<".repeat(distance));
+            for factor in 2..=self.max_factor.min(amount) {
+                for times in [amount / factor, amount / factor + 1] {
+                    if times < 1 || times * factor > headroom {
+                        continue;
+                    }
+                    let temporary_headroom = match self.wraps {
+                        true => i64::MAX,
+                        false => self.max_value,
+                    };
+                    let rest = amount - times * factor;
+                    let code = format!(
+                        "{right}{}[{left}{}{right}-]{left}{}",
+                        self.add(times, 1, depth - 1, temporary_headroom),
+                        up.to_string().repeat(factor as usize),
+                        match rest >= 0 {
+                            true => up.to_string().repeat(rest as usize),
+                            false => down.to_string().repeat(-rest as usize),
+                        }
+                    );
+                    if code.len() < best.len() {
+                        best = code;
+                    }
+                }
+            }
+        }
+
+        self.memo.insert(key, best.clone());
+        best
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arguments::DEFAULT_ARRAY_SIZE;
+    use crate::bf_interpreter::interpreter::Interpreter;
+    use console::Term;
+    use pretty_assertions::assert_eq;
+
+    /// Run the program without input, the out of bounds pointer and values fail the interpreter
+    fn run(code: &str, features: &[Feature]) -> String {
+        let mut interpreter =
+            Interpreter::new(DEFAULT_ARRAY_SIZE, features.to_vec(), Term::stdout());
+        interpreter.input = Some(Default::default());
+        interpreter.output = Some(Vec::new());
+        assert_eq!(interpreter.run(code.to_string()), Ok(0));
+        String::from_utf8(interpreter.output.unwrap()).unwrap()
+    }
+
+    #[test]
+    fn print_the_text() {
+        for optimize in [false, true] {
+            for text in ["Hello, World!\n", "hi", "", "\u{0}\u{ff}\u{1}~"] {
+                let code = generate(text, &[], optimize).unwrap();
+                assert_eq!(run(&code, &[]), text);
+            }
+        }
+        // Compact enough, with the multiplication loops
+        let code = generate("Hello, World!\n", &[], false).unwrap();
+        assert!(code.len() < 200 && code.contains('['), "{}", code);
+        assert!(generate("Hello, World!\n", &[], true).unwrap().len() <= code.len());
+    }
+
+    #[test]
+    fn wrapping_values() {
+        let features = [Feature::NoReverseValue];
+        let code = generate("\u{ff}", &features, false).unwrap();
+        assert_eq!(code, "-.");
+        assert_eq!(run(&code, &features), "\u{ff}");
+    }
+
+    #[test]
+    fn utf8_text() {
+        assert!(generate("🦀", &[], false).is_err());
+        let features = [Feature::AllowUtf8];
+        for text in ["🦀 crab", "مرحبا", "\u{10ffff}"] {
+            let code = generate(text, &features, false).unwrap();
+            assert!(code.len() < 400, "{}", code);
+            assert_eq!(run(&code, &features), text);
+        }
+    }
+}
@@ -0,0 +1,328 @@
+use crate::arguments::Feature;
+use crate::header;
+use crate::tools::diagnostic::{self, Diagnostic, Level, Node, Position, Token};
+
+// The lints:
+// L001: cancelling pair, e.g. `+-` or `<>`
+// L002: loop that never runs, because the cell is always zero there
+// L003: counter loop that moves the pointer on every iteration
+// L004: loop that never changes its cell, a guaranteed infinite loop
+// L005: char that is probably a typo of a command, e.g. `=` or `{`
+
+/// The comment that suppresses the lints on its line and on the next line, e.g. `bfy:allow(L001 L004)`
+const ALLOW_PRAGMA: &str = "bfy:allow(";
+
+/// Lint the brainfuck source code, the brackets must be balanced
+/// # Arguments
+/// * `source` - The brainfuck source code, with the comments
+/// * `allow` - The codes of the lints to suppress in all the source code
+/// * `features` - The extra features, their commands are not typos
+pub fn lint(source: &str, allow: &[String], features: &[Feature]) -> Vec<Diagnostic> {
+    let tokens = diagnostic::tokens(source);
+    let mut linter = Linter {
+        tokens: &tokens,
+        diagnostics: Vec::new(),
+    };
+    linter.cancelling_pairs(&feature_commands(source, features));
+    linter.block(&diagnostic::nodes(&tokens), true);
+    linter.typos(source, features);
+
+    let allowed_lines = allowed_lines(source);
+    let mut diagnostics: Vec<Diagnostic> = linter
+        .diagnostics
+        .into_iter()
+        .filter(|d| !allow.iter().any(|code| code == d.code))
+        .filter(|d| {
+            !allowed_lines.iter().any(|(line, codes)| {
+                (*line..=line + 1).contains(&d.start.line) && codes.contains(&d.code.to_string())
+            })
+        })
+        .collect();
+    diagnostics.sort_by_key(|d| (d.start.line, d.start.column));
+    diagnostics
+}
+
+/// The line and the column of the commands of the features, they are not comments between the brainfuck commands
+fn feature_commands(source: &str, features: &[Feature]) -> Vec<(usize, usize)> {
+    source
+        .lines()
+        .enumerate()
+        .skip(header::header_lines(source))
+        .flat_map(|(line_index, line)| {
+            line.chars()
+                .enumerate()
+                .filter(|(_, c)| {
+                    features
+                        .iter()
+                        .any(|feature| feature.commands().contains(*c))
+                })
+                .map(move |(column_index, _)| (line_index + 1, column_index + 1))
+        })
+        .collect()
+}
+
+/// The lines with the allow pragma and the codes they allow
+fn allowed_lines(source: &str) -> Vec<(usize, Vec<String>)> {
+    source
+        .lines()
+        .enumerate()
+        .filter_map(|(index, line)| {
+            let start = line.find(ALLOW_PRAGMA)? + ALLOW_PRAGMA.len();
+            let end = start + line[start..].find(')')?;
+            Some((
+                index + 1,
+                line[start..end]
+                    .split_whitespace()
+                    .map(String::from)
+                    .collect(),
+            ))
+        })
+        .collect()
+}
+
+struct Linter<'a> {
+    tokens: &'a [Token],
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Linter<'_> {
+    fn push(&mut self, code: &'static str, message: String, start: usize, end: usize) {
+        self.diagnostics.push(Diagnostic {
+            level: Level::Warning,
+            code,
+            message,
+            start: self.tokens[start].position,
+            end: self.tokens[end].position,
+        });
+    }
+
+    /// L001: two adjacent commands that cancel each other, without a command of the features between them
+    fn cancelling_pairs(&mut self, feature_commands: &[(usize, usize)]) {
+        let mut index = 0;
+        while index + 1 < self.tokens.len() {
+            let pair = [self.tokens[index].command, self.tokens[index + 1].command];
+            let (start, end) = (self.tokens[index].position, self.tokens[index + 1].position);
+            let separated = feature_commands.iter().any(|command| {
+                (start.line, start.column) < *command && *command < (end.line, end.column)
+            });
+            if !separated && matches!(pair, ['+', '-'] | ['-', '+'] | ['<', '>'] | ['>', '<']) {
+                self.push(
+                    "L001",
+                    format!("`{}{}` cancel each other", pair[0], pair[1]),
+                    index,
+                    index + 1,
+                );
+                index += 2;
+            } else {
+                index += 1;
+            }
+        }
+    }
+
+    /// L002, L003 and L004, `all_zero` is true when all the cells are known to be zero at the block start
+    fn block(&mut self, nodes: &[Node], mut all_zero: bool) {
+        // The current cell is zero at the start of the program and after every loop
+        let mut current_zero = all_zero;
+        for node in nodes {
+            match node {
+                Node::Command(index) => match self.tokens[*index].command {
+                    '<' | '>' => current_zero = all_zero,
+                    '.' => {}
+                    _ => {
+                        all_zero = false;
+                        current_zero = false;
+                    }
+                },
+                Node::Loop { open, close, body } => {
+                    if current_zero {
+                        self.push(
+                            "L002",
+                            "This loop never runs, the cell is always zero here".to_string(),
+                            *open,
+                            *close,
+                        );
+                    } else {
+                        self.simple_loop(*open, *close, body);
+                        self.block(body, false);
+                    }
+                    all_zero = false;
+                    current_zero = true;
+                }
+            }
+        }
+    }
+
+    /// L003 and L004 for the loops without nested loops and without input,
+    /// a counter loop changes its cell on every iteration
+    fn simple_loop(&mut self, open: usize, close: usize, body: &[Node]) {
+        let mut offset: isize = 0;
+        let mut loop_cell_change = 0;
+        for node in body {
+            let Node::Command(index) = node else {
+                return;
+            };
+            match self.tokens[*index].command {
+                '>' => offset += 1,
+                '<' => offset -= 1,
+                '+' | '-' => {
+                    let change = if self.tokens[*index].command == '+' {
+                        1
+                    } else {
+                        -1
+                    };
+                    if offset == 0 {
+                        loop_cell_change += change;
+                    }
+                }
+                ',' => return,
+                _ => {}
+            }
+        }
+
+        if offset == 0 && loop_cell_change == 0 {
+            self.push(
+                "L004",
+                "This loop never changes its cell, it never ends once it starts".to_string(),
+                open,
+                close,
+            );
+        } else if offset != 0 && loop_cell_change != 0 {
+            self.push(
+                "L003",
+                format!(
+                    "This loop looks like a counter but it moves the pointer by {} on every iteration",
+                    offset
+                ),
+                open,
+                close,
+            );
+        }
+    }
+
+    /// L005: the chars next to the code that look like a command, the header lines are not code
+    /// and the commands of the features are not typos
+    fn typos(&mut self, source: &str, features: &[Feature]) {
+        for (line_index, line) in header::strip(source).lines().enumerate() {
+            let chars: Vec<char> = line.chars().collect();
+            for (column_index, c) in chars.iter().enumerate() {
+                if features
+                    .iter()
+                    .any(|feature| feature.commands().contains(*c))
+                {
+                    continue;
+                }
+                let command = match c {
+                    '{' => "[",
+                    '}' => "]",
+                    '=' => "+` or `-",
+                    '_' | '~' => "-",
+                    ';' => ",",
+                    _ => continue,
+                };
+                let is_code = |index: Option<usize>| {
+                    index
+                        .and_then(|index| chars.get(index))
+                        .is_some_and(|c| crate::utils::is_command(*c))
+                };
+                if !is_code(column_index.checked_sub(1)) && !is_code(Some(column_index + 1)) {
+                    continue;
+                }
+                let position = Position {
+                    line: line_index + 1,
+                    column: column_index + 1,
+                };
+                self.diagnostics.push(Diagnostic {
+                    level: Level::Warning,
+                    code: "L005",
+                    message: format!("`{}` is a comment, did you mean `{}`?", c, command),
+                    start: position,
+                    end: position,
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn codes(source: &str) -> Vec<(&'static str, String)> {
+        codes_with(source, &[])
+    }
+
+    fn codes_with(source: &str, features: &[Feature]) -> Vec<(&'static str, String)> {
+        lint(source, &[], features)
+            .into_iter()
+            .map(|d| (d.code, d.start.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn cancelling_pairs_and_typos() {
+        assert_eq!(
+            codes("+++-\n><>.\n++={+}"),
+            vec![
+                ("L001", "1:3".to_string()),
+                ("L001", "2:1".to_string()),
+                ("L005", "3:3".to_string()),
+                ("L005", "3:4".to_string()),
+                ("L005", "3:6".to_string()),
+            ]
+        );
+        // Only the chars next to the code
+        assert!(codes("a = b { c }").is_empty());
+        // Not the options of the header
+        assert_eq!(
+            codes("#!/usr/bin/env bfy\nbfy: array-size=+10\n+={+}"),
+            vec![
+                ("L005", "3:2".to_string()),
+                ("L005", "3:3".to_string()),
+                ("L005", "3:5".to_string()),
+            ]
+        );
+        // Not the commands of the features
+        assert_eq!(
+            codes_with("+{>+~<}-", &[Feature::ExtendedType1]),
+            Vec::<(&'static str, String)>::new()
+        );
+        assert_eq!(
+            codes_with("+%>_<;>=", &[Feature::FileIo]),
+            Vec::<(&'static str, String)>::new()
+        );
+        assert_eq!(
+            codes_with("+{>+~<}=", &[Feature::FileIo]),
+            vec![
+                ("L005", "1:2".to_string()),
+                ("L005", "1:5".to_string()),
+                ("L005", "1:7".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn loops() {
+        assert_eq!(
+            codes("[comment]+[>+<]\n[.]+[.]+[->+>]+[->+<]"),
+            vec![
+                ("L002", "1:1".to_string()),
+                ("L004", "1:11".to_string()),
+                ("L002", "2:1".to_string()),
+                ("L004", "2:5".to_string()),
+                ("L003", "2:9".to_string()),
+            ]
+        );
+        // Not a counter without changing the loop cell, and not infinite with an input
+        assert!(codes("+[>]+[>+]+[,]").is_empty());
+    }
+
+    #[test]
+    fn suppress_the_lints() {
+        assert_eq!(
+            codes("bfy:allow(L001 L002)\n[comment]+-\n+-"),
+            vec![("L001", "3:1".to_string())]
+        );
+        assert!(lint("+-\n><", &["L001".to_string()], &[]).is_empty());
+    }
+}
@@ -0,0 +1,178 @@
+use crate::arguments::Feature;
+use crate::bf_interpreter::cell::Cell;
+use crate::bf_interpreter::interpreter::BfCommand;
+
+/// Minify the brainfuck commands to the shortest equivalent program on one line.
+/// The runs of `+-` and `<>` are cancelled only when they wrap (like the optimiser does),
+/// and the loops that can never run (at the program start or right after another loop) are removed
+/// # Arguments
+/// * `bf_commands` - The parsed brainfuck program, without the comments
+/// * `array_size` - The brainfuck array size, the pointer runs wrap around it with `rp`
+/// * `features` - The extra features to follow
+pub fn minify(bf_commands: &[BfCommand], array_size: usize, features: &[Feature]) -> String {
+    let mut minifier = Minifier {
+        code: String::new(),
+        run: Vec::new(),
+        cell_modulus: Cell::default_cell(features).max_value() as usize + 1,
+        array_size,
+        wrap_value: Cell::wraps(features),
+        wrap_pointer: features.contains(&Feature::ReversePointer),
+    };
+    minifier.block(bf_commands, true);
+    minifier.flush_run();
+    minifier.code
+}
+
+struct Minifier {
+    code: String,
+    /// The run of `+-` or `<>` commands that is not written yet
+    run: Vec<char>,
+    cell_modulus: usize,
+    array_size: usize,
+    wrap_value: bool,
+    wrap_pointer: bool,
+}
+
+impl Minifier {
+    /// Minify the block, `all_zero` is true when all the cells are known to be zero at its start
+    fn block(&mut self, bf_commands: &[BfCommand], mut all_zero: bool) {
+        // The current cell is zero at the start of the program and after every loop
+        let mut current_zero = all_zero;
+        for command in bf_commands {
+            match command {
+                BfCommand::IncVal | BfCommand::DecVal => {
+                    self.push_run(if *command == BfCommand::IncVal {
+                        '+'
+                    } else {
+                        '-'
+                    });
+                    all_zero = false;
+                    current_zero = false;
+                }
+                BfCommand::IncPtr | BfCommand::DecPtr => {
+                    self.push_run(if *command == BfCommand::IncPtr {
+                        '>'
+                    } else {
+                        '<'
+                    });
+                    current_zero = all_zero;
+                }
+                BfCommand::Print => {
+                    self.flush_run();
+                    self.code.push('.');
+                }
+                BfCommand::Read => {
+                    self.flush_run();
+                    self.code.push(',');
+                    all_zero = false;
+                    current_zero = false;
+                }
+                BfCommand::Loop(loop_body) => {
+                    if current_zero {
+                        continue;
+                    }
+                    self.flush_run();
+                    self.code.push('[');
+                    self.block(loop_body, false);
+                    self.flush_run();
+                    self.code.push(']');
+                    all_zero = false;
+                    current_zero = true;
+                }
+                BfCommand::Procedure(_)
+                | BfCommand::Call
+                | BfCommand::Fork
+                | BfCommand::Extended(_)
+                | BfCommand::File(_)
+                | BfCommand::Debug => {
+                    unreachable!(
+                        "The extra commands of the features are only parsed for the interpreter"
+                    )
+                }
+            }
+        }
+    }
+
+    fn push_run(&mut self, command: char) {
+        let is_value = |c: char| matches!(c, '+' | '-');
+        if self
+            .run
+            .last()
+            .is_some_and(|last| is_value(*last) != is_value(command))
+        {
+            self.flush_run();
+        }
+        self.run.push(command);
+    }
+
+    /// Write the run, folded to the shorter direction when it wraps
+    fn flush_run(&mut self) {
+        let run = std::mem::take(&mut self.run);
+        let Some(first) = run.first() else {
+            return;
+        };
+        let (up, down, modulus, wraps) = if matches!(first, '+' | '-') {
+            ('+', '-', self.cell_modulus, self.wrap_value)
+        } else {
+            ('>', '<', self.array_size, self.wrap_pointer)
+        };
+        if !wraps {
+            self.code.extend(run);
+            return;
+        }
+
+        let net = run
+            .iter()
+            .map(|c| if *c == up { 1 } else { -1 })
+            .sum::<isize>()
+            .rem_euclid(modulus as isize) as usize;
+        if net <= modulus - net {
+            self.code.extend(std::iter::repeat_n(up, net));
+        } else {
+            self.code.extend(std::iter::repeat_n(down, modulus - net));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bf_interpreter::interpreter::to_bf_commands;
+    use pretty_assertions::assert_eq;
+
+    fn minify_code(code: &str, features: &[Feature]) -> String {
+        minify(
+            &to_bf_commands(code.chars().collect()).unwrap(),
+            30000,
+            features,
+        )
+    }
+
+    #[test]
+    fn cancel_the_runs_only_when_they_wrap() {
+        assert_eq!(minify_code("+++--><<.", &[]), "+++--><<.");
+        assert_eq!(
+            minify_code("+++--><<.", &[Feature::NoReverseValue]),
+            "+><<."
+        );
+        assert_eq!(
+            minify_code(
+                "+-+-><>.<",
+                &[Feature::NoReverseValue, Feature::ReversePointer]
+            ),
+            ">.<"
+        );
+        assert_eq!(
+            minify_code(&"+".repeat(250), &[Feature::NoReverseValue]),
+            "------"
+        );
+    }
+
+    #[test]
+    fn remove_the_dead_loops() {
+        assert_eq!(minify_code("[-]>[.]+[->+<][<]>.", &[]), ">+[->+<]>.");
+        // The loop body doesn't start with a known zero cell
+        assert_eq!(minify_code("+[[-]]", &[]), "+[[-]]");
+        assert_eq!(minify_code(",[.,][.]", &[]), ",[.,]");
+    }
+}
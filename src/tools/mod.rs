@@ -0,0 +1,227 @@
+pub mod analyzer;
+pub mod diagnostic;
+pub mod formatter;
+pub mod generator;
+pub mod linter;
+pub mod minifier;
+
+use crate::arguments::{Feature, Lang};
+use crate::bf_interpreter::interpreter::to_bf_commands;
+use crate::dialects::{self, Dialect};
+use crate::preprocessor::SourceMap;
+use crate::tools::diagnostic::{Diagnostic, Level};
+use crate::utils;
+
+/// Format the brainfuck source code file
+/// # Arguments
+/// * `source` - The brainfuck source code file
+/// * `output` - The file to write the formatted code to, if `None` it will be printed to stdout
+/// * `check` - Don't write the formatted code, exit with code 1 if the file is not formatted
+pub fn format_file(source: &String, output: Option<&String>, check: bool) {
+    info!("Formatting brainfuck source code from file: {}", source);
+    if utils::is_macro_file(source) {
+        error!(
+            "Formatting the macro source files is not supported: {}",
+            source
+        );
+        eprintln!(
+            "Formatting the macro source files is not supported: {}",
+            source
+        );
+        std::process::exit(1);
+    }
+    let lang = dialects::detect(source);
+    if lang != Lang::Brainfuck {
+        error!(
+            "Formatting the {:?} source files is not supported: {}",
+            lang, source
+        );
+        eprintln!(
+            "Formatting the {:?} source files is not supported: {}",
+            lang, source
+        );
+        std::process::exit(1);
+    }
+    let code = utils::read_source_file(source);
+    if let Err(e) = to_bf_commands(code.chars().filter(|c| utils::is_command(*c)).collect()) {
+        error!("Failed to format brainfuck source code from file: {}", e);
+        eprintln!("Failed to format brainfuck source code from file: {}", e);
+        std::process::exit(e.code);
+    }
+
+    let formatted = formatter::format(&code);
+    if check {
+        if formatted != code {
+            eprintln!("The file is not formatted: {}", source);
+            std::process::exit(1);
+        }
+        info!("The file is formatted: {}", source);
+        return;
+    }
+    utils::write_output(formatted.as_bytes(), output);
+}
+
+/// Minify the brainfuck source code file
+/// # Arguments
+/// * `source` - The brainfuck source code file
+/// * `output` - The file to write the minified code to, if `None` it will be printed to stdout
+/// * `array_size` - The brainfuck array size
+/// * `features` - The extra features to follow
+pub fn minify_file(
+    source: &String,
+    output: Option<&String>,
+    array_size: usize,
+    features: &[Feature],
+) {
+    info!("Minifying brainfuck source code from file: {}", source);
+    utils::require_standard_commands(features, "minified");
+    let bf_commands = match to_bf_commands(utils::read_brainfuck_code(source).chars().collect()) {
+        Ok(bf_commands) => bf_commands,
+        Err(e) => {
+            error!("Failed to minify brainfuck source code from file: {}", e);
+            eprintln!("Failed to minify brainfuck source code from file: {}", e);
+            std::process::exit(e.code);
+        }
+    };
+
+    let mut minified = minifier::minify(&bf_commands, array_size, features);
+    minified.push('\n');
+    utils::write_output(minified.as_bytes(), output);
+}
+
+/// Generate a brainfuck program that prints the text
+/// # Arguments
+/// * `text` - The text to print
+/// * `output` - The file to write the generated code to, if `None` it will be printed to stdout
+/// * `features` - The extra features to follow
+/// * `optimize` - Search harder for a shorter program
+pub fn generate_text(text: &str, output: Option<&String>, features: &[Feature], optimize: bool) {
+    info!("Generating brainfuck source code that prints: {:?}", text);
+    match generator::generate(text, features, optimize) {
+        Ok(mut code) => {
+            code.push('\n');
+            utils::write_output(code.as_bytes(), output);
+        }
+        Err(e) => {
+            error!("Failed to generate brainfuck source code: {}", e);
+            eprintln!("Failed to generate brainfuck source code: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Lint the brainfuck source code file, print the warnings and exit with code 1 if there are any
+/// # Arguments
+/// * `source` - The brainfuck source code file
+/// * `allow` - The codes of the lints to suppress
+/// * `features` - The extra features, their commands are not typos
+pub fn lint_file(source: &String, allow: &[String], features: &[Feature]) {
+    info!("Linting brainfuck source code from file: {}", source);
+    let (code, source_map) = read_diagnostics_source(source, &dialects::detect(source).into());
+    if let Err(e) = to_bf_commands(code.chars().filter(|c| utils::is_command(*c)).collect()) {
+        error!("Failed to lint brainfuck source code from file: {}", e);
+        eprintln!("Failed to lint brainfuck source code from file: {}", e);
+        std::process::exit(e.code);
+    }
+
+    let diagnostics = linter::lint(&code, allow, features);
+    print_diagnostics(source, &code, source_map.as_ref(), &diagnostics);
+    if !diagnostics.is_empty() {
+        eprintln!("{} warning(s) in {}", diagnostics.len(), source);
+        std::process::exit(1);
+    }
+}
+
+/// Analyse the brainfuck source code file, print the diagnostics and exit with code 1 if there are errors
+/// # Arguments
+/// * `source` - The brainfuck source code file
+/// * `array_size` - The brainfuck array size
+/// * `features` - The extra features to follow
+pub fn analyze_file(source: &String, array_size: usize, features: &[Feature]) {
+    info!("Analysing brainfuck source code from file: {}", source);
+    if !check_file(
+        source,
+        &dialects::detect(source).into(),
+        array_size,
+        features,
+    ) {
+        std::process::exit(1);
+    }
+}
+
+/// Analyse the source code file and print the diagnostics
+/// # Arguments
+/// * `source` - The source code file
+/// * `dialect` - The dialect of the source code file
+/// * `array_size` - The brainfuck array size
+/// * `features` - The extra features to follow
+/// # Returns
+/// `false` if the source code has definite errors
+pub fn check_file(
+    source: &String,
+    dialect: &Dialect,
+    array_size: usize,
+    features: &[Feature],
+) -> bool {
+    utils::require_standard_commands(features, "analysed");
+    let (code, source_map) = read_diagnostics_source(source, dialect);
+    if let Err(e) = to_bf_commands(code.chars().filter(|c| utils::is_command(*c)).collect()) {
+        error!("Failed to analyse brainfuck source code from file: {}", e);
+        eprintln!("Failed to analyse brainfuck source code from file: {}", e);
+        std::process::exit(e.code);
+    }
+
+    let diagnostics = analyzer::analyze(&code, array_size, features);
+    print_diagnostics(source, &code, source_map.as_ref(), &diagnostics);
+    !diagnostics.iter().any(|d| d.level == Level::Error)
+}
+
+/// Read the source code file for the diagnostics,
+/// the macro source files and the dialects are translated to one line of commands
+fn read_diagnostics_source(source: &String, dialect: &Dialect) -> (String, Option<SourceMap>) {
+    if *dialect != Dialect::Lang(Lang::Brainfuck) {
+        let translated = utils::translate_file(source, dialect);
+        let source_map = SourceMap::new(
+            source,
+            &utils::read_source_file(source),
+            &translated.locations,
+        );
+        (translated.code, Some(source_map))
+    } else if utils::is_macro_file(source) {
+        let preprocessed = utils::expand_macro_file(source);
+        (preprocessed.code, Some(preprocessed.source_map))
+    } else {
+        (utils::read_source_file(source), None)
+    }
+}
+
+/// Print the diagnostics, the positions in the translated code are mapped back to the source files
+fn print_diagnostics(
+    source: &str,
+    code: &str,
+    source_map: Option<&SourceMap>,
+    diagnostics: &[Diagnostic],
+) {
+    let Some(source_map) = source_map else {
+        for diagnostic in diagnostics {
+            diagnostic.print(source, code);
+        }
+        return;
+    };
+    for diagnostic in diagnostics {
+        // The expanded code is one line, so the column is the index of the command
+        let Some((file, start)) = source_map.location(diagnostic.start.column - 1) else {
+            continue;
+        };
+        let end = match source_map.location(diagnostic.end.column - 1) {
+            Some((end_file, end)) if end_file == file && end.line == start.line => end,
+            _ => start,
+        };
+        let mapped = Diagnostic {
+            start,
+            end,
+            ..diagnostic.clone()
+        };
+        mapped.print(file, &utils::read_source_file(&file.to_string()));
+    }
+}
@@ -1,10 +1,43 @@
+use crate::arguments::{Feature, Lang};
+use crate::config::{self, Config};
+use crate::dialects::{self, mapping, Dialect, Translated};
+use crate::header::{self, Header};
+use crate::preprocessor::{self, Preprocessed};
+use clap::ValueEnum;
+use std::io::Write;
+
+/// Read the brainfuck code of the source file, in the language of its extension
 pub fn read_brainfuck_code(source: &String) -> String {
+    read_code(source, &dialects::detect(source).into(), &[])
+}
+
+/// Read the source file in the dialect, and translate it to brainfuck code without comments
+/// # Arguments
+/// * `source` - The source code file
+/// * `dialect` - The dialect of the source code file
+/// * `features` - The features, their extra commands are kept (e.g. the pbrain procedures)
+pub fn read_code(source: &String, dialect: &Dialect, features: &[Feature]) -> String {
+    let code = match dialect {
+        Dialect::Lang(Lang::Brainfuck) if is_macro_file(source) => expand_macro_file(source).code,
+        Dialect::Lang(Lang::Brainfuck) => read_source_file(source),
+        _ => translate_file(source, dialect).code,
+    };
+    clean(header::strip(&code), features).unwrap_or_else(|| {
+        error!("The source code is empty");
+        std::process::exit(2);
+    })
+}
+
+/// Read the source file of a bit tape language without its header, the interpreter runs it on the bit cells
+pub fn read_bit_code(source: &String) -> String {
+    header::strip(&read_source_file(source))
+}
+
+/// Read the source code file as it is, with the comments
+pub fn read_source_file(source: &String) -> String {
     info!("Reading brainfuck source code from file: {}", source);
     match std::fs::read_to_string(source) {
-        Ok(source) => clean(source).unwrap_or_else(|| {
-            error!("The source code is empty");
-            std::process::exit(2);
-        }),
+        Ok(source) => source,
         Err(e) => {
             error!("Failed to read source code file: {}", e);
             eprintln!("Failed to read source code file: {}", e);
@@ -13,19 +46,155 @@ pub fn read_brainfuck_code(source: &String) -> String {
     }
 }
 
-fn clean(source: String) -> Option<String> {
+/// Read the interpreter options from the source file header, or exit if they are invalid
+pub fn read_header(source: &String) -> Header {
+    match header::parse(&read_source_file(source)) {
+        Ok(header) => {
+            info!("Parsed the source code file header: {:?}", header);
+            header
+        }
+        Err(e) => {
+            error!("Failed to parse the source code file header: {}", e);
+            eprintln!("Failed to parse the source code file header: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Read the defaults from the config files, or exit if they are invalid
+pub fn read_config() -> Config {
+    match config::load() {
+        Ok(config) => {
+            info!("Loaded the config: {:?}", config);
+            config
+        }
+        Err(e) => {
+            error!("Failed to load the config: {}", e);
+            eprintln!("Failed to load the config: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// The macro source files (`.bfm`) are expanded by the preprocessor before they are cleaned
+pub fn is_macro_file(source: &str) -> bool {
+    source.ends_with(".bfm")
+}
+
+/// Expand the macros of the source file, or exit if it fails
+pub fn expand_macro_file(source: &String) -> Preprocessed {
+    info!("Expanding the macros of the source code file: {}", source);
+    match preprocessor::expand_file(source) {
+        Ok(preprocessed) => preprocessed,
+        Err(e) => {
+            error!("Failed to expand the macros: {}", e);
+            eprintln!("Failed to expand the macros: {}", e);
+            std::process::exit(e.code);
+        }
+    }
+}
+
+/// Translate the source file of the dialect to brainfuck, or exit if it is invalid
+pub fn translate_file(source: &String, dialect: &Dialect) -> Translated {
+    info!(
+        "Translating the {} source code from file: {}",
+        dialect, source
+    );
+    match dialects::to_brainfuck(dialect, &header::strip(&read_source_file(source))) {
+        Ok(translated) => translated,
+        Err(e) => {
+            error!(
+                "Failed to translate the {} source code: {}:{}",
+                dialect, source, e
+            );
+            eprintln!(
+                "Failed to translate the {} source code: {}:{}",
+                dialect, source, e
+            );
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Read the dialect definition file, or exit if it is invalid
+pub fn read_dialect(file: &String) -> Dialect {
+    info!("Reading the dialect definition file: {}", file);
+    let name = std::path::Path::new(file)
+        .file_stem()
+        .map_or(file.clone(), |stem| stem.to_string_lossy().to_string());
+    match mapping::parse(&name, &read_source_file(file)) {
+        Ok(mapping) => Dialect::Mapping(mapping),
+        Err(e) => {
+            error!("Invalid dialect definition file: {}: {}", file, e);
+            eprintln!("Invalid dialect definition file: {}: {}", file, e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Write the generated code to the output file, or to stdout if `None`
+pub fn write_output(code: &[u8], output: Option<&String>) {
+    match output {
+        Some(output) => {
+            info!("Writing the generated code to file: {}", output);
+            if let Err(e) = std::fs::write(output, code) {
+                error!("Failed to write the generated code to file: {}", e);
+                eprintln!("Failed to write the generated code to file: {}", e);
+                std::process::exit(1);
+            }
+        }
+        None => {
+            if let Err(e) = std::io::stdout().write_all(code) {
+                error!("Failed to write the generated code to stdout: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+fn clean(source: String, features: &[Feature]) -> Option<String> {
     if source.is_empty() {
         return None;
     }
     let code: String = source
         .chars()
-        .filter(|c| match c {
-            '+' | '-' | '<' | '>' | '[' | ']' | '.' | ',' => true,
-            _ => false,
-        })
+        .filter(|c| is_command(*c) || is_feature_command(*c, features))
         .collect();
     if code.is_empty() {
         return None;
     }
     Some(code)
 }
+
+/// Check if the char is one of the brainfuck commands, the other chars are comments
+pub fn is_command(c: char) -> bool {
+    matches!(c, '+' | '-' | '<' | '>' | '[' | ']' | '.' | ',')
+}
+
+/// Check if the char is one of the extra commands of the features
+pub fn is_feature_command(c: char, features: &[Feature]) -> bool {
+    features
+        .iter()
+        .any(|feature| feature.commands().contains(c))
+}
+
+/// Exit if one of the features has extra commands, only the interpreter runs them
+/// # Arguments
+/// * `features` - The features to follow
+/// * `action` - What is done to the code, e.g. `compiled`
+pub fn require_standard_commands(features: &[Feature], action: &str) {
+    if let Some(feature) = features.iter().find(|f| !f.commands().is_empty()) {
+        let name = feature
+            .to_possible_value()
+            .map_or(String::new(), |value| value.get_name().to_string());
+        error!(
+            "The `{}` feature is only supported by the interpreter, the code can't be {}",
+            name, action
+        );
+        eprintln!(
+            "The `{}` feature is only supported by the interpreter, the code can't be {}",
+            name, action
+        );
+        std::process::exit(1);
+    }
+}
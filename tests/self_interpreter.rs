@@ -0,0 +1,115 @@
+use pretty_assertions::assert_eq;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+// The sample programs are run by the brainfuck self-interpreter (dbfi) under bfy, it reads
+// the program and its input from stdin separated by `!`, and the output must be the same
+// as the output of the program run directly by bfy with the same input
+
+const SELF_INTERPRETER: &str = "test_code/dbfi.bf";
+
+/// The samples and their input, the slow and the endless samples are not run through the self-interpreter
+const SAMPLES: [(&str, &str); 13] = [
+    ("hello_world.bf", ""),
+    ("like_cat.bf", "Hello, cat\n"),
+    ("print_7_formatted.bf", ""),
+    ("print_hello_world_2.bf", ""),
+    ("print_hello_world_2_formated.bf", ""),
+    ("print_hi.bf", ""),
+    ("print_hi_yooo.bf", ""),
+    ("print_my_first_name.bf", ""),
+    ("print_my_first_name_formatted.bf", ""),
+    ("print_my_first_name_and_last_name.bf", ""),
+    ("print_my_first_name_and_last_name_formated.bf", ""),
+    ("print_my_first_name_and_last_name_formatted.bf", ""),
+    ("read_name_and_print_hi.bf", "Anas\n"),
+];
+
+/// An empty directory for the test, bfy runs in it with it as the home directory,
+/// so the user and the project config files don't change the results
+fn test_directory(test: &str) -> PathBuf {
+    let directory = std::env::temp_dir().join(format!("bfy_{}_{}", test, std::process::id()));
+    std::fs::create_dir_all(&directory).expect("Failed to create the test directory");
+    directory
+}
+
+/// Run the source file by bfy, the end of the input is 0 for both the self-interpreter and the samples
+/// # Arguments
+/// * `directory` - The test directory, see `test_directory`
+/// * `source` - The source code file, relative to the crate
+/// * `input` - The bytes written to the standard input
+fn run_bfy(directory: &Path, source: &str, input: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_bfy"))
+        .arg(Path::new(env!("CARGO_MANIFEST_DIR")).join(source))
+        .args(["--without-tiles", "--eof", "zero"])
+        .current_dir(directory)
+        .env("HOME", directory)
+        .env("XDG_CONFIG_HOME", directory.join(".config"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to run bfy");
+    child
+        .stdin
+        .take()
+        .expect("The stdin is piped")
+        .write_all(input)
+        .expect("Failed to write the input");
+    child.wait_with_output().expect("Failed to wait for bfy")
+}
+
+/// The program and its input for the self-interpreter, only the commands are kept
+/// so the comments can't end the program with a `!`
+fn self_interpreter_input(code: &str, input: &str) -> Vec<u8> {
+    let program = code
+        .chars()
+        .filter(|c| "><+-.,[]".contains(*c))
+        .collect::<String>();
+    format!("{}!{}", program, input).into_bytes()
+}
+
+#[test]
+fn run_the_samples_through_the_self_interpreter() {
+    let directory = test_directory("samples");
+    for (sample, input) in SAMPLES {
+        let source = format!("test_code/{}", sample);
+        let direct = run_bfy(&directory, &source, input.as_bytes());
+        assert!(direct.status.success(), "{}: {:?}", sample, direct);
+        assert!(!direct.stdout.is_empty(), "{} prints nothing", sample);
+
+        let code = std::fs::read_to_string(&source).unwrap();
+        let interpreted = run_bfy(
+            &directory,
+            SELF_INTERPRETER,
+            &self_interpreter_input(&code, input),
+        );
+        assert!(
+            interpreted.status.success(),
+            "{}: {:?}",
+            sample,
+            interpreted
+        );
+        assert_eq!(
+            String::from_utf8_lossy(&interpreted.stdout),
+            String::from_utf8_lossy(&direct.stdout),
+            "{}",
+            sample
+        );
+    }
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn run_the_self_interpreter_through_itself() {
+    // The inner self-interpreter reads the program and its input after the outer `!`
+    let self_interpreter = std::fs::read_to_string(SELF_INTERPRETER).unwrap();
+    let inner = self_interpreter_input(",+.", "A");
+    let input = self_interpreter_input(&self_interpreter, &String::from_utf8(inner).unwrap());
+    let directory = test_directory("self_interpreter");
+    let output = run_bfy(&directory, SELF_INTERPRETER, &input);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "B");
+    std::fs::remove_dir_all(&directory).unwrap();
+}